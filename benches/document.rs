@@ -0,0 +1,213 @@
+// Benchmarks for document queries and mutations on large documents. Each
+// indexed operation is measured next to the linear scan that the document
+// used before it had a spatial index and a keyed z-order, so that the gain
+// shows up side by side in the report.
+//
+// Run with: cargo bench
+
+use criterion::{BatchSize, Criterion, black_box, criterion_group, criterion_main};
+use sauron_shapes::shapes::color::Color;
+use sauron_shapes::shapes::core::{Bounds, Geometry, Shape, Style, XYPoint};
+use sauron_shapes::shapes::doc::{Document, ShapeId};
+use sauron_shapes::shapes::history::History;
+
+const SHAPE_COUNT: usize = 20_000;
+const GRID_COLUMNS: usize = 200;
+const CELL_SIZE: f64 = 50.0;
+
+// A grid of small overlapping rectangles, like a busy diagram.
+fn large_document() -> Document {
+    let shapes: Vec<Shape> = (0..SHAPE_COUNT)
+        .map(|index| {
+            let column = (index % GRID_COLUMNS) as f64;
+            let row = (index / GRID_COLUMNS) as f64;
+            let geometry = Geometry::Rectangle {
+                top_left: XYPoint::new(column * CELL_SIZE, row * CELL_SIZE),
+                size: XYPoint::new(CELL_SIZE * 1.5, CELL_SIZE * 1.5),
+            };
+            Shape::new(
+                geometry,
+                Style::new(Color::PALETTE[index % Color::PALETTE.len()]),
+            )
+        })
+        .collect();
+    Document::new_from_shapes(&shapes)
+}
+
+fn viewport() -> Bounds {
+    Bounds::new(XYPoint::new(2000.0, 2000.0), XYPoint::new(3000.0, 2800.0))
+}
+
+// The shapes in painting order as a plain list, which is what the linear
+// scans used to walk. We collect it once so that the baselines do not pay
+// for walking the document's groups.
+fn painting_order(doc: &Document) -> Vec<(ShapeId, Shape)> {
+    doc.shape_id_shapes_iter()
+        .map(|(shape_id, shape)| (shape_id, shape.clone()))
+        .collect()
+}
+
+fn linear_shape_ids_intersecting(shapes: &[(ShapeId, Shape)], bounds: &Bounds) -> Vec<ShapeId> {
+    shapes
+        .iter()
+        .filter(|(_, shape)| bounds.intersects(&shape.bounds()))
+        .map(|(shape_id, _)| *shape_id)
+        .collect()
+}
+
+fn linear_topmost_shape_at(shapes: &[(ShapeId, Shape)], point: &XYPoint) -> Option<ShapeId> {
+    shapes
+        .iter()
+        .rev()
+        .find(|(_, shape)| shape.contains_point(point))
+        .map(|(shape_id, _)| *shape_id)
+}
+
+fn bench_queries(c: &mut Criterion) {
+    let doc = large_document();
+    let shapes = painting_order(&doc);
+    let viewport = viewport();
+    let point = XYPoint::new(2510.0, 2510.0);
+
+    let mut group = c.benchmark_group("viewport query");
+    group.bench_function("indexed", |b| {
+        b.iter(|| doc.shape_ids_in_viewport(black_box(&viewport)))
+    });
+    group.bench_function("linear", |b| {
+        b.iter(|| linear_shape_ids_intersecting(&shapes, black_box(&viewport)))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("hit test");
+    group.bench_function("indexed", |b| {
+        b.iter(|| doc.topmost_shape_at(black_box(&point)))
+    });
+    group.bench_function("linear", |b| {
+        b.iter(|| linear_topmost_shape_at(&shapes, black_box(&point)))
+    });
+    group.finish();
+}
+
+fn bench_z_order(c: &mut Criterion) {
+    let doc = large_document();
+    let shape_ids: Vec<ShapeId> = doc
+        .shape_id_shapes_iter()
+        .map(|(shape_id, _)| shape_id)
+        .collect();
+    let (low, high) = (shape_ids[SHAPE_COUNT / 4], shape_ids[SHAPE_COUNT * 3 / 4]);
+
+    let mut group = c.benchmark_group("z-order comparison");
+    group.bench_function("keyed", |b| {
+        b.iter(|| doc.compare_z_order(black_box(&low), black_box(&high)))
+    });
+    group.bench_function("linear", |b| {
+        b.iter(|| {
+            let position = |shape_id: &ShapeId| shape_ids.iter().position(|id| id == shape_id);
+            position(black_box(&low)).cmp(&position(black_box(&high)))
+        })
+    });
+    group.finish();
+}
+
+fn bench_mutations(c: &mut Criterion) {
+    let doc = large_document();
+    let shape_ids: Vec<ShapeId> = doc
+        .shape_id_shapes_iter()
+        .map(|(shape_id, _)| shape_id)
+        .collect();
+    let target = shape_ids[SHAPE_COUNT / 2];
+
+    // Cloning the document dominates a single delete, so delete a batch.
+    let batch: Vec<ShapeId> = shape_ids.iter().step_by(20).copied().collect();
+
+    let mut group = c.benchmark_group("delete batch");
+    group.sample_size(10);
+    group.bench_function("indexed", |b| {
+        b.iter_batched(
+            || doc.clone(),
+            |mut doc| {
+                for shape_id in &batch {
+                    doc.delete_shape_with_id(shape_id);
+                }
+                doc
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("linear", |b| {
+        b.iter_batched(
+            || shape_ids.clone(),
+            |mut sequence| {
+                for shape_id in &batch {
+                    if let Some(idx) = sequence.iter().position(|id| id == shape_id) {
+                        sequence.remove(idx);
+                    }
+                }
+                sequence
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("move to top");
+    group.bench_function("keyed", |b| {
+        let mut doc = doc.clone();
+        b.iter(|| doc.move_shape_with_id_to_top(black_box(&target)))
+    });
+    group.bench_function("linear", |b| {
+        let mut sequence = shape_ids.clone();
+        b.iter(|| {
+            if let Some(idx) = sequence.iter().position(|id| id == black_box(&target)) {
+                sequence.remove(idx);
+                sequence.push(target);
+            }
+        })
+    });
+    group.finish();
+}
+
+// Undoable edits go through the history, which records the z-order
+// changes of the shapes that move rather than copying whole child lists,
+// so the cost of an edit and of undoing it should not grow with the size
+// of the layer.
+fn bench_history(c: &mut Criterion) {
+    let doc = large_document();
+    let target = doc
+        .shape_id_shapes_iter()
+        .nth(SHAPE_COUNT / 2)
+        .map(|(shape_id, _)| shape_id)
+        .unwrap();
+    let move_to_top = |doc: &mut Document, history: &mut History| {
+        history.begin_transaction();
+        history.will_change_placement(doc, &target);
+        doc.move_shape_with_id_to_top(&target);
+        history.commit_transaction(doc);
+    };
+
+    let mut group = c.benchmark_group("move to top with history");
+    group.bench_function("edit and undo", |b| {
+        let mut doc = doc.clone();
+        let mut history = History::new_empty();
+        b.iter(|| {
+            move_to_top(&mut doc, &mut history);
+            history.undo(&mut doc)
+        })
+    });
+    group.bench_function("undo and redo", |b| {
+        let mut doc = doc.clone();
+        let mut history = History::new_empty();
+        move_to_top(&mut doc, &mut history);
+        b.iter(|| history.undo(&mut doc) && history.redo(&mut doc))
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_queries,
+    bench_z_order,
+    bench_mutations,
+    bench_history
+);
+criterion_main!(benches);
@@ -1 +1,2 @@
+pub mod shortcuts;
 pub mod tracking;
@@ -0,0 +1,216 @@
+use sauron::KeyboardEvent;
+use std::collections::HashMap;
+
+// Keyboard shortcuts map key chords -- a key together with the modifiers
+// held down -- to commands. The table is just data, so it can be set up
+// however the application likes and changed as it runs.
+//
+// Keys are the values of KeyboardEvent.key (e.g., "a", "Delete",
+// "ArrowLeft"). Single character keys are compared without regard to case
+// since holding shift changes their case. The primary modifier is control
+// or, so that the usual shortcuts work on the Mac, the command key.
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    key: String,
+    primary: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl KeyChord {
+    // A key pressed without any modifiers.
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: normalized_key(key),
+            primary: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    // The same chord with the primary modifier held.
+    pub fn with_primary(self) -> Self {
+        Self {
+            primary: true,
+            ..self
+        }
+    }
+
+    // The same chord with shift held.
+    pub fn with_shift(self) -> Self {
+        Self {
+            shift: true,
+            ..self
+        }
+    }
+
+    // The same chord with alt (option on the Mac) held. No shortcut in
+    // the app uses alt yet.
+    #[allow(dead_code)]
+    pub fn with_alt(self) -> Self {
+        Self { alt: true, ..self }
+    }
+
+    // The chord for a key event.
+    pub fn from_event(evt: &KeyboardEvent) -> Self {
+        Self::from_modifiers(
+            &evt.key(),
+            evt.ctrl_key(),
+            evt.meta_key(),
+            evt.shift_key(),
+            evt.alt_key(),
+        )
+    }
+
+    // The chord for a key with the state of each modifier key, as a key
+    // event reports them. Either control or meta counts as primary.
+    pub fn from_modifiers(key: &str, ctrl: bool, meta: bool, shift: bool, alt: bool) -> Self {
+        Self {
+            key: normalized_key(key),
+            primary: ctrl || meta,
+            shift,
+            alt,
+        }
+    }
+}
+
+fn normalized_key(key: &str) -> String {
+    if key.chars().count() == 1 {
+        key.to_lowercase()
+    } else {
+        key.to_string()
+    }
+}
+
+pub struct Shortcuts<C> {
+    bindings: HashMap<KeyChord, C>,
+}
+
+impl<C> Shortcuts<C> {
+    // A table without any shortcuts.
+    pub fn new_empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    // Bind a chord to a command, replacing any command it had before.
+    pub fn bind(&mut self, chord: KeyChord, command: C) {
+        self.bindings.insert(chord, command);
+    }
+
+    // The command for the chord of a key event if there is one.
+    pub fn command_for_event(&self, evt: &KeyboardEvent) -> Option<&C> {
+        self.command_for_chord(&KeyChord::from_event(evt))
+    }
+
+    // The command for a chord if there is one.
+    pub fn command_for_chord(&self, chord: &KeyChord) -> Option<&C> {
+        self.bindings.get(chord)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The chord for a key event with the given key and modifiers.
+    fn pressed(key: &str, ctrl: bool, meta: bool, shift: bool) -> KeyChord {
+        KeyChord::from_modifiers(key, ctrl, meta, shift, false)
+    }
+
+    #[test]
+    fn single_characters_fold_case() {
+        let mut shortcuts = Shortcuts::new_empty();
+        shortcuts.bind(KeyChord::new("z").with_primary(), "undo");
+        shortcuts.bind(KeyChord::new("Z").with_primary().with_shift(), "redo");
+        // Shift turns "z" into "Z", but both bindings are by the letter.
+        let cases = [
+            (pressed("z", true, false, false), Some(&"undo")),
+            (pressed("Z", true, false, false), Some(&"undo")),
+            (pressed("Z", true, false, true), Some(&"redo")),
+            (pressed("z", true, false, true), Some(&"redo")),
+            (pressed("z", false, false, false), None),
+        ];
+        for (chord, expected) in cases {
+            assert_eq!(
+                shortcuts.command_for_chord(&chord),
+                expected,
+                "{}",
+                chord.key
+            );
+        }
+        // Named keys keep their case.
+        assert!(KeyChord::new("Delete") != KeyChord::new("delete"));
+        assert!(KeyChord::new("Delete") == pressed("Delete", false, false, false));
+    }
+
+    #[test]
+    fn shifted_brackets_are_separate_keys() {
+        // Shift turns "]" into "}" on US keyboards, but not on every
+        // layout, so shifted bracket shortcuts are bound under both.
+        assert!(KeyChord::new("]") != KeyChord::new("}"));
+        assert!(KeyChord::new("[") != KeyChord::new("{"));
+        let mut shortcuts = Shortcuts::new_empty();
+        shortcuts.bind(KeyChord::new("]").with_primary(), "forward");
+        shortcuts.bind(KeyChord::new("[").with_primary(), "backward");
+        for key in ["]", "}"] {
+            shortcuts.bind(KeyChord::new(key).with_primary().with_shift(), "front");
+        }
+        for key in ["[", "{"] {
+            shortcuts.bind(KeyChord::new(key).with_primary().with_shift(), "back");
+        }
+        let cases = [
+            (pressed("]", true, false, false), Some(&"forward")),
+            (pressed("[", true, false, false), Some(&"backward")),
+            (pressed("}", true, false, true), Some(&"front")),
+            (pressed("]", true, false, true), Some(&"front")),
+            (pressed("{", true, false, true), Some(&"back")),
+            (pressed("[", true, false, true), Some(&"back")),
+            (pressed("}", true, false, false), None),
+        ];
+        for (chord, expected) in cases {
+            assert_eq!(
+                shortcuts.command_for_chord(&chord),
+                expected,
+                "{}",
+                chord.key
+            );
+        }
+    }
+
+    #[test]
+    fn alt_chords_match_only_with_alt() {
+        let mut shortcuts = Shortcuts::new_empty();
+        shortcuts.bind(KeyChord::new("d").with_alt(), "duplicate");
+        shortcuts.bind(KeyChord::new("d").with_primary().with_alt(), "deselect");
+        let alt = |key: &str, ctrl: bool| KeyChord::from_modifiers(key, ctrl, false, false, true);
+        let cases = [
+            (alt("d", false), Some(&"duplicate")),
+            (alt("D", false), Some(&"duplicate")),
+            (alt("d", true), Some(&"deselect")),
+            (pressed("d", false, false, false), None),
+            (pressed("d", true, false, false), None),
+        ];
+        for (chord, expected) in cases {
+            assert_eq!(
+                shortcuts.command_for_chord(&chord),
+                expected,
+                "{}",
+                chord.key
+            );
+        }
+    }
+
+    #[test]
+    fn control_or_meta_is_primary() {
+        let primary = KeyChord::new("a").with_primary();
+        assert!(pressed("a", true, false, false) == primary);
+        assert!(pressed("a", false, true, false) == primary);
+        assert!(pressed("a", true, true, false) == primary);
+        assert!(pressed("a", false, false, false) == KeyChord::new("a"));
+        assert!(pressed("a", false, true, true) == primary.clone().with_shift());
+        assert!(KeyChord::from_modifiers("a", false, true, false, true) != primary);
+    }
+}
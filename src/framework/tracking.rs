@@ -1,32 +1,287 @@
-use sauron::MouseEvent;
+use sauron::dom::Event as DomEvent;
+use sauron::wasm_bindgen::JsCast;
+use sauron::web_sys::{Node, PointerEvent, SvgGraphicsElement};
+use sauron::{Attribute, MouseEvent, events};
+
+// Tracking follows a pointer -- a mouse, a pen, or a finger -- from when it
+// goes down until it comes back up. It is built on pointer events so that
+// it works the same for all of them.
 
 // Labeling for tracking events. We could probably test the type
-// for the event, but labeling where we got it seems better.
+// for the event, but labeling where we got it seems better. A pointer
+// cancel means that the browser took the pointer over -- e.g., to scroll
+// -- and we will hear nothing more from it.
 
 #[derive(Clone, PartialEq)]
 pub enum Selector {
-    MouseMove,
-    MouseUp,
+    Move,
+    Up,
+    Cancel,
+}
+
+// The kinds of pointers.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PointerType {
+    Mouse,
+    Pen,
+    Touch,
+    // Some other kind of pointer or one the browser did not identify.
+    Other,
+}
+
+impl PointerType {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "mouse" => PointerType::Mouse,
+            "pen" => PointerType::Pen,
+            "touch" => PointerType::Touch,
+            _ => PointerType::Other,
+        }
+    }
+}
+
+// The modifier keys held down during an event.
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    pub fn from_event(mouse_event: &MouseEvent) -> Self {
+        Self {
+            shift: mouse_event.shift_key(),
+            alt: mouse_event.alt_key(),
+            ctrl: mouse_event.ctrl_key(),
+            meta: mouse_event.meta_key(),
+        }
+    }
 }
 
+// Tracking events carry the position of the pointer in the local coordinate
+// space of the element being tracked over -- e.g., a drawing canvas --
+// along with which pointer it is, how hard it is pressed (0 to 1, with
+// 0.5 for a pressed pointer that cannot tell), the modifier keys, and the
+// raw pointer event for everything else. The listeners for tracking
+// usually sit on an element enclosing the one being tracked over so that
+// tracking continues when the pointer leaves it. That means that the
+// positions in the pointer event itself are relative to the wrong element,
+// if they are relative to an element at all.
+
 #[derive(Clone)]
 pub struct Event {
     pub selector: Selector,
-    pub mouse_event: MouseEvent,
+    pub pointer_event: PointerEvent,
+    pub position: LocalPoint,
+    pub pointer_id: i32,
+    // Nothing in the app looks at the pointer type or pressure yet.
+    #[allow(dead_code)]
+    pub pointer_type: PointerType,
+    #[allow(dead_code)]
+    pub pressure: f64,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct LocalPoint {
+    pub x: f64,
+    pub y: f64,
 }
 
 impl Event {
-    pub fn mouse_move(mouse_event: MouseEvent) -> Event {
+    pub fn pointer_move(pointer_event: PointerEvent, element_id: &str) -> Event {
+        Self::new(Selector::Move, pointer_event, element_id)
+    }
+
+    pub fn pointer_up(pointer_event: PointerEvent, element_id: &str) -> Event {
+        Self::new(Selector::Up, pointer_event, element_id)
+    }
+
+    pub fn pointer_cancel(pointer_event: PointerEvent, element_id: &str) -> Event {
+        Self::new(Selector::Cancel, pointer_event, element_id)
+    }
+
+    fn new(selector: Selector, pointer_event: PointerEvent, element_id: &str) -> Event {
         Event {
-            selector: Selector::MouseMove,
-            mouse_event,
+            selector,
+            position: local_position(&pointer_event, element_id),
+            pointer_id: pointer_event.pointer_id(),
+            pointer_type: PointerType::from_name(&pointer_event.pointer_type()),
+            pressure: f64::from(pointer_event.pressure()),
+            modifiers: Modifiers::from_event(&pointer_event),
+            pointer_event,
         }
     }
 
-    pub fn mouse_up(mouse_event: MouseEvent) -> Event {
-        Event {
-            selector: Selector::MouseUp,
-            mouse_event,
+    // Does the pointer stop being tracked with this event?
+    pub fn is_end(&self) -> bool {
+        self.selector != Selector::Move
+    }
+
+    // Did the event happen over the element with an id or something inside
+    // it? The listeners see events from everything inside the element they
+    // sit on -- e.g., the buttons of a toolbar -- and those are not meant
+    // for tracking. Events from a captured pointer always count since they
+    // go to the capturing element wherever the pointer is.
+    pub fn is_over(&self, element_id: &str) -> bool {
+        let target = self
+            .pointer_event
+            .target()
+            .and_then(|target| target.dyn_into::<Node>().ok());
+        match (sauron::document().get_element_by_id(element_id), target) {
+            (Some(element), Some(target)) => element.contains(Some(&target)),
+            _ => false,
         }
     }
 }
+
+// Sauron does not have listeners for pointer events, so we build them on
+// its generic event listeners.
+
+pub fn on_pointerdown<MSG: 'static>(
+    f: impl FnMut(PointerEvent) -> MSG + 'static,
+) -> Attribute<MSG> {
+    on_pointer_event("pointerdown", f)
+}
+
+pub fn on_pointermove<MSG: 'static>(
+    f: impl FnMut(PointerEvent) -> MSG + 'static,
+) -> Attribute<MSG> {
+    on_pointer_event("pointermove", f)
+}
+
+pub fn on_pointerup<MSG: 'static>(f: impl FnMut(PointerEvent) -> MSG + 'static) -> Attribute<MSG> {
+    on_pointer_event("pointerup", f)
+}
+
+pub fn on_pointercancel<MSG: 'static>(
+    f: impl FnMut(PointerEvent) -> MSG + 'static,
+) -> Attribute<MSG> {
+    on_pointer_event("pointercancel", f)
+}
+
+fn on_pointer_event<MSG: 'static>(
+    event_name: &'static str,
+    mut f: impl FnMut(PointerEvent) -> MSG + 'static,
+) -> Attribute<MSG> {
+    events::on(event_name, move |event: DomEvent| {
+        let web_event = event.as_web().expect("must be a web_sys event");
+        f(web_event
+            .dyn_into()
+            .expect("Unable to cast to pointer event"))
+    })
+}
+
+// A tracker follows one pointer at a time. When a pointer goes down and no
+// other pointer is being followed, the tracker follows it and captures it
+// to an element so that its events keep coming even when it leaves the
+// element or the window. Other pointers are ignored -- e.g., a second
+// finger landing during a drag -- until the followed pointer comes up.
+// With no pointer down, events from any pointer are accepted so that,
+// e.g., a mouse can hover between the clicks of a shape drawn with
+// several clicks.
+
+#[derive(Clone, PartialEq)]
+pub struct Tracker {
+    pointer_id: Option<i32>,
+}
+
+impl Tracker {
+    pub fn new_idle() -> Self {
+        Self { pointer_id: None }
+    }
+
+    // Start following the pointer of a pointer down if we are not already
+    // following another. Returns whether the pointer down is from the
+    // followed pointer. The browser releases the capture when a pointer
+    // comes up, so if the followed pointer is no longer captured, it went
+    // away without our hearing about it and we follow the new one instead.
+    pub fn pointer_down(&mut self, pointer_down: &PointerEvent, element_id: &str) -> bool {
+        let pointer_id = pointer_down.pointer_id();
+        let element = sauron::document().get_element_by_id(element_id);
+        match self.pointer_id {
+            Some(followed_id) if followed_id == pointer_id => true,
+            Some(followed_id)
+                if element
+                    .as_ref()
+                    .is_some_and(|element| element.has_pointer_capture(followed_id)) =>
+            {
+                false
+            }
+            _ => {
+                self.pointer_id = Some(pointer_id);
+                if let Some(element) = element {
+                    // Capture fails if the pointer is already up, in which
+                    // case the pointer up is on its way anyway.
+                    let _ = element.set_pointer_capture(pointer_id);
+                }
+                true
+            }
+        }
+    }
+
+    // Is a tracking event from the followed pointer, or from any pointer
+    // if none is being followed? The followed pointer is let go when it
+    // comes up or is cancelled.
+    pub fn accepts(&mut self, tracking_event: &Event) -> bool {
+        match self.pointer_id {
+            Some(followed_id) if followed_id != tracking_event.pointer_id => false,
+            Some(_) => {
+                if tracking_event.is_end() {
+                    self.pointer_id = None;
+                }
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+// Find the position of a mouse or pointer event in the local coordinate
+// space of the element with an id. For SVG elements, we map the window
+// position through the inverse of the element's screen CTM so that view
+// boxes and transforms are taken into account. For other elements, we
+// measure from the top left of the element's bounding rectangle. Either
+// way, this holds up when the element is not at the origin of the page or
+// the page is scrolled. If there is no such element, we fall back to the
+// window position.
+
+pub fn local_position(mouse_event: &MouseEvent, element_id: &str) -> LocalPoint {
+    let client_x = f64::from(mouse_event.client_x());
+    let client_y = f64::from(mouse_event.client_y());
+    let Some(element) = sauron::document().get_element_by_id(element_id) else {
+        return LocalPoint {
+            x: client_x,
+            y: client_y,
+        };
+    };
+    if let Some(graphics) = element.dyn_ref::<SvgGraphicsElement>()
+        && let Some(ctm) = graphics.get_screen_ctm()
+    {
+        let (a, b, c, d, e, f) = (
+            f64::from(ctm.a()),
+            f64::from(ctm.b()),
+            f64::from(ctm.c()),
+            f64::from(ctm.d()),
+            f64::from(ctm.e()),
+            f64::from(ctm.f()),
+        );
+        let determinant = a * d - b * c;
+        if determinant != 0.0 && determinant.is_finite() {
+            let (dx, dy) = (client_x - e, client_y - f);
+            return LocalPoint {
+                x: (d * dx - c * dy) / determinant,
+                y: (a * dy - b * dx) / determinant,
+            };
+        }
+    }
+    let rect = element.get_bounding_client_rect();
+    LocalPoint {
+        x: client_x - rect.left(),
+        y: client_y - rect.top(),
+    }
+}
@@ -1,5 +1,5 @@
 mod framework;
-mod shapes;
+pub mod shapes;
 mod shell;
 
 use sauron::{Program, wasm_bindgen};
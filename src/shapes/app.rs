@@ -1,332 +1,2710 @@
 // This code covers the App State. In this case, it consists of the
-// shapes document and the information for coloring new shapes.
+// shapes document, its undo history, the selection, the layer for new
+// shapes, the viewport onto the document, the grid, and the information
+// for coloring new shapes.
 
-use sauron::{MouseEvent, Node, events, id, svg};
+use sauron::wasm_bindgen::JsCast;
+use sauron::web_sys::PointerEvent;
+use sauron::{Attribute, MouseEvent, Node, events, html, id, svg};
+use web_sys::WheelEvent;
 
 use crate::framework::tracking;
-use crate::shapes::core::{Color, Geometry, Shape, Style, XYPoint};
-use crate::shapes::doc::{Document, ShapeId};
+use crate::shapes::color::Color;
+use crate::shapes::core::{
+    Bounds, Geometry, PathSegment, Shape, Stroke, Style, Transform, XYPoint,
+};
+use crate::shapes::doc::{Alignment, Distribution, Document, Layer, ShapeId};
+use crate::shapes::grid::Grid;
+use crate::shapes::guides::{Guide, GuideTargets};
+use crate::shapes::history::History;
+use crate::shapes::svg_export::{
+    document_to_svg, svg_dash_array, svg_path_data, svg_points, svg_transform,
+};
+use crate::shapes::viewport::Viewport;
 
+use std::collections::HashSet;
+use std::rc::Rc;
 use std::vec::Vec;
 
-// Our model is simple. It consists of the document being edited and
-// information for tools. At this point, the latter just contains
-// the fill color for new shapes.
+// Our model is simple. It consists of the document being edited, the
+// undo history for the document, the set of selected shapes, the active
+// layer that new shapes go into, the viewport, the grid, and information
+// for tools.
+// At this point, the latter just contains the current tool, the color for
+// new shapes, and whether new shapes are drawn as outlines rather than
+// filled. We also track where the context menu is open, if it is,
+// whether the space bar is held down for panning, and which pointer we are
+// following. If we know how big the canvas can be, we only render the
+// shapes that fall within it.
 pub struct Model {
     doc: Document,
+    history: History,
+    selection: HashSet<ShapeId>,
+    active_layer: ShapeId,
+    viewport: Viewport,
+    grid: Grid,
+    space_held: bool,
+    tool: Tool,
     fill_color: Color,
+    outline_only: bool,
     tracking_state: TrackingState,
+    tracker: tracking::Tracker,
+    context_menu: Option<XYPoint>,
+    canvas_size: Option<XYPoint>,
 }
 
-// Messages we can use to update the model.
+// The tool determines what dragging on the background does.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tool {
+    // Drag out a marquee to select shapes.
+    Select,
+    // Drag out a new rectangle, ellipse, or line.
+    Rectangle,
+    Ellipse,
+    Line,
+    // Click (or drag) from point to point to make a new polyline or
+    // polygon. Click on the last point again to finish. For polygons,
+    // clicking on the first point also finishes.
+    Polyline,
+    Polygon,
+    // Click to add anchors to a new path and drag to pull out control
+    // handles. Click on the last anchor again to finish an open path or on
+    // the first anchor to close the path.
+    Pen,
+    // Drag the anchors of the selected shape.
+    EditNodes,
+}
+
+impl Tool {
+    // All of the tools in the order to present them.
+    pub fn all() -> &'static [Tool] {
+        &[
+            Tool::Select,
+            Tool::Rectangle,
+            Tool::Ellipse,
+            Tool::Line,
+            Tool::Polyline,
+            Tool::Polygon,
+            Tool::Pen,
+            Tool::EditNodes,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tool::Select => "Select",
+            Tool::Rectangle => "Rectangle",
+            Tool::Ellipse => "Ellipse",
+            Tool::Line => "Line",
+            Tool::Polyline => "Polyline",
+            Tool::Polygon => "Polygon",
+            Tool::Pen => "Pen",
+            Tool::EditNodes => "Edit Nodes",
+        }
+    }
+
+    // The geometry for dragging from one point to another with a tool
+    // that drags out shapes. Returns None if the geometry would be empty
+    // or if the tool does not drag out shapes.
+    fn geometry_for_drag(&self, from: &XYPoint, to: &XYPoint) -> Option<Geometry> {
+        let bounds = Bounds::from_corners(from, to);
+        let is_empty = bounds.width() == 0.0 || bounds.height() == 0.0;
+        match self {
+            Tool::Rectangle if !is_empty => Some(Geometry::rectangle(
+                bounds.min.x,
+                bounds.min.y,
+                bounds.width(),
+                bounds.height(),
+            )),
+            Tool::Ellipse if !is_empty => {
+                let center = bounds.center();
+                Some(Geometry::ellipse(
+                    center.x,
+                    center.y,
+                    bounds.width() / 2.0,
+                    bounds.height() / 2.0,
+                ))
+            }
+            Tool::Line if from != to => Some(Geometry::Line {
+                start: from.clone(),
+                end: to.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Changes to the stacking order of the selected shapes.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Arrange {
+    BringToFront,
+    BringForward,
+    SendBackward,
+    SendToBack,
+}
+
+impl Arrange {
+    // All of the arrangements in the order to present them.
+    pub fn all() -> &'static [Arrange] {
+        &[
+            Arrange::BringToFront,
+            Arrange::BringForward,
+            Arrange::SendBackward,
+            Arrange::SendToBack,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Arrange::BringToFront => "Bring to Front",
+            Arrange::BringForward => "Bring Forward",
+            Arrange::SendBackward => "Send Backward",
+            Arrange::SendToBack => "Send to Back",
+        }
+    }
+}
+
+// The id of the SVG element for the canvas. Mouse positions are measured
+// in its coordinate space.
+pub const CANVAS_ID: &str = "canvas";
+
+// Messages we can use to update the model. Messages can be cloned so that
+// tables -- e.g., of keyboard shortcuts -- can hold them.
+#[derive(Clone)]
 pub enum Msg {
-    // A mouse down event on a shape.
-    ShapeMouseDown(ShapeId, MouseEvent),
-    // A mouse down on the background.
-    BackgroundMouseDown(MouseEvent),
+    // A pointer down event on a shape.
+    ShapePointerDown(ShapeId, PointerEvent),
+    // A pointer down on the background.
+    BackgroundPointerDown(PointerEvent),
+    // A pointer down on one of the resize handles for a shape.
+    HandlePointerDown(ShapeId, Handle, PointerEvent),
+    // A pointer down on one of the anchors of a shape (by index).
+    NodePointerDown(ShapeId, usize, PointerEvent),
+    // A pointer down on the rotation handle for a shape.
+    RotateHandlePointerDown(ShapeId, PointerEvent),
+    // A request for the context menu on a shape -- e.g., a right click.
+    ShapeContextMenu(ShapeId, MouseEvent),
+    // Close the context menu without choosing anything.
+    CloseContextMenu,
+    // Abandon the current tracking -- e.g., a drag -- if any, undoing
+    // whatever it did to the document.
+    CancelTracking,
     // Tracking event
     FromTracking(tracking::Event),
+    // Undo the last edit.
+    Undo,
+    // Redo the last undone edit.
+    Redo,
+    // Switch to a different tool.
+    SetTool(Tool),
+    // Choose between filled and outline-only new shapes.
+    SetOutlineOnly(bool),
+    // Change the stacking order of the selected shapes.
+    Arrange(Arrange),
+    // Gather the selected shapes into a group.
+    Group,
+    // Break the selected groups back up into their children.
+    Ungroup,
+    // Select everything on the layers that can be edited.
+    SelectAll,
+    // Delete the selected shapes and groups.
+    DeleteSelection,
+    // Move the selection by a distance in the document.
+    Nudge(XYPoint),
+    // Line up the selected shapes.
+    Align(Alignment),
+    // Space the selected shapes out evenly.
+    Distribute(Distribution),
+    // Add a new layer on top and make it the active layer.
+    AddLayer,
+    // Delete a layer along with everything in it.
+    DeleteLayer(ShapeId),
+    RenameLayer(ShapeId, String),
+    SetLayerVisible(ShapeId, bool),
+    SetLayerLocked(ShapeId, bool),
+    // Choose the layer that new shapes go into.
+    SetActiveLayer(ShapeId),
+    // Move a layer up or down one step in the stack of layers.
+    RaiseLayer(ShapeId),
+    LowerLayer(ShapeId),
+    // Move the selected shapes to the top of a layer.
+    MoveSelectionToLayer(ShapeId),
+    // Zoom the view by a factor around a point on the canvas -- e.g., from
+    // the mouse wheel.
+    ZoomAround(XYPoint, f64),
+    // Go back to showing the document at actual size from its origin.
+    ResetView,
+    // Whether the space bar is held, in which case dragging pans the view.
+    SetSpaceHeld(bool),
+    // The size of the canvas in screen pixels, or at least an upper bound
+    // on it -- e.g., the size of the window.
+    SetCanvasSize(XYPoint),
+    // Grid settings.
+    SetGridVisible(bool),
+    SetSnapToGrid(bool),
+    SetGridSpacing(f64),
+    SetGridSubdivisions(u32),
+}
+
+impl Msg {
+    pub fn from_tracking(tracking_event: &tracking::Event) -> Self {
+        Self::FromTracking(tracking_event.clone())
+    }
+
+    // The pointer event for messages about a pointer going down.
+    fn pointer_down(&self) -> Option<&PointerEvent> {
+        match self {
+            Msg::ShapePointerDown(_, pointer_down)
+            | Msg::BackgroundPointerDown(pointer_down)
+            | Msg::HandlePointerDown(_, _, pointer_down)
+            | Msg::NodePointerDown(_, _, pointer_down)
+            | Msg::RotateHandlePointerDown(_, pointer_down) => Some(pointer_down),
+            _ => None,
+        }
+    }
+}
+
+impl Model {
+    // Generate a new document. At this point, we wire it to the demo
+    // case but in the future, this will likely just generate an empty
+    // document.
+    pub fn new() -> Self {
+        Self::new_demo()
+    }
+
+    pub fn update(&mut self, msg: &Msg) {
+        // We follow one pointer at a time. While it is down, other
+        // pointers -- e.g., a second finger -- are ignored.
+        if let Some(pointer_down) = msg.pointer_down()
+            && !self.tracker.pointer_down(pointer_down, CANVAS_ID)
+        {
+            return;
+        }
+        // Pointer events from outside the canvas -- e.g., clicks in the
+        // toolbar -- are not for us.
+        if let Msg::FromTracking(tracking_event) = msg
+            && (!tracking_event.is_over(CANVAS_ID) || !self.tracker.accepts(tracking_event))
+        {
+            return;
+        }
+
+        // Anything but pointer tracking dismisses the context menu, including
+        // choosing one of its items.
+        if !matches!(msg, Msg::FromTracking(_) | Msg::ShapeContextMenu(..)) {
+            self.context_menu = None;
+        }
+
+        match msg {
+            // The middle button, or the primary button with the space bar
+            // held, pans the view. We can pan in the middle of drawing a
+            // shape that takes several clicks but not in the middle of a
+            // drag.
+            Msg::ShapePointerDown(_, pointer_down)
+            | Msg::BackgroundPointerDown(pointer_down)
+            | Msg::HandlePointerDown(_, _, pointer_down)
+            | Msg::NodePointerDown(_, _, pointer_down)
+            | Msg::RotateHandlePointerDown(_, pointer_down)
+                if (pointer_down.button() == 1
+                    || (pointer_down.button() == 0 && self.space_held))
+                    && (matches!(self.tracking_state, TrackingState::None)
+                        || self.tracking_state.spans_clicks()) =>
+            {
+                Pan::start(self, pointer_down)
+            }
+
+            // Only the primary button draws and drags. The other buttons
+            // are left for the context menu.
+            Msg::ShapePointerDown(_, pointer_down)
+            | Msg::BackgroundPointerDown(pointer_down)
+            | Msg::HandlePointerDown(_, _, pointer_down)
+            | Msg::NodePointerDown(_, _, pointer_down)
+            | Msg::RotateHandlePointerDown(_, pointer_down)
+                if pointer_down.button() != 0 => {}
+
+            // Some tracking continues across clicks, in which case the
+            // pointer downs belong to the tracking rather than to whatever
+            // was clicked.
+            Msg::ShapePointerDown(_, pointer_down)
+            | Msg::BackgroundPointerDown(pointer_down)
+            | Msg::HandlePointerDown(_, _, pointer_down)
+            | Msg::NodePointerDown(_, _, pointer_down)
+            | Msg::RotateHandlePointerDown(_, pointer_down)
+                if self.tracking_state.spans_clicks() =>
+            {
+                self.tracking_state
+                    .clone()
+                    .update_model_for_pointer_down(self, pointer_down)
+            }
+
+            // Shapes on hidden or locked layers cannot be picked.
+            Msg::ShapePointerDown(shape_id, _) | Msg::ShapeContextMenu(shape_id, _)
+                if !self.doc.is_editable(shape_id) => {}
+
+            Msg::ShapePointerDown(shape_id, pointer_down) => {
+                // Clicking on a shape inside a group picks the group.
+                let shape_id = &self.doc.top_level_id_for(shape_id);
+                // Shift-click toggles the shape in the selection. A plain
+                // click on an unselected shape selects just that shape.
+                if pointer_down.shift_key() {
+                    self.toggle_selection(shape_id);
+                } else if !self.selection.contains(shape_id) {
+                    self.select_only(shape_id);
+                }
+                // Shift-clicking a shape out of the selection does not
+                // start a drag.
+                if self.selection.contains(shape_id) {
+                    DragShape::start(self, pointer_down);
+                }
+            }
+
+            Msg::BackgroundPointerDown(pointer_down) => {
+                if !pointer_down.shift_key() {
+                    self.selection.clear();
+                }
+                // New shapes cannot go into a hidden or locked layer.
+                let can_draw = self.doc.is_editable(&self.active_layer_id());
+                match self.tool {
+                    Tool::Select | Tool::EditNodes => Marquee::start(self, pointer_down),
+                    _ if !can_draw => {}
+                    Tool::Rectangle | Tool::Ellipse | Tool::Line => {
+                        DragNewShape::start(self, self.tool, pointer_down);
+                        self.advance_fill_color();
+                    }
+                    Tool::Polyline | Tool::Polygon => {
+                        DrawPolyline::start(self, self.tool == Tool::Polygon, pointer_down);
+                        self.advance_fill_color();
+                    }
+                    Tool::Pen => {
+                        DrawPath::start(self, pointer_down);
+                        self.advance_fill_color();
+                    }
+                }
+            }
+
+            Msg::HandlePointerDown(shape_id, handle, pointer_down) => {
+                ResizeShape::start(self, shape_id, *handle, pointer_down);
+            }
+
+            Msg::NodePointerDown(shape_id, anchor_index, pointer_down) => {
+                DragNode::start(self, shape_id, *anchor_index, pointer_down);
+            }
+
+            Msg::RotateHandlePointerDown(shape_id, pointer_down) => {
+                RotateShape::start(self, shape_id, pointer_down);
+            }
+
+            Msg::ShapeContextMenu(shape_id, mouse_event) => {
+                // Like a plain click, this selects an unselected shape so
+                // that the menu applies to what was clicked.
+                let shape_id = &self.doc.top_level_id_for(shape_id);
+                if matches!(self.tracking_state, TrackingState::None) {
+                    if !self.selection.contains(shape_id) {
+                        self.select_only(shape_id);
+                    }
+                    self.context_menu = Some(XYPoint::new(
+                        mouse_event.client_x().into(),
+                        mouse_event.client_y().into(),
+                    ));
+                }
+            }
+
+            Msg::CloseContextMenu => {}
+
+            Msg::CancelTracking => self.cancel_tracking(),
+
+            // The browser took the pointer over, so the gesture cannot
+            // finish. We treat that like Escape.
+            Msg::FromTracking(tracking_event)
+                if tracking_event.selector == tracking::Selector::Cancel =>
+            {
+                self.cancel_tracking()
+            }
+
+            Msg::FromTracking(tracking_event) => {
+                // Clone so that we do not get an alias conflict over the
+                // tracking state.
+                self.tracking_state
+                    .clone()
+                    .update_model_for_tracking_event(self, tracking_event)
+            }
+
+            Msg::Undo => {
+                self.history.undo(&mut self.doc);
+                self.prune_selection();
+            }
+
+            Msg::Redo => {
+                self.history.redo(&mut self.doc);
+                self.prune_selection();
+            }
+
+            Msg::SetTool(tool) => {
+                self.tool = *tool;
+            }
+
+            Msg::SetOutlineOnly(outline_only) => {
+                self.outline_only = *outline_only;
+            }
+
+            Msg::Arrange(arrange) => self.arrange_selection(*arrange),
+
+            Msg::Group => self.group_selection(),
+
+            Msg::Ungroup => self.ungroup_selection(),
+
+            Msg::SelectAll => self.select_all(),
+
+            Msg::DeleteSelection => self.delete_selection(),
+
+            Msg::Nudge(delta) => self.nudge_selection(delta),
+
+            Msg::Align(alignment) => self.align_selection(*alignment),
+
+            Msg::Distribute(distribution) => self.distribute_selection(*distribution),
+
+            Msg::AddLayer => self.perform_edit(|model| {
+                let layer_id = model.add_layer();
+                model.active_layer = layer_id;
+            }),
+
+            Msg::DeleteLayer(layer_id) => {
+                self.perform_edit(|model| model.delete_layer_with_id(layer_id));
+                self.prune_selection();
+            }
+
+            Msg::RenameLayer(layer_id, name) => self.perform_edit(|model| {
+                model.change_layer(layer_id, |layer| layer.name = name.clone())
+            }),
+
+            Msg::SetLayerVisible(layer_id, visible) => {
+                self.perform_edit(|model| {
+                    model.change_layer(layer_id, |layer| layer.visible = *visible)
+                });
+                self.prune_selection();
+            }
+
+            Msg::SetLayerLocked(layer_id, locked) => {
+                self.perform_edit(|model| {
+                    model.change_layer(layer_id, |layer| layer.locked = *locked)
+                });
+                self.prune_selection();
+            }
+
+            Msg::SetActiveLayer(layer_id) => {
+                if self.doc.get_layer_by_id(layer_id).is_some() {
+                    self.active_layer = *layer_id;
+                }
+            }
+
+            Msg::RaiseLayer(layer_id) => self.perform_edit(|model| {
+                model.arrange_shape_ids(&HashSet::from([*layer_id]), Arrange::BringForward)
+            }),
+
+            Msg::LowerLayer(layer_id) => self.perform_edit(|model| {
+                model.arrange_shape_ids(&HashSet::from([*layer_id]), Arrange::SendBackward)
+            }),
+
+            Msg::MoveSelectionToLayer(layer_id) => {
+                let selection = self.selection.clone();
+                self.perform_edit(|model| model.move_shape_ids_to_layer(&selection, layer_id));
+                self.prune_selection();
+            }
+
+            Msg::ZoomAround(screen_point, factor) => {
+                self.viewport = self.viewport.zoomed_around(screen_point, *factor);
+            }
+
+            Msg::ResetView => {
+                self.viewport = Viewport::new_identity();
+            }
+
+            Msg::SetSpaceHeld(space_held) => {
+                self.space_held = *space_held;
+            }
+
+            Msg::SetCanvasSize(size) => {
+                self.canvas_size = Some(size.clone());
+            }
+
+            Msg::SetGridVisible(visible) => {
+                self.grid.visible = *visible;
+            }
+
+            Msg::SetSnapToGrid(snap) => {
+                self.grid.snap = *snap;
+            }
+
+            Msg::SetGridSpacing(spacing) => {
+                if spacing.is_finite() && 0.0 < *spacing {
+                    self.grid.spacing = *spacing;
+                }
+            }
+
+            Msg::SetGridSubdivisions(subdivisions) => {
+                if 0 < *subdivisions {
+                    self.grid.subdivisions = *subdivisions;
+                }
+            }
+        }
+    }
+
+    pub fn view(&self) -> Node<Msg> {
+        use svg::attributes::*;
+        use svg::*;
+
+        let mut children = Vec::with_capacity(4);
+
+        // Add the background and the grid. The background covers the
+        // canvas no matter how the view is zoomed or panned.
+
+        children.push(render_background(&self.grid, &self.viewport));
+
+        // Add the visible layers, transformed by the viewport. Shapes
+        // outside the canvas are left out.
+
+        let on_canvas = self.shape_ids_on_canvas();
+        children.push(g(
+            [
+                id("viewport"),
+                transform(svg_transform(&self.viewport.transform())),
+            ],
+            self.doc
+                .layer_ids_iter()
+                .filter_map(|layer_id| self.render_layer(layer_id, on_canvas.as_ref())),
+        ));
+
+        // Add the selection overlay on top. The overlay is drawn in canvas
+        // coordinates so that handles keep their size as the view zooms.
+
+        children.push(self.render_selection());
+
+        // Add any feedback for the current tracking
+
+        children.extend(self.tracking_state.render_overlay(&self.viewport));
+
+        let canvas_cursor = match (&self.tracking_state, self.space_held) {
+            (TrackingState::Pan(_), _) => "grabbing",
+            (_, true) => "grab",
+            _ => "default",
+        };
+        svg(
+            [
+                id(CANVAS_ID),
+                width("100%"),
+                height("100%"),
+                preserve_aspect_ratio("none"),
+                cursor(canvas_cursor),
+                events::on_wheel(canvas_wheel),
+            ],
+            children,
+        )
+    }
+
+    pub fn tool(&self) -> Tool {
+        self.tool
+    }
+
+    pub fn outline_only(&self) -> bool {
+        self.outline_only
+    }
+
+    pub fn zoom(&self) -> f64 {
+        self.viewport.zoom()
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    // The context menu is HTML rather than SVG so it gets placed outside of
+    // the drawing. It sits at the position where it was opened in the
+    // window.
+    pub fn view_context_menu(&self) -> Option<Node<Msg>> {
+        use html::attributes::*;
+        use html::*;
+
+        let position = self.context_menu.as_ref()?;
+        let mut items: Vec<Node<Msg>> = Arrange::all()
+            .iter()
+            .map(|&arrange| context_menu_item(arrange.label(), move || Msg::Arrange(arrange)))
+            .collect();
+        items.push(hr([], []));
+        items.push(context_menu_item("Group", || Msg::Group));
+        items.push(context_menu_item("Ungroup", || Msg::Ungroup));
+        items.push(hr([], []));
+        items.extend(
+            Alignment::all().iter().map(|&alignment| {
+                context_menu_item(alignment.label(), move || Msg::Align(alignment))
+            }),
+        );
+        items.push(hr([], []));
+        items.extend(Distribution::all().iter().map(|&distribution| {
+            context_menu_item(distribution.label(), move || Msg::Distribute(distribution))
+        }));
+        Some(div(
+            [
+                class("context-menu"),
+                styles([
+                    ("left", format!("{}px", position.x)),
+                    ("top", format!("{}px", position.y)),
+                ]),
+                events::on_contextmenu(|evt| {
+                    evt.prevent_default();
+                    Msg::CloseContextMenu
+                }),
+            ],
+            items,
+        ))
+    }
+
+    // The layers panel lists the layers from top to bottom, as they stack
+    // in the drawing. Each row picks the active layer, edits the name and
+    // flags, moves the layer up or down, and moves the selection into the
+    // layer.
+    pub fn view_layers_panel(&self) -> Node<Msg> {
+        use html::attributes::*;
+        use html::*;
+        // The html module has a title element as well as the attribute.
+        use html::attributes::title;
+
+        let active_layer_id = self.active_layer_id();
+        let can_delete = 1 < self.doc.layer_ids_iter().count();
+        let rows = self.doc.layer_ids_iter().rev().filter_map(|&layer_id| {
+            let layer = self.doc.get_layer_by_id(&layer_id)?;
+            let (visible, locked) = (layer.visible, layer.locked);
+            Some(div(
+                [classes_flag([
+                    ("layer-row", true),
+                    ("active", layer_id == active_layer_id),
+                ])],
+                [
+                    input(
+                        [
+                            r#type("radio"),
+                            name("active-layer"),
+                            title("Draw new shapes in this layer"),
+                            checked(layer_id == active_layer_id),
+                            events::on_change(move |_| Msg::SetActiveLayer(layer_id)),
+                        ],
+                        [],
+                    ),
+                    input(
+                        [
+                            r#type("text"),
+                            class("layer-name"),
+                            value(layer.name.clone()),
+                            events::on_change(move |evt| Msg::RenameLayer(layer_id, evt.value())),
+                        ],
+                        [],
+                    ),
+                    layer_flag_checkbox("Visible", visible, move || {
+                        Msg::SetLayerVisible(layer_id, !visible)
+                    }),
+                    layer_flag_checkbox("Locked", locked, move || {
+                        Msg::SetLayerLocked(layer_id, !locked)
+                    }),
+                    button(
+                        [
+                            title("Raise layer"),
+                            events::on_click(move |_| Msg::RaiseLayer(layer_id)),
+                        ],
+                        [text("▲")],
+                    ),
+                    button(
+                        [
+                            title("Lower layer"),
+                            events::on_click(move |_| Msg::LowerLayer(layer_id)),
+                        ],
+                        [text("▼")],
+                    ),
+                    button(
+                        [
+                            title("Move the selected shapes to this layer"),
+                            disabled(self.selection.is_empty()),
+                            events::on_click(move |_| Msg::MoveSelectionToLayer(layer_id)),
+                        ],
+                        [text("Move selection here")],
+                    ),
+                    button(
+                        [
+                            title("Delete this layer and everything in it"),
+                            disabled(!can_delete),
+                            events::on_click(move |_| Msg::DeleteLayer(layer_id)),
+                        ],
+                        [text("Delete")],
+                    ),
+                ],
+            ))
+        });
+        div(
+            [class("layers-panel")],
+            [h3([], [text("Layers")])]
+                .into_iter()
+                .chain(rows)
+                .chain([button(
+                    [events::on_click(|_| Msg::AddLayer)],
+                    [text("Add layer")],
+                )]),
+        )
+    }
+
+    // Replace the document being edited -- e.g., with one read from a
+    // file. The history starts over with the new document.
+    pub fn open_document(&mut self, doc: Document) {
+        self.active_layer = doc.top_layer_id();
+        self.doc = doc;
+        self.viewport = Viewport::new_identity();
+        self.history = History::new_empty();
+        self.selection.clear();
+        self.tracking_state = TrackingState::None;
+        self.context_menu = None;
+    }
+
+    // Export the document as a standalone SVG file.
+    pub fn export_svg(&self) -> String {
+        document_to_svg(&self.doc)
+    }
+}
+
+fn context_menu_item(item_label: &'static str, to_msg: impl Fn() -> Msg + 'static) -> Node<Msg> {
+    use html::*;
+
+    button([events::on_click(move |_| to_msg())], [text(item_label)])
+}
+
+fn layer_flag_checkbox(
+    flag_label: &'static str,
+    is_set: bool,
+    to_msg: impl Fn() -> Msg + 'static,
+) -> Node<Msg> {
+    use html::attributes::*;
+    use html::*;
+
+    label(
+        [],
+        [
+            input(
+                [
+                    r#type("checkbox"),
+                    checked(is_set),
+                    events::on_change(move |_| to_msg()),
+                ],
+                [],
+            ),
+            text(flag_label),
+        ],
+    )
+}
+
+impl Model {
+    // Render a layer as an SVG group. Hidden layers are left out entirely
+    // and locked layers let the mouse through to whatever is below them.
+    fn render_layer(
+        &self,
+        layer_id: &ShapeId,
+        on_canvas: Option<&HashSet<ShapeId>>,
+    ) -> Option<Node<Msg>> {
+        use svg::attributes::*;
+        use svg::*;
+
+        let layer = self.doc.get_layer_by_id(layer_id)?;
+        if !layer.visible {
+            return None;
+        }
+        let mut attributes = vec![id(format!("layer_{layer_id}"))];
+        if layer.locked {
+            attributes.push(pointer_events("none"));
+        }
+        Some(g(
+            attributes,
+            self.doc
+                .group_child_ids_iter(layer_id)
+                .filter_map(|child_id| self.render_item(child_id, on_canvas)),
+        ))
+    }
+
+    // Render a shape or a group with its children. Groups become SVG
+    // groups so that the structure carries through to the DOM. Shapes not
+    // in on_canvas, if given, are left out.
+    fn render_item(
+        &self,
+        shape_id: &ShapeId,
+        on_canvas: Option<&HashSet<ShapeId>>,
+    ) -> Option<Node<Msg>> {
+        use svg::*;
+
+        if let Some(shape) = self.doc.get_shape_by_id(shape_id) {
+            if on_canvas.is_some_and(|on_canvas| !on_canvas.contains(shape_id)) {
+                return None;
+            }
+            return Some(render_shape(*shape_id, shape));
+        }
+        if !self.doc.is_group(shape_id) {
+            return None;
+        }
+        Some(g(
+            [id(format!("group_{shape_id}"))],
+            self.doc
+                .group_child_ids_iter(shape_id)
+                .filter_map(|child_id| self.render_item(child_id, on_canvas)),
+        ))
+    }
+
+    // The shapes that paint anything on the canvas, or None if we do not
+    // know the size of the canvas.
+    fn shape_ids_on_canvas(&self) -> Option<HashSet<ShapeId>> {
+        let size = self.canvas_size.as_ref()?;
+        let visible = Bounds::from_corners(
+            &self.viewport.to_document(&XYPoint::new(0.0, 0.0)),
+            &self.viewport.to_document(size),
+        );
+        Some(
+            self.doc
+                .shape_ids_in_viewport(&visible)
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+// Render a shape to SVG and attach a pointer down handler that
+// initiates dragging.
+
+fn render_shape(shape_id: ShapeId, shape: &Shape) -> Node<Msg> {
+    use svg::attributes::*;
+    use svg::*;
+
+    let mut attributes = vec![
+        id(format!("shape_{shape_id}")),
+        tracking::on_pointerdown(move |evt| shape_pointer_down(shape_id, evt)),
+        events::on_contextmenu(move |evt| shape_context_menu(shape_id, evt)),
+    ];
+    attributes.extend(style_attributes(&shape.rendered_style()));
+    if !shape.transform.is_identity() {
+        attributes.push(transform(svg_transform(&shape.transform)));
+    }
+
+    match &shape.geometry {
+        Geometry::Circle { center, radius } => circle(
+            [cx(center.x), cy(center.y), r(*radius)]
+                .into_iter()
+                .chain(attributes),
+            [],
+        ),
+        Geometry::Rectangle { top_left, size } => rect(
+            [x(top_left.x), y(top_left.y), width(size.x), height(size.y)]
+                .into_iter()
+                .chain(attributes),
+            [],
+        ),
+        Geometry::Ellipse { center, radii } => ellipse(
+            [cx(center.x), cy(center.y), rx(radii.x), ry(radii.y)]
+                .into_iter()
+                .chain(attributes),
+            [],
+        ),
+        Geometry::Line { start, end } => line(
+            [x1(start.x), y1(start.y), x2(end.x), y2(end.y)]
+                .into_iter()
+                .chain(attributes),
+            [],
+        ),
+        Geometry::Polyline {
+            points: shape_points,
+        } => polyline(
+            [points(svg_points(shape_points))]
+                .into_iter()
+                .chain(attributes),
+            [],
+        ),
+        Geometry::Polygon {
+            points: shape_points,
+        } => polygon(
+            [points(svg_points(shape_points))]
+                .into_iter()
+                .chain(attributes),
+            [],
+        ),
+        Geometry::Path { segments } => path(
+            [d(svg_path_data(segments))].into_iter().chain(attributes),
+            [],
+        ),
+    }
+}
+
+// The fill and stroke attributes for a style. Unlike the export, the live
+// view can use CSS colors with alpha directly.
+
+fn style_attributes(style: &Style) -> Vec<Attribute<Msg>> {
+    use svg::attributes::*;
+
+    let mut attributes = vec![fill(
+        style
+            .fill
+            .map_or("none".to_string(), |color| color.to_css()),
+    )];
+    if let Some(shape_stroke) = &style.stroke {
+        attributes.push(stroke(shape_stroke.color.to_css()));
+        attributes.push(stroke_width(shape_stroke.width));
+        if !shape_stroke.dash_array.is_empty() {
+            attributes.push(stroke_dasharray(svg_dash_array(&shape_stroke.dash_array)));
+        }
+        attributes.push(stroke_linejoin(shape_stroke.line_join.svg_name()));
+        attributes.push(stroke_linecap(shape_stroke.line_cap.svg_name()));
+    }
+    attributes
+}
+
+// Render the selection as an outline around each selected shape plus,
+// if more than one shape is selected, a dashed box around all of them.
+// The outlines ignore the mouse so that clicks go through to the shapes.
+// If exactly one shape is selected, it also gets resize and rotation
+// handles or, when editing nodes, handles for its anchors. The outlines and
+// handles for transformed shapes follow the transform. Everything is
+// mapped through the viewport to canvas coordinates.
+
+impl Model {
+    fn render_selection(&self) -> Node<Msg> {
+        use svg::attributes::*;
+        use svg::*;
+
+        let viewport = &self.viewport;
+        let to_canvas = viewport.transform();
+        let selected_ids = self.doc.sorted_by_z_order(self.selection.iter().copied());
+        let selected: Vec<(ShapeId, &Shape)> = selected_ids
+            .iter()
+            .filter_map(|shape_id| {
+                self.doc
+                    .get_shape_by_id(shape_id)
+                    .map(|shape| (*shape_id, shape))
+            })
+            .collect();
+
+        // Shapes get boxes that follow their transforms while groups get
+        // plain boxes around their bounds.
+        let mut outlines: Vec<Node<Msg>> = selected_ids
+            .iter()
+            .filter_map(|shape_id| match self.doc.get_shape_by_id(shape_id) {
+                Some(shape) => Some(render_selection_box(
+                    &shape.geometry.bounds(),
+                    &shape.transform.then(&to_canvas),
+                    "none",
+                )),
+                None => self
+                    .doc
+                    .bounds_for_id(shape_id)
+                    .map(|bounds| render_selection_box(&bounds, &to_canvas, "none")),
+            })
+            .collect();
+
+        if 1 < selected_ids.len()
+            && let Some(all_bounds) = selected_ids
+                .iter()
+                .filter_map(|shape_id| self.doc.bounds_for_id(shape_id))
+                .reduce(|acc, b| acc.union(&b))
+        {
+            outlines.push(render_selection_box(&all_bounds, &to_canvas, "4 4"));
+        }
+
+        // Only a lone selected shape gets handles.
+        let lone_shape = match selected_ids.len() {
+            1 => selected.as_slice(),
+            _ => &[],
+        };
+        let handles = match lone_shape {
+            [(shape_id, shape)] if self.tool == Tool::EditNodes => shape
+                .geometry
+                .anchors()
+                .iter()
+                .enumerate()
+                .map(|(anchor_index, anchor)| {
+                    let position = viewport.to_screen(&shape.transform.apply(anchor));
+                    render_node(*shape_id, anchor_index, &position)
+                })
+                .collect(),
+            [(shape_id, shape)] => {
+                let bounds = shape.geometry.bounds();
+                let mut handles: Vec<Node<Msg>> = Handle::all()
+                    .iter()
+                    .map(|handle| {
+                        let position = shape.transform.apply(&handle.position_on(&bounds));
+                        render_handle(*shape_id, *handle, &viewport.to_screen(&position))
+                    })
+                    .collect();
+                handles.extend(render_rotate_handle(*shape_id, shape, viewport));
+                handles
+            }
+            _ => Vec::new(),
+        };
+
+        g(
+            vec![id("selection")],
+            [
+                g(vec![pointer_events("none")], outlines),
+                g(vec![id("handles")], handles),
+            ],
+        )
+    }
+}
+
+fn render_selection_box(bounds: &Bounds, box_transform: &Transform, dash_array: &str) -> Node<Msg> {
+    use svg::attributes::*;
+    use svg::*;
+
+    rect(
+        vec![
+            x(bounds.min.x),
+            y(bounds.min.y),
+            width(bounds.width()),
+            height(bounds.height()),
+            fill("none"),
+            stroke("dodgerblue"),
+            stroke_width(1),
+            stroke_dasharray(dash_array.to_string()),
+            transform(svg_transform(box_transform)),
+            vector_effect("non-scaling-stroke"),
+        ],
+        [],
+    )
+}
+
+// Handles are small squares centered on the corners and edge midpoints of
+// the shape bounds.
+
+const HANDLE_SIZE: f64 = 8.0;
+
+// The stroke width for new outline-only shapes.
+const OUTLINE_STROKE_WIDTH: f64 = 2.0;
+
+fn render_handle(shape_id: ShapeId, handle: Handle, position: &XYPoint) -> Node<Msg> {
+    use svg::attributes::*;
+    use svg::*;
+
+    rect(
+        vec![
+            x(position.x - HANDLE_SIZE / 2.0),
+            y(position.y - HANDLE_SIZE / 2.0),
+            width(HANDLE_SIZE),
+            height(HANDLE_SIZE),
+            fill("white"),
+            stroke("dodgerblue"),
+            stroke_width(1),
+            cursor(handle.cursor()),
+            tracking::on_pointerdown(move |evt| handle_pointer_down(shape_id, handle, evt)),
+        ],
+        [],
+    )
+}
+
+// The rotation handle sits a fixed distance on the canvas beyond the
+// middle of the top edge -- in the shape's own space -- with a line back
+// to that edge.
+
+const ROTATE_HANDLE_DISTANCE: f64 = 24.0;
+
+fn render_rotate_handle(shape_id: ShapeId, shape: &Shape, viewport: &Viewport) -> Vec<Node<Msg>> {
+    use svg::attributes::*;
+    use svg::*;
+
+    let bounds = shape.geometry.bounds();
+    let top = viewport.to_screen(
+        &shape
+            .transform
+            .apply(&XYPoint::new(bounds.center().x, bounds.min.y)),
+    );
+    let outward = top.subtract(&viewport.to_screen(&shape.center()));
+    let length = outward.x.hypot(outward.y);
+    let direction = if length == 0.0 {
+        XYPoint::new(0.0, -1.0)
+    } else {
+        XYPoint::new(outward.x / length, outward.y / length)
+    };
+    let position = XYPoint::new(
+        top.x + direction.x * ROTATE_HANDLE_DISTANCE,
+        top.y + direction.y * ROTATE_HANDLE_DISTANCE,
+    );
+    vec![
+        line(
+            vec![
+                x1(top.x),
+                y1(top.y),
+                x2(position.x),
+                y2(position.y),
+                stroke("dodgerblue"),
+                stroke_width(1),
+                pointer_events("none"),
+            ],
+            [],
+        ),
+        circle(
+            vec![
+                cx(position.x),
+                cy(position.y),
+                r(HANDLE_SIZE / 2.0),
+                fill("white"),
+                stroke("dodgerblue"),
+                stroke_width(1),
+                cursor("grab"),
+                tracking::on_pointerdown(move |evt| rotate_handle_pointer_down(shape_id, evt)),
+            ],
+            [],
+        ),
+    ]
+}
+
+fn rotate_handle_pointer_down(shape_id: ShapeId, evt: PointerEvent) -> Msg {
+    evt.stop_propagation();
+    Msg::RotateHandlePointerDown(shape_id, evt)
+}
+
+// Nodes are small circles centered on the anchors.
+
+fn render_node(shape_id: ShapeId, anchor_index: usize, anchor: &XYPoint) -> Node<Msg> {
+    use svg::attributes::*;
+    use svg::*;
+
+    circle(
+        vec![
+            cx(anchor.x),
+            cy(anchor.y),
+            r(HANDLE_SIZE / 2.0),
+            fill("white"),
+            stroke("dodgerblue"),
+            stroke_width(1),
+            cursor("move"),
+            tracking::on_pointerdown(move |evt| node_pointer_down(shape_id, anchor_index, evt)),
+        ],
+        [],
+    )
+}
+
+fn node_pointer_down(shape_id: ShapeId, anchor_index: usize, evt: PointerEvent) -> Msg {
+    evt.stop_propagation();
+    Msg::NodePointerDown(shape_id, anchor_index, evt)
+}
+
+fn handle_pointer_down(shape_id: ShapeId, handle: Handle, evt: PointerEvent) -> Msg {
+    evt.stop_propagation();
+    Msg::HandlePointerDown(shape_id, handle, evt)
+}
+
+fn shape_pointer_down(shape_id: ShapeId, evt: PointerEvent) -> Msg {
+    evt.stop_propagation();
+    Msg::ShapePointerDown(shape_id, evt)
+}
+
+// Suppress the browser's own context menu in favor of ours.
+fn shape_context_menu(shape_id: ShapeId, evt: MouseEvent) -> Msg {
+    evt.prevent_default();
+    evt.stop_propagation();
+    Msg::ShapeContextMenu(shape_id, evt)
+}
+
+// The background is white with the grid, if visible, on top. The grid is
+// a pattern of a single major cell in document coordinates, mapped through
+// the viewport. Each cell draws major lines along its top and left edges,
+// which the cell clips to half their width, and minor lines across it.
+// Lines are a pixel wide on the canvas at any zoom, and lines that would
+// be too close together on the canvas are left out.
+
+const MIN_GRID_LINE_GAP: f64 = 4.0;
+const MAJOR_GRID_COLOR: &str = "#d0d0d0";
+const MINOR_GRID_COLOR: &str = "#ececec";
+
+fn render_background(grid: &Grid, viewport: &Viewport) -> Node<Msg> {
+    use svg::attributes::*;
+    use svg::*;
+
+    let mut children = vec![rect(
+        vec![
+            id("background"),
+            x("0"),
+            y("0"),
+            width("100%"),
+            height("100%"),
+            fill("white"),
+            tracking::on_pointerdown(background_pointer_down),
+        ],
+        [],
+    )];
+    let zoom = viewport.zoom();
+    if grid.visible && MIN_GRID_LINE_GAP <= grid.spacing * zoom {
+        let spacing = grid.spacing;
+        let line_width = viewport.document_length(1.0);
+        let mut cell = Vec::new();
+        if MIN_GRID_LINE_GAP <= grid.step() * zoom {
+            let minor_lines: String = (1..grid.subdivisions)
+                .map(|index| {
+                    let offset = f64::from(index) * grid.step();
+                    format!("M{offset} 0V{spacing}M0 {offset}H{spacing}")
+                })
+                .collect();
+            cell.push(path(
+                vec![
+                    d(minor_lines),
+                    fill("none"),
+                    stroke(MINOR_GRID_COLOR),
+                    stroke_width(line_width),
+                ],
+                [],
+            ));
+        }
+        cell.push(path(
+            vec![
+                d(format!("M0 {spacing}V0H{spacing}")),
+                fill("none"),
+                stroke(MAJOR_GRID_COLOR),
+                stroke_width(2.0 * line_width),
+            ],
+            [],
+        ));
+        children.push(defs(
+            [],
+            [pattern(
+                vec![
+                    id("grid"),
+                    pattern_units("userSpaceOnUse"),
+                    width(spacing),
+                    height(spacing),
+                    pattern_transform(svg_transform(&viewport.transform())),
+                ],
+                cell,
+            )],
+        ));
+        children.push(rect(
+            vec![
+                x("0"),
+                y("0"),
+                width("100%"),
+                height("100%"),
+                fill("url(#grid)"),
+                pointer_events("none"),
+            ],
+            [],
+        ));
+    }
+    g([], children)
+}
+
+fn background_pointer_down(evt: PointerEvent) -> Msg {
+    evt.stop_propagation();
+    Msg::BackgroundPointerDown(evt)
+}
+
+// How much a wheel movement of one pixel zooms the view. Pinching on a
+// trackpad arrives as wheel events with the control key down and much
+// smaller movements, so it gets a larger rate.
+const WHEEL_ZOOM_RATE: f64 = 0.002;
+const PINCH_ZOOM_RATE: f64 = 0.01;
+
+// Wheel movements can come in lines or pages rather than pixels. These
+// are the usual sizes for converting them.
+const WHEEL_LINE_PIXELS: f64 = 16.0;
+const WHEEL_PAGE_PIXELS: f64 = 800.0;
+
+// Zoom around the mouse rather than scrolling the page. Moving the wheel
+// away from the user -- a negative movement -- zooms in.
+fn canvas_wheel(evt: MouseEvent) -> Msg {
+    evt.prevent_default();
+    let factor = match evt.dyn_ref::<WheelEvent>() {
+        Some(wheel) => {
+            let pixels = wheel.delta_y()
+                * match wheel.delta_mode() {
+                    WheelEvent::DOM_DELTA_LINE => WHEEL_LINE_PIXELS,
+                    WheelEvent::DOM_DELTA_PAGE => WHEEL_PAGE_PIXELS,
+                    _ => 1.0,
+                };
+            let rate = if wheel.ctrl_key() {
+                PINCH_ZOOM_RATE
+            } else {
+                WHEEL_ZOOM_RATE
+            };
+            (-pixels * rate).exp()
+        }
+        None => 1.0,
+    };
+    Msg::ZoomAround(get_canvas_coordinates(&evt), factor)
+}
+
+impl Model {
+    // Create a new demo model
+    fn new_demo() -> Self {
+        let doc = Document::new_demo();
+        Self {
+            active_layer: doc.top_layer_id(),
+            doc,
+            history: History::new_empty(),
+            selection: HashSet::new(),
+            tool: Tool::Rectangle,
+            fill_color: Color::RED,
+            outline_only: false,
+            tracking_state: TrackingState::None,
+            tracker: tracking::Tracker::new_idle(),
+            context_menu: None,
+            viewport: Viewport::new_identity(),
+            grid: Grid::new_default(),
+            space_held: false,
+            canvas_size: None,
+        }
+    }
+
+    // Advance the fill color skipping white. This is purely part of the
+    // demo logic to make shape drawing more interesting.
+    fn advance_fill_color(&mut self) {
+        loop {
+            self.fill_color.advance();
+            if self.fill_color != Color::WHITE {
+                break;
+            }
+        }
+    }
+
+    // Get the style for new shapes. Outlines are stroked in the color
+    // that would otherwise be the fill.
+    fn get_new_shape_style(&self) -> Style {
+        if self.outline_only {
+            Style::new_outline(Stroke::new(self.fill_color, OUTLINE_STROKE_WIDTH))
+        } else {
+            Style::new(self.fill_color)
+        }
+    }
+
+    // Document edits go through the following functions so that the
+    // history can record the prior state of whatever is about to change.
+
+    // Upsert a shape. New shapes go into the active layer.
+    // https://en.wiktionary.org/wiki/upsert
+    fn upsert_shape_with_id(&mut self, shape_id: &ShapeId, new_shape: Shape) {
+        let layer_id = self.active_layer_id();
+        self.history.will_change_shape(&self.doc, shape_id);
+        self.history.will_change_placement(&self.doc, shape_id);
+        self.doc
+            .upsert_shape_with_id_in_layer(shape_id, new_shape, &layer_id);
+    }
+
+    // Delete a shape if it exists. Do nothing if it does not.
+    fn delete_shape_with_id(&mut self, shape_id: &ShapeId) {
+        if self.doc.get_shape_by_id(shape_id).is_some() {
+            self.history.will_change_shape(&self.doc, shape_id);
+            self.history.will_change_placement(&self.doc, shape_id);
+        }
+        self.doc.delete_shape_with_id(shape_id);
+    }
+
+    // Replace the geometry of a shape
+    fn set_geometry_for_shape_with_id(&mut self, shape_id: &ShapeId, new_geometry: Geometry) {
+        self.history.will_change_shape(&self.doc, shape_id);
+        self.doc.set_geometry_for_shape_id(shape_id, new_geometry);
+    }
+
+    // Change the stacking order of some shapes.
+    fn arrange_shape_ids(&mut self, shape_ids: &HashSet<ShapeId>, arrange: Arrange) {
+        for shape_id in shape_ids {
+            self.history.will_change_placement(&self.doc, shape_id);
+        }
+        match arrange {
+            Arrange::BringToFront => self.doc.move_shape_ids_to_top(shape_ids),
+            Arrange::BringForward => self.doc.move_shape_ids_forward(shape_ids),
+            Arrange::SendBackward => self.doc.move_shape_ids_backward(shape_ids),
+            Arrange::SendToBack => self.doc.move_shape_ids_to_bottom(shape_ids),
+        }
+    }
+
+    // Note that the shapes making up some shapes and groups are about to
+    // change -- e.g., because they are about to move.
+    fn will_change_shapes_in(&mut self, shape_ids: &HashSet<ShapeId>) {
+        for shape_id in shape_ids {
+            for inner_id in self.doc.shape_ids_in(shape_id) {
+                self.history.will_change_shape(&self.doc, &inner_id);
+            }
+        }
+    }
+
+    // Delete shapes and groups along with everything inside the groups.
+    fn delete_shape_ids(&mut self, shape_ids: &HashSet<ShapeId>) {
+        for shape_id in shape_ids {
+            for inner_id in std::iter::once(*shape_id).chain(self.doc.descendant_ids_of(shape_id)) {
+                self.history.will_change_shape(&self.doc, &inner_id);
+                self.history.will_change_group(&self.doc, &inner_id);
+                self.history.will_change_placement(&self.doc, &inner_id);
+            }
+        }
+        self.doc.delete_shape_ids(shape_ids);
+    }
+
+    // Move shapes and groups.
+    fn offset_shape_ids_by(&mut self, shape_ids: &HashSet<ShapeId>, delta: &XYPoint) {
+        self.will_change_shapes_in(shape_ids);
+        self.doc.offset_shape_ids_by(shape_ids, delta);
+    }
+
+    // Line up shapes and groups.
+    fn align_shape_ids(&mut self, shape_ids: &HashSet<ShapeId>, alignment: Alignment) {
+        self.will_change_shapes_in(shape_ids);
+        self.doc.align_shape_ids(shape_ids, alignment);
+    }
+
+    // Space shapes and groups out evenly.
+    fn distribute_shape_ids(&mut self, shape_ids: &HashSet<ShapeId>, distribution: Distribution) {
+        self.will_change_shapes_in(shape_ids);
+        self.doc.distribute_shape_ids(shape_ids, distribution);
+    }
+
+    // Gather shapes and groups into a new group and return its id.
+    fn group_shape_ids(&mut self, shape_ids: &HashSet<ShapeId>) -> ShapeId {
+        let group_id = self.generate_shape_id();
+        for shape_id in shape_ids {
+            self.history.will_change_placement(&self.doc, shape_id);
+        }
+        self.history.will_change_group(&self.doc, &group_id);
+        self.history.will_change_placement(&self.doc, &group_id);
+        self.doc.group_shape_ids_with_id(&group_id, shape_ids);
+        group_id
+    }
+
+    // Replace a group with its children and return their ids.
+    fn ungroup_shape_id(&mut self, group_id: &ShapeId) -> Vec<ShapeId> {
+        self.history.will_change_group(&self.doc, group_id);
+        self.history.will_change_placement(&self.doc, group_id);
+        for child_id in self.doc.group_child_ids_iter(group_id) {
+            self.history.will_change_placement(&self.doc, child_id);
+        }
+        self.doc.ungroup_shape_id(group_id)
+    }
+
+    // Move shapes and groups into a layer.
+    fn move_shape_ids_to_layer(&mut self, shape_ids: &HashSet<ShapeId>, layer_id: &ShapeId) {
+        for shape_id in shape_ids {
+            self.history.will_change_placement(&self.doc, shape_id);
+        }
+        self.doc.move_shape_ids_to_layer(shape_ids, layer_id);
+    }
+
+    // Add a new empty layer on top and return its id. The new layer gets
+    // the first numbered name that is not already taken.
+    fn add_layer(&mut self) -> ShapeId {
+        let layer_id = self.generate_shape_id();
+        let names: HashSet<&str> = self
+            .doc
+            .layer_ids_iter()
+            .filter_map(|layer_id| self.doc.get_layer_by_id(layer_id))
+            .map(|layer| layer.name.as_str())
+            .collect();
+        let name = (1..)
+            .map(|number| format!("Layer {number}"))
+            .find(|name| !names.contains(name.as_str()))
+            .unwrap_or_default();
+        self.history.will_change_layer(&self.doc, &layer_id);
+        self.history.will_change_group(&self.doc, &layer_id);
+        self.history.will_change_placement(&self.doc, &layer_id);
+        self.doc.upsert_layer_with_id(&layer_id, Layer::new(&name));
+        layer_id
+    }
+
+    // Delete a layer and everything in it. The document refuses to delete
+    // its last layer, in which case nothing changes.
+    fn delete_layer_with_id(&mut self, layer_id: &ShapeId) {
+        for shape_id in self.doc.descendant_ids_of(layer_id) {
+            self.history.will_change_shape(&self.doc, &shape_id);
+            self.history.will_change_group(&self.doc, &shape_id);
+            self.history.will_change_placement(&self.doc, &shape_id);
+        }
+        self.history.will_change_layer(&self.doc, layer_id);
+        self.history.will_change_group(&self.doc, layer_id);
+        self.history.will_change_placement(&self.doc, layer_id);
+        self.doc.delete_layer_with_id(layer_id);
+    }
+
+    // Change the name or flags of a layer.
+    fn change_layer(&mut self, layer_id: &ShapeId, change: impl FnOnce(&mut Layer)) {
+        if let Some(mut layer) = self.doc.get_layer_by_id(layer_id).cloned() {
+            change(&mut layer);
+            self.history.will_change_layer(&self.doc, layer_id);
+            self.doc.upsert_layer_with_id(layer_id, layer);
+        }
+    }
+
+    // The layer that new shapes go into. If the active layer has gone
+    // away -- e.g., after an undo -- we fall back to the top layer.
+    fn active_layer_id(&self) -> ShapeId {
+        if self.doc.get_layer_by_id(&self.active_layer).is_some() {
+            self.active_layer
+        } else {
+            self.doc.top_layer_id()
+        }
+    }
+
+    // Make an edit outside of pointer tracking -- e.g., from a keyboard
+    // shortcut -- as a single undoable step. We leave the document alone in
+    // the middle of tracking.
+    fn perform_edit(&mut self, edit: impl FnOnce(&mut Self)) {
+        if !matches!(self.tracking_state, TrackingState::None) {
+            return;
+        }
+        self.history.begin_transaction();
+        edit(self);
+        self.history.commit_transaction(&self.doc);
+    }
+
+    // Change the stacking order of the selection.
+    fn arrange_selection(&mut self, arrange: Arrange) {
+        let selection = self.selection.clone();
+        self.perform_edit(|model| model.arrange_shape_ids(&selection, arrange));
+    }
+
+    // Delete the selection.
+    fn delete_selection(&mut self) {
+        let selection = self.selection.clone();
+        self.perform_edit(|model| {
+            model.delete_shape_ids(&selection);
+            model.selection.clear();
+        });
+    }
+
+    // Move the selection -- e.g., with the arrow keys.
+    fn nudge_selection(&mut self, delta: &XYPoint) {
+        let selection = self.selection.clone();
+        self.perform_edit(|model| model.offset_shape_ids_by(&selection, delta));
+    }
+
+    // Line up the selection.
+    fn align_selection(&mut self, alignment: Alignment) {
+        let selection = self.selection.clone();
+        self.perform_edit(|model| model.align_shape_ids(&selection, alignment));
+    }
+
+    // Space the selection out evenly.
+    fn distribute_selection(&mut self, distribution: Distribution) {
+        let selection = self.selection.clone();
+        self.perform_edit(|model| model.distribute_shape_ids(&selection, distribution));
+    }
+
+    // Group the selection and select the new group.
+    fn group_selection(&mut self) {
+        if self.selection.is_empty() {
+            return;
+        }
+        let selection = self.selection.clone();
+        self.perform_edit(|model| {
+            let group_id = model.group_shape_ids(&selection);
+            if model.doc.is_group(&group_id) {
+                model.select_only(&group_id);
+            }
+        });
+    }
+
+    // Ungroup the selected groups and select their former children along
+    // with anything else that was selected.
+    fn ungroup_selection(&mut self) {
+        let group_ids: Vec<ShapeId> = self
+            .selection
+            .iter()
+            .filter(|shape_id| self.doc.is_group(shape_id))
+            .copied()
+            .collect();
+        self.perform_edit(|model| {
+            for group_id in &group_ids {
+                model.selection.remove(group_id);
+                let child_ids = model.ungroup_shape_id(group_id);
+                model.selection.extend(child_ids);
+            }
+        });
+    }
+
+    // Selection
+
+    // Replace the selection with a single shape
+    fn select_only(&mut self, shape_id: &ShapeId) {
+        self.selection.clear();
+        self.selection.insert(*shape_id);
+    }
+
+    // Select the top-level shapes and groups on the visible, unlocked
+    // layers. We leave the selection alone in the middle of tracking.
+    fn select_all(&mut self) {
+        if !matches!(self.tracking_state, TrackingState::None) {
+            return;
+        }
+        let doc = &self.doc;
+        self.selection = doc
+            .layer_ids_iter()
+            .filter(|layer_id| doc.is_editable(layer_id))
+            .flat_map(|layer_id| doc.group_child_ids_iter(layer_id))
+            .copied()
+            .collect();
+    }
+
+    // Add the shape to the selection if it is not selected and remove
+    // it if it is
+    fn toggle_selection(&mut self, shape_id: &ShapeId) {
+        if !self.selection.remove(shape_id) {
+            self.selection.insert(*shape_id);
+        }
+    }
+
+    // Drop any shapes from the selection that are no longer in the
+    // document -- e.g., after an undo -- or that are on a layer that has
+    // been hidden or locked.
+    fn prune_selection(&mut self) {
+        let doc = &self.doc;
+        self.selection.retain(|shape_id| {
+            (doc.get_shape_by_id(shape_id).is_some() || doc.is_group(shape_id))
+                && doc.is_editable(shape_id)
+        });
+    }
+
+    // Generate a new shape id
+    fn generate_shape_id(&mut self) -> ShapeId {
+        self.doc.generate_shape_id()
+    }
+
+    // The bounds of what dragged shapes can line up with: the top-level
+    // shapes and groups on visible layers, leaving out the ones that are
+    // moving.
+    fn guide_targets(&self, moving: &HashSet<ShapeId>) -> GuideTargets {
+        let doc = &self.doc;
+        GuideTargets::new(
+            doc.layer_ids_iter()
+                .filter(|layer_id| {
+                    doc.get_layer_by_id(layer_id)
+                        .is_some_and(|layer| layer.visible)
+                })
+                .flat_map(|layer_id| doc.group_child_ids_iter(layer_id))
+                .filter(|shape_id| !moving.contains(shape_id))
+                .filter_map(|shape_id| doc.bounds_for_id(shape_id))
+                .collect(),
+        )
+    }
+
+    // Snap a point for drawing a new shape. Along each axis, the point
+    // snaps to a nearby line of another shape or else to the grid.
+    fn snap_drawing_point(&self, point: &XYPoint, targets: &GuideTargets) -> XYPoint {
+        let tolerance = self.viewport.document_length(GUIDE_SNAP_TOLERANCE);
+        let (guide_x, guide_y) = targets.snap_point(point, tolerance);
+        let grid_point = self.grid.snap_point(point);
+        XYPoint::new(
+            guide_x.unwrap_or(grid_point.x),
+            guide_y.unwrap_or(grid_point.y),
+        )
+    }
+
+    // Convert the position of a mouse event to document coordinates.
+    fn document_point(&self, mouse_event: &MouseEvent) -> XYPoint {
+        self.viewport
+            .to_document(&get_canvas_coordinates(mouse_event))
+    }
+
+    // Convert the position of a tracking event to document coordinates.
+    fn tracking_point(&self, tracking_event: &tracking::Event) -> XYPoint {
+        self.viewport
+            .to_document(&tracking_canvas_coordinates(tracking_event))
+    }
+
+    // Is a click position close enough to a point to count as a click on
+    // that point? Both are in document coordinates but the tolerance is
+    // in pixels on the canvas.
+    fn is_within_click_tolerance(&self, position: &XYPoint, point: &XYPoint) -> bool {
+        let tolerance = self.viewport.document_length(CLICK_TOLERANCE);
+        let offset = position.subtract(point);
+        offset.x.abs() <= tolerance && offset.y.abs() <= tolerance
+    }
+
+    // Set the tracking state. The edits made while tracking form a
+    // single undoable step.
+    fn set_tracking_state(&mut self, new_tracking_state: TrackingState) {
+        self.tracking_state = new_tracking_state;
+        self.history.begin_transaction();
+    }
+
+    // Stop tracking early -- e.g., on Escape -- and put the document back
+    // the way it was before the pointer down: new shapes go away and moved,
+    // resized, or reshaped ones return to where they were. A cancelled
+    // marquee restores the selection it started with. Any further events
+    // from the gesture are ignored since there is no tracking state to
+    // handle them.
+
+    fn cancel_tracking(&mut self) {
+        if let TrackingState::Marquee(marquee) = &self.tracking_state {
+            self.selection = marquee.original_selection.clone();
+        }
+        self.tracking_state = TrackingState::None;
+        self.history.abandon_transaction(&mut self.doc);
+        self.prune_selection();
+    }
+
+    // Clear the tracking state and commit the edits made while tracking.
+
+    fn stop_tracking(&mut self) {
+        self.tracking_state = TrackingState::None;
+        self.history.commit_transaction(&self.doc);
+    }
+}
+
+// Tracking
+
+// When we are tracking a pointer, we have a tracking state
+// that is used to hold the information about the type of
+// tracking we are doing.
+
+#[derive(Clone)]
+enum TrackingState {
+    None,
+    DragNewShape(DragNewShape),
+    DrawPolyline(DrawPolyline),
+    DrawPath(DrawPath),
+    DragNode(DragNode),
+    DragShape(DragShape),
+    Marquee(Marquee),
+    ResizeShape(ResizeShape),
+    RotateShape(RotateShape),
+    Pan(Pan),
+}
+
+// Dispatch updates based on the tracking state
+impl TrackingState {
+    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
+        match self {
+            TrackingState::None => {}
+            TrackingState::DragNewShape(drag_new_shape) => {
+                drag_new_shape.update_model_for_tracking_event(model, tracking_event)
+            }
+            TrackingState::DrawPolyline(draw_polyline) => {
+                draw_polyline.update_model_for_tracking_event(model, tracking_event)
+            }
+            TrackingState::DrawPath(draw_path) => {
+                draw_path.update_model_for_tracking_event(model, tracking_event)
+            }
+            TrackingState::DragNode(drag_node) => {
+                drag_node.update_model_for_tracking_event(model, tracking_event)
+            }
+            TrackingState::DragShape(drag_shape) => {
+                drag_shape.update_model_for_tracking_event(model, tracking_event)
+            }
+            TrackingState::Marquee(marquee) => {
+                marquee.update_model_for_tracking_event(model, tracking_event)
+            }
+            TrackingState::ResizeShape(resize_shape) => {
+                resize_shape.update_model_for_tracking_event(model, tracking_event)
+            }
+            TrackingState::RotateShape(rotate_shape) => {
+                rotate_shape.update_model_for_tracking_event(model, tracking_event)
+            }
+            TrackingState::Pan(pan) => pan.update_model_for_tracking_event(model, tracking_event),
+        }
+    }
+
+    // Does the tracking continue past pointer up to include further clicks?
+    fn spans_clicks(&self) -> bool {
+        matches!(
+            self,
+            TrackingState::DrawPolyline(_) | TrackingState::DrawPath(_)
+        )
+    }
+
+    // Dispatch pointer downs for tracking that spans clicks.
+    fn update_model_for_pointer_down(&self, model: &mut Model, pointer_down: &PointerEvent) {
+        if let TrackingState::DrawPath(draw_path) = self {
+            draw_path.update_model_for_pointer_down(model, pointer_down)
+        }
+    }
+
+    // Some tracking states draw feedback on top of the document.
+    fn render_overlay(&self, viewport: &Viewport) -> Option<Node<Msg>> {
+        match self {
+            TrackingState::Marquee(marquee) => Some(marquee.render(viewport)),
+            TrackingState::DragNewShape(DragNewShape { guides, .. })
+            | TrackingState::DragShape(DragShape { guides, .. }) => {
+                Some(render_guides(guides, viewport))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Guides are lines on the canvas in a color that stands apart from the
+// selection. They ignore the mouse.
+
+const GUIDE_COLOR: &str = "magenta";
+
+fn render_guides(guides: &[Guide], viewport: &Viewport) -> Node<Msg> {
+    use svg::attributes::*;
+    use svg::*;
+
+    g(
+        vec![id("guides"), pointer_events("none")],
+        guides.iter().map(|guide| {
+            let (from, to) = guide.ends();
+            let (from, to) = (viewport.to_screen(&from), viewport.to_screen(&to));
+            line(
+                vec![
+                    x1(from.x),
+                    y1(from.y),
+                    x2(to.x),
+                    y2(to.y),
+                    stroke(GUIDE_COLOR),
+                    stroke_width(1),
+                ],
+                [],
+            )
+        }),
+    )
+}
+
+// Dragging out a new shape: the pointer down and drag positions give the
+// corners of the bounds for rectangles and ellipses and the ends of lines.
+// Both positions snap to the lines of other shapes when close to them and
+// otherwise, with snapping on, to the grid. Guides show where the new
+// shape lines up with other shapes.
+
+#[derive(Clone)]
+struct DragNewShape {
+    tool: Tool,
+    shape_id: ShapeId,
+    pointer_down_position: XYPoint,
+    style: Style,
+    targets: Rc<GuideTargets>,
+    guides: Vec<Guide>,
+}
+
+impl DragNewShape {
+    fn start(model: &mut Model, tool: Tool, pointer_down: &PointerEvent) {
+        let shape_id = model.generate_shape_id();
+        let style = model.get_new_shape_style();
+        let targets = Rc::new(model.guide_targets(&HashSet::new()));
+        let pointer_down_position =
+            model.snap_drawing_point(&model.document_point(pointer_down), &targets);
+        model.set_tracking_state(TrackingState::DragNewShape(Self {
+            tool,
+            shape_id,
+            style,
+            pointer_down_position,
+            targets,
+            guides: Vec::new(),
+        }))
+    }
+
+    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
+        let drag_position =
+            model.snap_drawing_point(&model.tracking_point(tracking_event), &self.targets);
+        let mut guides = Vec::new();
+        match self
+            .tool
+            .geometry_for_drag(&self.pointer_down_position, &drag_position)
+        {
+            // If non-empty, upsert the shape
+            Some(geometry) => {
+                guides = self.targets.guides_for(&geometry.bounds());
+                model
+                    .upsert_shape_with_id(&self.shape_id, Shape::new(geometry, self.style.clone()));
+            }
+            // If empty, delete the shape.
+            None => model.delete_shape_with_id(&self.shape_id),
+        }
+        if tracking_event.selector == tracking::Selector::Up {
+            // Select the new shape if we made one.
+            if model.doc.get_shape_by_id(&self.shape_id).is_some() {
+                model.selection.insert(self.shape_id);
+            }
+            model.stop_tracking()
+        } else {
+            model.set_tracking_state(TrackingState::DragNewShape(Self {
+                guides,
+                ..self.clone()
+            }))
+        }
+    }
+}
+
+// Drawing a polyline or polygon spans several clicks. Each pointer up fixes
+// a point and moving the mouse rubber-bands a segment from the last fixed
+// point. Clicking on the last fixed point again (i.e., double clicking)
+// finishes the shape as does clicking on the first point of a polygon.
+
+#[derive(Clone)]
+struct DrawPolyline {
+    closed: bool,
+    shape_id: ShapeId,
+    fixed_points: Vec<XYPoint>,
+    style: Style,
 }
 
-impl Msg {
-    pub fn from_tracking(tracking_event: &tracking::Event) -> Self {
-        Self::FromTracking(tracking_event.clone())
+// How close (in pixels) a click must be to a point to count as a click on
+// that point.
+const CLICK_TOLERANCE: f64 = 4.0;
+
+// How close (in pixels) a dragged line must come to a line of another
+// shape to snap to it.
+const GUIDE_SNAP_TOLERANCE: f64 = 5.0;
+
+impl DrawPolyline {
+    fn start(model: &mut Model, closed: bool, pointer_down: &PointerEvent) {
+        let shape_id = model.generate_shape_id();
+        let style = model.get_new_shape_style();
+        model.set_tracking_state(TrackingState::DrawPolyline(Self {
+            closed,
+            shape_id,
+            fixed_points: vec![model.document_point(pointer_down)],
+            style,
+        }))
     }
-}
 
-impl Model {
-    // Generate a new document. At this point, we wire it to the demo
-    // case but in the future, this will likely just generate an empty
-    // document.
-    pub fn new() -> Self {
-        Self::new_demo()
+    // The geometry for some points if there are enough of them.
+    fn geometry_for_points(&self, points: Vec<XYPoint>) -> Option<Geometry> {
+        match (self.closed, points.len()) {
+            (true, 3..) => Some(Geometry::Polygon { points }),
+            (false, 2..) => Some(Geometry::Polyline { points }),
+            _ => None,
+        }
     }
 
-    pub fn update(&mut self, msg: &Msg) {
-        match msg {
-            Msg::ShapeMouseDown(shape_id, mouse_down) => {
-                DragShape::start(self, shape_id, mouse_down);
-            }
-
-            Msg::BackgroundMouseDown(mouse_down) => {
-                DragNewRect::start(self, mouse_down);
-                // Advance the fill color skipping white. This is purely
-                // part of the demo logic to make shape drawing more
-                // interesting.
-                loop {
-                    self.fill_color.advance();
-                    if self.fill_color != Color::White {
-                        break;
-                    }
-                }
+    fn update_shape(&self, model: &mut Model, points: Vec<XYPoint>) {
+        match self.geometry_for_points(points) {
+            Some(geometry) => {
+                let shape = Shape::new(geometry, self.style.clone());
+                model.upsert_shape_with_id(&self.shape_id, shape);
             }
+            None => model.delete_shape_with_id(&self.shape_id),
+        }
+    }
 
-            Msg::FromTracking(tracking_event) => {
-                // Clone so that we do not get an alias conflict over the
-                // tracking state.
-                self.tracking_state
-                    .clone()
-                    .update_model_for_tracking_event(self, tracking_event)
+    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
+        let position = model.tracking_point(tracking_event);
+        let is_near = |point: &XYPoint| model.is_within_click_tolerance(&position, point);
+        let near_last = self.fixed_points.last().is_some_and(is_near);
+        let near_first =
+            self.closed && 3 <= self.fixed_points.len() && is_near(&self.fixed_points[0]);
+
+        if tracking_event.selector != tracking::Selector::Up {
+            // Rubber-band from the last fixed point.
+            let mut points = self.fixed_points.clone();
+            if !near_last {
+                points.push(position);
+            }
+            self.update_shape(model, points);
+        } else if (near_last && 2 <= self.fixed_points.len()) || near_first {
+            // Finish with just the fixed points.
+            self.update_shape(model, self.fixed_points.clone());
+            if model.doc.get_shape_by_id(&self.shape_id).is_some() {
+                model.selection.insert(self.shape_id);
             }
+            model.stop_tracking()
+        } else if near_last {
+            // The pointer up from the first click: keep going.
+        } else {
+            // Fix another point and keep going.
+            let mut fixed_points = self.fixed_points.clone();
+            fixed_points.push(position);
+            model.set_tracking_state(TrackingState::DrawPolyline(Self {
+                fixed_points,
+                ..self.clone()
+            }))
         }
     }
+}
 
-    pub fn view(&self) -> Node<Msg> {
-        use svg::attributes::*;
-        use svg::*;
-
-        let shape_count = self.doc.shape_ids_sequence_iter().count();
+// Dragging moves all of the selected shapes together, including all of
+// the shapes inside selected groups. We remember where each shape started
+// so that each update is relative to the original shape. The edges and
+// center of the bounds around all of the shapes snap to the lines of other
+// shapes when close to them, with guides to show it, and otherwise, with
+// snapping on, the edges snap to the grid.
 
-        let mut children = Vec::with_capacity(shape_count + 1);
+#[derive(Clone)]
+struct DragShape {
+    original_shapes: Vec<(ShapeId, Shape)>,
+    original_bounds: Option<Bounds>,
+    pointer_down_position: XYPoint,
+    targets: Rc<GuideTargets>,
+    guides: Vec<Guide>,
+}
 
-        // Add the background
+impl DragShape {
+    fn start(model: &mut Model, pointer_down: &PointerEvent) {
+        let original_shapes: Vec<(ShapeId, Shape)> = model
+            .selection
+            .iter()
+            .flat_map(|shape_id| model.doc.shape_ids_in(shape_id))
+            .filter_map(|shape_id| {
+                model
+                    .doc
+                    .get_shape_by_id(&shape_id)
+                    .map(|shape| (shape_id, shape.clone()))
+            })
+            .collect();
+        if !original_shapes.is_empty() {
+            let original_bounds = original_shapes
+                .iter()
+                .map(|(_, shape)| shape.bounds())
+                .reduce(|acc, bounds| acc.union(&bounds));
+            let targets = Rc::new(model.guide_targets(&model.selection));
+            model.set_tracking_state(TrackingState::DragShape(Self {
+                original_shapes,
+                original_bounds,
+                pointer_down_position: model.document_point(pointer_down),
+                targets,
+                guides: Vec::new(),
+            }))
+        }
+    }
 
-        children.push(background());
+    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
+        let drag_position = model.tracking_point(tracking_event);
+        let mut delta = drag_position.subtract(&self.pointer_down_position);
+        let mut guides = Vec::new();
+        if let Some(bounds) = &self.original_bounds {
+            let tolerance = model.viewport.document_length(GUIDE_SNAP_TOLERANCE);
+            let (guide_x, guide_y) = self.targets.snap_offset(bounds, &delta, tolerance);
+            let grid_delta = model.grid.snap_offset_for_bounds(bounds, &delta);
+            delta = XYPoint::new(
+                guide_x.unwrap_or(grid_delta.x),
+                guide_y.unwrap_or(grid_delta.y),
+            );
+            guides = self
+                .targets
+                .guides_for(&Bounds::new(bounds.min.add(&delta), bounds.max.add(&delta)));
+        }
+        for (shape_id, original_shape) in &self.original_shapes {
+            model.upsert_shape_with_id(shape_id, original_shape.offset_by(&delta));
+        }
+        if tracking_event.selector == tracking::Selector::Up {
+            model.stop_tracking()
+        } else {
+            model.set_tracking_state(TrackingState::DragShape(Self {
+                guides,
+                ..self.clone()
+            }))
+        }
+    }
+}
 
-        // Add the shapes to the children
+// Dragging out a marquee selects the shapes whose bounds intersect the
+// marquee or, with the alt key down, the shapes entirely within the
+// marquee. With the shift key down at the start, the marquee adds to the
+// existing selection. As with clicks, we select top-level groups rather
+// than the shapes inside them and skip hidden and locked layers. A
+// marquee that never opens up -- a click on the background -- has no area
+// to intersect, so it selects what a click there would hit, if anything.
 
-        children.extend(
-            self.doc
-                .shape_id_shapes_iter()
-                .map(|(shape_id, shape)| render_shape(shape_id, shape)),
-        );
+#[derive(Clone)]
+struct Marquee {
+    pointer_down_position: XYPoint,
+    drag_position: XYPoint,
+    original_selection: HashSet<ShapeId>,
+}
 
-        svg(
-            [
-                width("100%"),
-                height("1500px"),
-                preserve_aspect_ratio("none"),
-            ],
-            children,
-        )
+impl Marquee {
+    fn start(model: &mut Model, pointer_down: &PointerEvent) {
+        let pointer_down_position = model.document_point(pointer_down);
+        model.set_tracking_state(TrackingState::Marquee(Self {
+            drag_position: pointer_down_position.clone(),
+            pointer_down_position,
+            original_selection: model.selection.clone(),
+        }))
     }
-}
 
-// Render a shape to SVG and attach a mouse down handler that
-// initiates dragging.
+    fn bounds(&self) -> Bounds {
+        Bounds::from_corners(&self.pointer_down_position, &self.drag_position)
+    }
 
-fn render_shape(shape_id: ShapeId, shape: &Shape) -> Node<Msg> {
-    use svg::attributes::*;
-    use svg::*;
+    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
+        let marquee = Self {
+            drag_position: model.tracking_point(tracking_event),
+            ..self.clone()
+        };
+        let bounds = marquee.bounds();
+        let is_click = bounds.width() == 0.0 && bounds.height() == 0.0;
+        let within = tracking_event.modifiers.alt && !is_click;
+        let shape_hits = if is_click {
+            model
+                .doc
+                .topmost_shape_at(&bounds.min)
+                .into_iter()
+                .collect()
+        } else if within {
+            model.doc.shape_ids_within(&bounds)
+        } else {
+            model.doc.shape_ids_intersecting(&bounds)
+        };
+        let mut hits: HashSet<ShapeId> = shape_hits
+            .iter()
+            .filter(|shape_id| model.doc.is_editable(shape_id))
+            .map(|shape_id| model.doc.top_level_id_for(shape_id))
+            .collect();
+        if within {
+            hits.retain(|shape_id| {
+                model
+                    .doc
+                    .bounds_for_id(shape_id)
+                    .is_some_and(|hit_bounds| bounds.contains_bounds(&hit_bounds))
+            });
+        }
+        model.selection = marquee.original_selection.clone();
+        model.selection.extend(hits);
+        if tracking_event.selector == tracking::Selector::Up {
+            model.stop_tracking()
+        } else {
+            model.set_tracking_state(TrackingState::Marquee(marquee))
+        }
+    }
 
-    let id_string = format!("shape_{shape_id}");
-    let fill_color = svg_color(&shape.style.fill);
+    fn render(&self, viewport: &Viewport) -> Node<Msg> {
+        use svg::attributes::*;
+        use svg::*;
 
-    match &shape.geometry {
-        Geometry::Circle { center, radius } => circle(
-            vec![
-                id(id_string),
-                cx(center.x),
-                cy(center.y),
-                r(*radius),
-                fill(fill_color),
-                events::on_mousedown(move |evt| shape_mouse_down(shape_id, evt)),
-            ],
-            [],
-        ),
-        Geometry::Rectangle { top_left, size } => rect(
+        let bounds = viewport.transform().apply_to_bounds(&self.bounds());
+        rect(
             vec![
-                id(id_string),
-                x(top_left.x),
-                y(top_left.y),
-                width(size.x),
-                height(size.y),
-                fill(fill_color),
-                events::on_mousedown(move |evt| shape_mouse_down(shape_id, evt)),
+                id("marquee"),
+                x(bounds.min.x),
+                y(bounds.min.y),
+                width(bounds.width()),
+                height(bounds.height()),
+                fill("dodgerblue"),
+                fill_opacity(0.1),
+                stroke("dodgerblue"),
+                stroke_width(1),
+                pointer_events("none"),
             ],
             [],
-        ),
+        )
     }
 }
 
-fn shape_mouse_down(shape_id: ShapeId, evt: MouseEvent) -> Msg {
-    evt.stop_propagation();
-    Msg::ShapeMouseDown(shape_id, evt)
-}
-
-fn background() -> Node<Msg> {
-    use svg::attributes::*;
-    use svg::*;
+// Panning drags the document around the canvas. Unlike the other tracking,
+// it works in canvas coordinates since the mapping to document coordinates
+// changes as we go. Panning can interrupt drawing a shape that spans
+// several clicks, in which case the drawing picks up again afterwards.
 
-    rect(
-        vec![
-            id("background"),
-            x("0"),
-            y("0"),
-            width("100%"),
-            height("100%"),
-            fill("white"),
-            events::on_mousedown(background_mouse_down),
-        ],
-        [],
-    )
+#[derive(Clone)]
+struct Pan {
+    last_position: XYPoint,
+    interrupted: Option<Box<TrackingState>>,
 }
 
-fn svg_color(color: &Color) -> String {
-    match color {
-        Color::Red => "red".to_string(),
-        Color::Orange => "orange".to_string(),
-        Color::Yellow => "yellow".to_string(),
-        Color::Green => "green".to_string(),
-        Color::Blue => "blue".to_string(),
-        Color::Indigo => "indigo".to_string(),
-        Color::Violet => "violet".to_string(),
-        Color::White => "white".to_string(),
-        Color::Black => "black".to_string(),
+impl Pan {
+    fn start(model: &mut Model, pointer_down: &PointerEvent) {
+        let interrupted = model
+            .tracking_state
+            .spans_clicks()
+            .then(|| Box::new(model.tracking_state.clone()));
+        model.set_tracking_state(TrackingState::Pan(Self {
+            last_position: get_canvas_coordinates(pointer_down),
+            interrupted,
+        }))
+    }
+
+    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
+        let position = tracking_canvas_coordinates(tracking_event);
+        model.viewport = model
+            .viewport
+            .panned_by(&position.subtract(&self.last_position));
+        if tracking_event.selector != tracking::Selector::Up {
+            model.set_tracking_state(TrackingState::Pan(Self {
+                last_position: position,
+                ..self.clone()
+            }))
+        } else if let Some(interrupted) = &self.interrupted {
+            model.set_tracking_state((**interrupted).clone())
+        } else {
+            model.stop_tracking()
+        }
     }
 }
 
-fn background_mouse_down(evt: MouseEvent) -> Msg {
-    evt.stop_propagation();
-    Msg::BackgroundMouseDown(evt)
+// There are eight resize handles: one for each corner and one for each
+// edge. A handle moves the edges in its direction -- e.g., the top left
+// handle moves the top and left edges.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Handle {
+    TopLeft,
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
 }
 
-impl Model {
-    // Create a new demo model
-    fn new_demo() -> Self {
-        Self {
-            doc: Document::new_demo(),
-            fill_color: Color::Red,
-            tracking_state: TrackingState::None,
+impl Handle {
+    fn all() -> &'static [Handle] {
+        &[
+            Handle::TopLeft,
+            Handle::Top,
+            Handle::TopRight,
+            Handle::Right,
+            Handle::BottomRight,
+            Handle::Bottom,
+            Handle::BottomLeft,
+            Handle::Left,
+        ]
+    }
+
+    // The direction in which the handle moves the edges along each axis:
+    // -1 for the min edge, 1 for the max edge, and 0 for neither.
+    fn directions(&self) -> (i8, i8) {
+        match self {
+            Handle::TopLeft => (-1, -1),
+            Handle::Top => (0, -1),
+            Handle::TopRight => (1, -1),
+            Handle::Right => (1, 0),
+            Handle::BottomRight => (1, 1),
+            Handle::Bottom => (0, 1),
+            Handle::BottomLeft => (-1, 1),
+            Handle::Left => (-1, 0),
         }
     }
 
-    // Get the style for new shapes
-    fn get_new_shape_style(&self) -> Style {
-        let fill_color = self.fill_color.clone();
-        Style::new(fill_color)
+    fn position_on(&self, bounds: &Bounds) -> XYPoint {
+        let center = bounds.center();
+        let coordinate = |direction: i8, min: f64, mid: f64, max: f64| match direction {
+            -1 => min,
+            1 => max,
+            _ => mid,
+        };
+        let (x_direction, y_direction) = self.directions();
+        XYPoint::new(
+            coordinate(x_direction, bounds.min.x, center.x, bounds.max.x),
+            coordinate(y_direction, bounds.min.y, center.y, bounds.max.y),
+        )
     }
 
-    // Upsert a shape
-    // https://en.wiktionary.org/wiki/upsert
-    fn upsert_shape_with_id(&mut self, shape_id: &ShapeId, new_shape: Shape) {
-        self.doc.upsert_shape_with_id(shape_id, new_shape);
+    fn cursor(&self) -> &'static str {
+        match self {
+            Handle::TopLeft | Handle::BottomRight => "nwse-resize",
+            Handle::TopRight | Handle::BottomLeft => "nesw-resize",
+            Handle::Top | Handle::Bottom => "ns-resize",
+            Handle::Left | Handle::Right => "ew-resize",
+        }
     }
+}
 
-    // Delete a shape if it exists. Do nothing if it does not.
-    fn delete_shape_with_id(&mut self, shape_id: &ShapeId) {
-        self.doc.delete_shape_with_id(shape_id);
+// Resizing drags one of the handles of a shape. Holding shift preserves
+// the aspect ratio and holding alt resizes around the center rather than
+// around the opposite edge. Circles always preserve their aspect ratio.
+// Transformed shapes resize in their own space. With snapping on, the
+// edges that the handle moves snap to the grid, except for transformed
+// shapes whose own space does not line up with the grid.
+
+#[derive(Clone)]
+struct ResizeShape {
+    shape_id: ShapeId,
+    handle: Handle,
+    original_geometry: Geometry,
+    transform: Transform,
+    pointer_down_position: XYPoint,
+}
+
+impl ResizeShape {
+    fn start(model: &mut Model, shape_id: &ShapeId, handle: Handle, pointer_down: &PointerEvent) {
+        if let Some(shape) = model.doc.get_shape_by_id(shape_id) {
+            model.set_tracking_state(TrackingState::ResizeShape(Self {
+                shape_id: *shape_id,
+                handle,
+                original_geometry: shape.geometry.clone(),
+                transform: shape.transform.clone(),
+                pointer_down_position: model.document_point(pointer_down),
+            }))
+        }
     }
 
-    // Replace the geometry of a shape
-    fn set_geometry_for_shape_with_id(&mut self, shape_id: &ShapeId, new_geometry: Geometry) {
-        self.doc.set_geometry_for_shape_id(shape_id, new_geometry);
+    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
+        let modifiers = tracking_event.modifiers;
+        let drag_position = model.tracking_point(tracking_event);
+        let mut delta = local_delta(&self.transform, &self.pointer_down_position, &drag_position);
+        if self.transform.is_identity() {
+            delta = self.snapped_delta(&model.grid, &delta);
+        }
+        let preserve_aspect =
+            modifiers.shift || matches!(self.original_geometry, Geometry::Circle { .. });
+        let bounds = resized_bounds(
+            &self.original_geometry.bounds(),
+            self.handle,
+            &delta,
+            preserve_aspect,
+            modifiers.alt,
+        );
+        model.set_geometry_for_shape_with_id(
+            &self.shape_id,
+            self.original_geometry.resized_to_bounds(&bounds),
+        );
+        if tracking_event.selector == tracking::Selector::Up {
+            model.stop_tracking()
+        }
     }
+}
 
-    // Generate a new shape id
-    fn generate_shape_id(&mut self) -> ShapeId {
-        self.doc.generate_shape_id()
+impl ResizeShape {
+    // Adjust the offset for the handle so that the edges it moves land on
+    // the grid.
+    fn snapped_delta(&self, grid: &Grid, delta: &XYPoint) -> XYPoint {
+        let bounds = self.original_geometry.bounds();
+        let snap_along = |direction: i8, min: f64, max: f64, offset: f64| match direction {
+            -1 => grid.snap_offset_for_edge(min, offset),
+            1 => grid.snap_offset_for_edge(max, offset),
+            _ => offset,
+        };
+        let (x_direction, y_direction) = self.handle.directions();
+        XYPoint::new(
+            snap_along(x_direction, bounds.min.x, bounds.max.x, delta.x),
+            snap_along(y_direction, bounds.min.y, bounds.max.y, delta.y),
+        )
     }
+}
 
-    // Set the tracking state
-    fn set_tracking_state(&mut self, new_tracking_state: TrackingState) {
-        self.tracking_state = new_tracking_state;
+// Compute the bounds that result from dragging a handle by delta.
+// Dragging past the opposite edge flips the bounds.
+
+fn resized_bounds(
+    original: &Bounds,
+    handle: Handle,
+    delta: &XYPoint,
+    preserve_aspect: bool,
+    from_center: bool,
+) -> Bounds {
+    let (x_direction, y_direction) = handle.directions();
+    let center = original.center();
+    let mut x_span = resize_span(
+        original.min.x,
+        original.max.x,
+        center.x,
+        x_direction,
+        delta.x,
+        from_center,
+    );
+    let mut y_span = resize_span(
+        original.min.y,
+        original.max.y,
+        center.y,
+        y_direction,
+        delta.y,
+        from_center,
+    );
+    if preserve_aspect {
+        let scale_x = span_length(x_span) / original.width();
+        let scale_y = span_length(y_span) / original.height();
+        // Edge handles scale by their own axis, corner handles by the
+        // larger change.
+        let scale = match (x_direction, y_direction) {
+            (0, _) => scale_y,
+            (_, 0) => scale_x,
+            _ => scale_x.max(scale_y),
+        };
+        // Degenerate (zero width or height) shapes cannot keep an aspect
+        // ratio.
+        if scale.is_finite() {
+            x_span = fit_span(
+                x_span,
+                center.x,
+                x_direction,
+                original.width() * scale,
+                from_center,
+            );
+            y_span = fit_span(
+                y_span,
+                center.y,
+                y_direction,
+                original.height() * scale,
+                from_center,
+            );
+        }
     }
+    Bounds::from_corners(
+        &XYPoint::new(x_span.0, y_span.0),
+        &XYPoint::new(x_span.1, y_span.1),
+    )
+}
 
-    // Clear the tracking state
+// Resize the span along one axis, returning the fixed end followed by the
+// moving end. Spans that do not move come back as (min, max).
 
-    fn stop_tracking(&mut self) {
-        self.tracking_state = TrackingState::None;
+fn resize_span(
+    min: f64,
+    max: f64,
+    center: f64,
+    direction: i8,
+    delta: f64,
+    from_center: bool,
+) -> (f64, f64) {
+    let (fixed, moving) = match direction {
+        -1 => (max, min + delta),
+        1 => (min, max + delta),
+        _ => return (min, max),
+    };
+    if from_center {
+        (2.0 * center - moving, moving)
+    } else {
+        (fixed, moving)
     }
 }
 
-// Tracking
+fn span_length(span: (f64, f64)) -> f64 {
+    (span.1 - span.0).abs()
+}
 
-// When we are tracking the mouse, we have a tracking state
-// that is used to hold the information about the type of
-// tracking we are doing.
+// Change the length of a span produced by resize_span while keeping its
+// anchor -- the fixed end, or the center when resizing from the center or
+// when the span does not move -- and its orientation.
+
+fn fit_span(
+    span: (f64, f64),
+    center: f64,
+    direction: i8,
+    length: f64,
+    from_center: bool,
+) -> (f64, f64) {
+    let (fixed, moving) = span;
+    if direction == 0 || from_center {
+        let sign = if direction == 0 || center <= moving {
+            1.0
+        } else {
+            -1.0
+        };
+        (center - sign * length / 2.0, center + sign * length / 2.0)
+    } else {
+        let sign = if fixed <= moving { 1.0 } else { -1.0 };
+        (fixed, fixed + sign * length)
+    }
+}
+
+// Drawing a path with the pen tool spans several clicks. Each pointer down
+// adds an anchor and dragging before the pointer up pulls out a control
+// handle for that anchor. The handle on the other side of the anchor
+// mirrors the dragged handle so that the curve passes smoothly through
+// the anchor. Between clicks, moving the mouse previews the next segment.
 
 #[derive(Clone)]
-enum TrackingState {
-    None,
-    DragNewRect(DragNewRect),
-    DragShape(DragShape),
+struct PenAnchor {
+    point: XYPoint,
+    // The outgoing control handle if any.
+    handle: Option<XYPoint>,
 }
 
-// Dispatch updates based on the tracking state
-impl TrackingState {
-    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
-        match self {
-            TrackingState::None => {}
-            TrackingState::DragNewRect(drag_new_rect) => {
-                drag_new_rect.update_model_for_tracking_event(model, tracking_event)
-            }
-            TrackingState::DragShape(drag_shape) => {
-                drag_shape.update_model_for_tracking_event(model, tracking_event)
-            }
+impl PenAnchor {
+    fn new(point: XYPoint) -> Self {
+        Self {
+            point,
+            handle: None,
         }
     }
+
+    // The incoming control handle is the outgoing one mirrored through
+    // the anchor.
+    fn incoming_handle(&self) -> Option<XYPoint> {
+        self.handle
+            .as_ref()
+            .map(|handle| self.point.add(&self.point.subtract(handle)))
+    }
 }
 
 #[derive(Clone)]
-struct DragNewRect {
+struct DrawPath {
     shape_id: ShapeId,
-    mouse_down_position: XYPoint,
+    anchors: Vec<PenAnchor>,
+    // Is the pointer down -- i.e., are we pulling out a handle?
+    pulling_handle: bool,
     style: Style,
 }
 
-impl DragNewRect {
-    fn start(model: &mut Model, mouse_down: &MouseEvent) {
+impl DrawPath {
+    fn start(model: &mut Model, pointer_down: &PointerEvent) {
         let shape_id = model.generate_shape_id();
         let style = model.get_new_shape_style();
-        model.set_tracking_state(TrackingState::DragNewRect(Self {
+        model.set_tracking_state(TrackingState::DrawPath(Self {
             shape_id,
+            anchors: vec![PenAnchor::new(model.document_point(pointer_down))],
+            pulling_handle: true,
             style,
-            mouse_down_position: get_page_coordinates(mouse_down),
         }))
     }
 
-    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
-        let drag_position = get_page_coordinates(&tracking_event.mouse_event);
-        let (min_x, span_x) = to_min_span(self.mouse_down_position.x, drag_position.x);
-        let (min_y, span_y) = to_min_span(self.mouse_down_position.y, drag_position.y);
-        // If non-empty, upsert the shape
-        if 0.0 < span_x && 0.0 < span_y {
-            let geometry = Geometry::Rectangle {
-                top_left: XYPoint::new(min_x, min_y),
-                size: XYPoint::new(span_x, span_y),
-            };
-            let shape = Shape {
-                geometry,
-                style: self.style.clone(),
+    // Build path segments from anchors. Segments between anchors without
+    // handles are lines; otherwise, they are cubic Beziers.
+    fn path_segments(anchors: &[PenAnchor], closed: bool) -> Vec<PathSegment> {
+        let segment_between = |from: &PenAnchor, to: &PenAnchor| match (&from.handle, &to.handle) {
+            (None, None) => PathSegment::LineTo {
+                to: to.point.clone(),
+            },
+            _ => PathSegment::CubicTo {
+                control1: from.handle.clone().unwrap_or_else(|| from.point.clone()),
+                control2: to.incoming_handle().unwrap_or_else(|| to.point.clone()),
+                to: to.point.clone(),
+            },
+        };
+        let mut segments = vec![PathSegment::MoveTo {
+            to: anchors[0].point.clone(),
+        }];
+        segments.extend(
+            anchors
+                .windows(2)
+                .map(|pair| segment_between(&pair[0], &pair[1])),
+        );
+        if closed && let Some(last) = anchors.last() {
+            segments.push(segment_between(last, &anchors[0]));
+            segments.push(PathSegment::Close);
+        }
+        segments
+    }
+
+    // Update the shape for the anchors. It takes at least two anchors to
+    // make a path.
+    fn update_shape(&self, model: &mut Model, anchors: &[PenAnchor], closed: bool) {
+        if 2 <= anchors.len() {
+            let geometry = Geometry::Path {
+                segments: Self::path_segments(anchors, closed),
             };
-            model.upsert_shape_with_id(&self.shape_id, shape);
-        // If empty, delete the shape.
+            model.upsert_shape_with_id(&self.shape_id, Shape::new(geometry, self.style.clone()));
         } else {
-            model.delete_shape_with_id(&self.shape_id)
+            model.delete_shape_with_id(&self.shape_id);
         }
-        if tracking_event.selector == tracking::Selector::MouseUp {
-            model.stop_tracking()
+    }
+
+    fn finish(&self, model: &mut Model, closed: bool) {
+        self.update_shape(model, &self.anchors, closed);
+        if model.doc.get_shape_by_id(&self.shape_id).is_some() {
+            model.selection.insert(self.shape_id);
+        }
+        model.stop_tracking()
+    }
+
+    fn update_model_for_pointer_down(&self, model: &mut Model, pointer_down: &PointerEvent) {
+        let position = model.document_point(pointer_down);
+        let near_first = model.is_within_click_tolerance(&position, &self.anchors[0].point);
+        let near_last = self
+            .anchors
+            .last()
+            .is_some_and(|anchor| model.is_within_click_tolerance(&position, &anchor.point));
+        if 2 <= self.anchors.len() && near_last {
+            self.finish(model, false);
+        } else if 2 <= self.anchors.len() && near_first {
+            self.finish(model, true);
+        } else if !near_last {
+            let mut anchors = self.anchors.clone();
+            anchors.push(PenAnchor::new(position));
+            self.update_shape(model, &anchors, false);
+            model.set_tracking_state(TrackingState::DrawPath(Self {
+                anchors,
+                pulling_handle: true,
+                ..self.clone()
+            }))
+        }
+    }
+
+    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
+        let position = model.tracking_point(tracking_event);
+        if self.pulling_handle {
+            let mut anchors = self.anchors.clone();
+            if let Some(last) = anchors.last_mut() {
+                last.handle = if model.is_within_click_tolerance(&position, &last.point) {
+                    None
+                } else {
+                    Some(position)
+                };
+            }
+            self.update_shape(model, &anchors, false);
+            model.set_tracking_state(TrackingState::DrawPath(Self {
+                anchors,
+                pulling_handle: tracking_event.selector != tracking::Selector::Up,
+                ..self.clone()
+            }))
+        } else if tracking_event.selector == tracking::Selector::Move {
+            // Preview the segment to the mouse.
+            let mut anchors = self.anchors.clone();
+            anchors.push(PenAnchor::new(position));
+            self.update_shape(model, &anchors, false);
         }
     }
 }
 
+// Dragging a node moves a single anchor of a shape. As with resizing,
+// transformed shapes move the anchor in their own space.
+
 #[derive(Clone)]
-struct DragShape {
+struct DragNode {
     shape_id: ShapeId,
+    anchor_index: usize,
     original_geometry: Geometry,
-    mouse_down_position: XYPoint,
+    transform: Transform,
+    pointer_down_position: XYPoint,
 }
 
-impl DragShape {
-    fn start(model: &mut Model, shape_id: &ShapeId, mouse_down: &MouseEvent) {
+impl DragNode {
+    fn start(
+        model: &mut Model,
+        shape_id: &ShapeId,
+        anchor_index: usize,
+        pointer_down: &PointerEvent,
+    ) {
         if let Some(shape) = model.doc.get_shape_by_id(shape_id) {
-            model.set_tracking_state(TrackingState::DragShape(Self {
+            model.set_tracking_state(TrackingState::DragNode(Self {
                 shape_id: *shape_id,
+                anchor_index,
                 original_geometry: shape.geometry.clone(),
-                mouse_down_position: get_page_coordinates(mouse_down),
+                transform: shape.transform.clone(),
+                pointer_down_position: model.document_point(pointer_down),
             }))
         }
     }
 
     fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
-        let drag_position = get_page_coordinates(&tracking_event.mouse_event);
-        let delta = drag_position.subtract(&self.mouse_down_position);
+        let drag_position = model.tracking_point(tracking_event);
+        let delta = local_delta(&self.transform, &self.pointer_down_position, &drag_position);
         model.set_geometry_for_shape_with_id(
             &self.shape_id,
-            self.original_geometry.offset_by(&delta),
+            self.original_geometry
+                .with_anchor_moved(self.anchor_index, &delta),
+        );
+        if tracking_event.selector == tracking::Selector::Up {
+            model.stop_tracking()
+        }
+    }
+}
+
+// Rotating drags the rotation handle around the center of the shape.
+// Holding shift snaps the rotation of the shape -- not just the change in
+// rotation -- to multiples of ROTATION_SNAP_DEGREES.
+
+const ROTATION_SNAP_DEGREES: f64 = 15.0;
+
+#[derive(Clone)]
+struct RotateShape {
+    shape_id: ShapeId,
+    original_shape: Shape,
+    center: XYPoint,
+    pointer_down_angle: f64,
+}
+
+impl RotateShape {
+    fn start(model: &mut Model, shape_id: &ShapeId, pointer_down: &PointerEvent) {
+        if let Some(shape) = model.doc.get_shape_by_id(shape_id) {
+            let center = shape.center();
+            let pointer_down_angle = angle_around(&center, &model.document_point(pointer_down));
+            model.set_tracking_state(TrackingState::RotateShape(Self {
+                shape_id: *shape_id,
+                original_shape: shape.clone(),
+                center,
+                pointer_down_angle,
+            }))
+        }
+    }
+
+    fn update_model_for_tracking_event(&self, model: &mut Model, tracking_event: &tracking::Event) {
+        let modifiers = tracking_event.modifiers;
+        let drag_position = model.tracking_point(tracking_event);
+        let mut angle = angle_around(&self.center, &drag_position) - self.pointer_down_angle;
+        if modifiers.shift {
+            let original_angle = self.original_shape.transform.rotation_angle();
+            let snap = ROTATION_SNAP_DEGREES.to_radians();
+            angle = ((original_angle + angle) / snap).round() * snap - original_angle;
+        }
+        let rotation = Transform::rotation(angle).about(&self.center);
+        model.upsert_shape_with_id(
+            &self.shape_id,
+            self.original_shape
+                .with_transform(self.original_shape.transform.then(&rotation)),
         );
-        if tracking_event.selector == tracking::Selector::MouseUp {
+        if tracking_event.selector == tracking::Selector::Up {
             model.stop_tracking()
         }
     }
@@ -334,19 +2712,35 @@ impl DragShape {
 
 // Utlities
 
-// Given a pair of coordinates, find the mimimum coordinate and the non-negative span
-// to the other coordinate.
+// The angle of a point around a center measured from the x axis.
 
-fn to_min_span(x1: f64, x2: f64) -> (f64, f64) {
-    if x1 < x2 {
-        (x1, x2 - x1)
-    } else {
-        (x2, x1 - x2)
+fn angle_around(center: &XYPoint, point: &XYPoint) -> f64 {
+    let offset = point.subtract(center);
+    offset.y.atan2(offset.x)
+}
+
+// The offset between two document positions as seen in the space of a
+// shape with the given transform. If the transform cannot be inverted,
+// we fall back to the offset in document space.
+
+fn local_delta(transform: &Transform, from: &XYPoint, to: &XYPoint) -> XYPoint {
+    match transform.inverse() {
+        Some(inverse) => inverse.apply(to).subtract(&inverse.apply(from)),
+        None => to.subtract(from),
     }
 }
 
-// Extract page coordinates from a mouse event.
+// Extract canvas coordinates -- i.e., coordinates in the space of the SVG
+// element -- from a mouse event. These are the coordinates that the
+// viewport maps to and from.
+
+pub fn get_canvas_coordinates(mouse_event: &MouseEvent) -> XYPoint {
+    let position = tracking::local_position(mouse_event, CANVAS_ID);
+    XYPoint::new(position.x, position.y)
+}
+
+// The tracking framework has already found the canvas coordinates.
 
-pub fn get_page_coordinates(mouse_event: &MouseEvent) -> XYPoint {
-    XYPoint::new(mouse_event.page_x().into(), mouse_event.page_y().into())
+fn tracking_canvas_coordinates(tracking_event: &tracking::Event) -> XYPoint {
+    XYPoint::new(tracking_event.position.x, tracking_event.position.y)
 }
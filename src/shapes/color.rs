@@ -0,0 +1,664 @@
+// Colors are sRGB with an alpha channel, eight bits per channel. We can
+// read the CSS color syntaxes that show up in practice -- hex, rgb(),
+// hsl(), and the named colors -- and write colors back out as CSS. The
+// HSL and HSV conversions are for building color pickers.
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+// Hue is in degrees in [0, 360). The other components are in [0, 1].
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Hsl {
+    pub hue: f64,
+    pub saturation: f64,
+    pub lightness: f64,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Hsv {
+    pub hue: f64,
+    pub saturation: f64,
+    pub value: f64,
+}
+
+impl Color {
+    // The colors we started with, which the app still cycles through.
+    pub const RED: Color = Color::rgb(0xff, 0x00, 0x00);
+    pub const ORANGE: Color = Color::rgb(0xff, 0xa5, 0x00);
+    pub const YELLOW: Color = Color::rgb(0xff, 0xff, 0x00);
+    pub const GREEN: Color = Color::rgb(0x00, 0x80, 0x00);
+    pub const BLUE: Color = Color::rgb(0x00, 0x00, 0xff);
+    pub const INDIGO: Color = Color::rgb(0x4b, 0x00, 0x82);
+    pub const VIOLET: Color = Color::rgb(0xee, 0x82, 0xee);
+    pub const WHITE: Color = Color::rgb(0xff, 0xff, 0xff);
+    pub const BLACK: Color = Color::rgb(0x00, 0x00, 0x00);
+    pub const TRANSPARENT: Color = Color::rgba(0x00, 0x00, 0x00, 0x00);
+
+    pub const PALETTE: [Color; 9] = [
+        Color::RED,
+        Color::ORANGE,
+        Color::YELLOW,
+        Color::GREEN,
+        Color::BLUE,
+        Color::INDIGO,
+        Color::VIOLET,
+        Color::BLACK,
+        Color::WHITE,
+    ];
+
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Color {
+        Color::rgba(red, green, blue, 0xff)
+    }
+
+    pub const fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Color {
+        Color {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    // Cycle through the palette. Colors that are not in the palette
+    // advance to the start of the palette.
+    pub fn advance(&mut self) {
+        *self = match Color::PALETTE.iter().position(|color| color == self) {
+            Some(index) => Color::PALETTE[(index + 1) % Color::PALETTE.len()],
+            None => Color::PALETTE[0],
+        };
+    }
+
+    // Opacity in [0, 1].
+    pub fn opacity(&self) -> f64 {
+        f64::from(self.alpha) / 255.0
+    }
+
+    pub fn with_opacity(&self, opacity: f64) -> Color {
+        Color {
+            alpha: unit_to_channel(opacity),
+            ..*self
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.alpha == 0xff
+    }
+
+    // The color as #rrggbb ignoring alpha. SVG 1.1 has no alpha in colors,
+    // so exports pair this with an opacity attribute.
+    pub fn to_hex_rgb(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+
+    // The color as #rrggbb if it is opaque and #rrggbbaa otherwise.
+    pub fn to_hex(&self) -> String {
+        if self.is_opaque() {
+            self.to_hex_rgb()
+        } else {
+            format!("{}{:02x}", self.to_hex_rgb(), self.alpha)
+        }
+    }
+
+    // The color for CSS (and SVG 2) attributes: #rrggbb if it is opaque and
+    // rgba() otherwise.
+    pub fn to_css(&self) -> String {
+        if self.is_opaque() {
+            self.to_hex_rgb()
+        } else {
+            format!(
+                "rgba({}, {}, {}, {})",
+                self.red,
+                self.green,
+                self.blue,
+                format_unit(self.opacity())
+            )
+        }
+    }
+
+    // Parse a CSS color: #rgb, #rgba, #rrggbb, #rrggbbaa, rgb(), rgba(),
+    // hsl(), hsla(), or a named color. Names and function names are case
+    // insensitive. Returns None if the text is not a color we understand.
+    pub fn from_css(text: &str) -> Option<Color> {
+        let text = text.trim().to_ascii_lowercase();
+        if let Some(hex) = text.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+        if let Some((name, rest)) = text.split_once('(') {
+            let arguments = rest.strip_suffix(')')?;
+            return match name.trim_end() {
+                "rgb" | "rgba" => parse_rgb_function(arguments),
+                "hsl" | "hsla" => parse_hsl_function(arguments),
+                _ => None,
+            };
+        }
+        if text == "transparent" {
+            return Some(Color::TRANSPARENT);
+        }
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| *name == text)
+            .map(|(_, rgb)| Color::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8))
+    }
+
+    pub fn from_hsl(hsl: &Hsl, opacity: f64) -> Color {
+        let saturation = hsl.saturation.clamp(0.0, 1.0);
+        let lightness = hsl.lightness.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        from_hue_chroma(hsl.hue, chroma, lightness - chroma / 2.0, opacity)
+    }
+
+    pub fn from_hsv(hsv: &Hsv, opacity: f64) -> Color {
+        let saturation = hsv.saturation.clamp(0.0, 1.0);
+        let value = hsv.value.clamp(0.0, 1.0);
+        let chroma = value * saturation;
+        from_hue_chroma(hsv.hue, chroma, value - chroma, opacity)
+    }
+
+    pub fn to_hsl(&self) -> Hsl {
+        let (hue, max, min) = self.hue_max_min();
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        Hsl {
+            hue,
+            saturation,
+            lightness,
+        }
+    }
+
+    pub fn to_hsv(&self) -> Hsv {
+        let (hue, max, min) = self.hue_max_min();
+        let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+        Hsv {
+            hue,
+            saturation,
+            value: max,
+        }
+    }
+
+    // The hue in degrees plus the largest and smallest of the RGB
+    // components in [0, 1]. Grays have a hue of zero.
+    fn hue_max_min(&self) -> (f64, f64, f64) {
+        let red = f64::from(self.red) / 255.0;
+        let green = f64::from(self.green) / 255.0;
+        let blue = f64::from(self.blue) / 255.0;
+        let max = red.max(green).max(blue);
+        let min = red.min(green).min(blue);
+        let chroma = max - min;
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == red {
+            60.0 * ((green - blue) / chroma).rem_euclid(6.0)
+        } else if max == green {
+            60.0 * ((blue - red) / chroma + 2.0)
+        } else {
+            60.0 * ((red - green) / chroma + 4.0)
+        };
+        (hue, max, min)
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.to_css())
+    }
+}
+
+// Serialization uses hex so that it round trips exactly. Reading accepts
+// anything from_css does, which includes the color names written by
+// earlier versions of the JSON format.
+
+#[cfg(feature = "serde")]
+impl From<Color> for String {
+    fn from(color: Color) -> String {
+        color.to_hex()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Color, String> {
+        Color::from_css(&text).ok_or_else(|| format!("invalid color \"{text}\""))
+    }
+}
+
+// Build a color from a hue, a chroma, and the amount to add to each
+// component to match the lightness or value. This is the common tail of
+// the HSL and HSV conversions.
+
+fn from_hue_chroma(hue: f64, chroma: f64, offset: f64, opacity: f64) -> Color {
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+    let (red, green, blue) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    Color::rgba(
+        unit_to_channel(red + offset),
+        unit_to_channel(green + offset),
+        unit_to_channel(blue + offset),
+        unit_to_channel(opacity),
+    )
+}
+
+fn unit_to_channel(unit: f64) -> u8 {
+    (unit.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Format a number in [0, 1] with at most three decimal places, which is
+// more precision than an eight bit channel has.
+
+fn format_unit(unit: f64) -> String {
+    let formatted = format!("{unit:.3}");
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+// Hex colors have one or two digits per channel with optional alpha.
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits: Vec<u8> = hex
+        .chars()
+        .map(|c| c.to_digit(16).unwrap_or_default() as u8)
+        .collect();
+    let channels: Vec<u8> = match digits.len() {
+        3 | 4 => digits.iter().map(|digit| digit * 17).collect(),
+        6 | 8 => digits
+            .chunks(2)
+            .map(|pair| pair[0] * 16 + pair[1])
+            .collect(),
+        _ => return None,
+    };
+    Some(Color::rgba(
+        channels[0],
+        channels[1],
+        channels[2],
+        channels.get(3).copied().unwrap_or(0xff),
+    ))
+}
+
+// Function arguments may be separated by commas or by whitespace with the
+// alpha after a slash. We do not check that the styles are not mixed.
+
+fn function_arguments(arguments: &str) -> Vec<&str> {
+    arguments
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|argument| !argument.is_empty())
+        .collect()
+}
+
+// A number, or a percentage of the given full scale.
+
+fn parse_number(text: &str, full_scale: f64) -> Option<f64> {
+    let number = match text.strip_suffix('%') {
+        Some(percentage) => percentage.parse::<f64>().ok()? / 100.0 * full_scale,
+        None => text.parse::<f64>().ok()?,
+    };
+    number.is_finite().then_some(number)
+}
+
+fn parse_alpha(arguments: &[&str]) -> Option<f64> {
+    match arguments {
+        [] => Some(1.0),
+        [alpha] => parse_number(alpha, 1.0),
+        _ => None,
+    }
+}
+
+fn parse_rgb_function(arguments: &str) -> Option<Color> {
+    let arguments = function_arguments(arguments);
+    if arguments.len() < 3 {
+        return None;
+    }
+    let channel =
+        |text: &str| parse_number(text, 255.0).map(|number| number.clamp(0.0, 255.0).round() as u8);
+    Some(Color::rgba(
+        channel(arguments[0])?,
+        channel(arguments[1])?,
+        channel(arguments[2])?,
+        unit_to_channel(parse_alpha(&arguments[3..])?),
+    ))
+}
+
+fn parse_hsl_function(arguments: &str) -> Option<Color> {
+    let arguments = function_arguments(arguments);
+    if arguments.len() < 3 {
+        return None;
+    }
+    let hue = parse_number(arguments[0].trim_end_matches("deg"), 360.0)?;
+    let hsl = Hsl {
+        hue,
+        saturation: parse_number(arguments[1], 100.0)? / 100.0,
+        lightness: parse_number(arguments[2], 100.0)? / 100.0,
+    };
+    Some(Color::from_hsl(&hsl, parse_alpha(&arguments[3..])?))
+}
+
+// The CSS named colors as 0xrrggbb.
+
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_css_colors() {
+        let cases = [
+            ("#f00", Color::rgb(0xff, 0x00, 0x00)),
+            ("#F00", Color::rgb(0xff, 0x00, 0x00)),
+            ("#f008", Color::rgba(0xff, 0x00, 0x00, 0x88)),
+            ("#ff8000", Color::rgb(0xff, 0x80, 0x00)),
+            ("#ff800080", Color::rgba(0xff, 0x80, 0x00, 0x80)),
+            ("  #0000ff  ", Color::BLUE),
+            ("rgb(255, 128, 0)", Color::rgb(0xff, 0x80, 0x00)),
+            ("RGB( 0 , 0 , 255 )", Color::BLUE),
+            ("rgb(100% 50% 0%)", Color::rgb(0xff, 0x80, 0x00)),
+            ("rgb(300, -5, 0)", Color::rgb(0xff, 0x00, 0x00)),
+            ("rgba(255, 0, 0, 0.5)", Color::rgba(0xff, 0x00, 0x00, 0x80)),
+            ("rgb(255 0 0 / 25%)", Color::rgba(0xff, 0x00, 0x00, 0x40)),
+            ("hsl(0, 100%, 50%)", Color::RED),
+            ("hsl(120, 100%, 25%)", Color::GREEN),
+            ("hsl(240deg 100% 50%)", Color::BLUE),
+            ("hsl(-120, 100%, 50%)", Color::BLUE),
+            ("hsl(0, 0%, 100%)", Color::WHITE),
+            (
+                "hsla(240, 100%, 50%, 0.25)",
+                Color::rgba(0x00, 0x00, 0xff, 0x40),
+            ),
+            ("red", Color::RED),
+            ("Indigo", Color::INDIGO),
+            ("DodgerBlue", Color::rgb(0x1e, 0x90, 0xff)),
+            ("rebeccapurple", Color::rgb(0x66, 0x33, 0x99)),
+            ("transparent", Color::TRANSPARENT),
+        ];
+        for (text, color) in cases {
+            assert_eq!(Color::from_css(text), Some(color), "parsing {text:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_css_colors() {
+        let cases = [
+            "",
+            "#",
+            "#ff",
+            "#12345",
+            "#1234567",
+            "#ggg",
+            "rgb(1, 2)",
+            "rgb(1, 2, 3, 4, 5)",
+            "rgb(a, b, c)",
+            "rgb(1, 2, 3",
+            "rgb(nan, 0, 0)",
+            "hsl(0, 100%)",
+            "cmyk(0, 0, 0, 0)",
+            "notacolor",
+        ];
+        for text in cases {
+            assert_eq!(Color::from_css(text), None, "parsing {text:?}");
+        }
+    }
+
+    #[test]
+    fn writes_css_and_hex() {
+        let cases = [
+            (Color::RED, "#ff0000", "#ff0000"),
+            (
+                Color::rgba(0xff, 0x00, 0x00, 0x80),
+                "#ff000080",
+                "rgba(255, 0, 0, 0.502)",
+            ),
+            (Color::TRANSPARENT, "#00000000", "rgba(0, 0, 0, 0)"),
+        ];
+        for (color, hex, css) in cases {
+            assert_eq!(color.to_hex(), hex);
+            assert_eq!(color.to_css(), css);
+            assert_eq!(Color::from_css(hex), Some(color));
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, what: &str) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{what}: expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn converts_to_hsl_and_hsv() {
+        let gray = 128.0 / 255.0;
+        // The color, then hue, HSL saturation, lightness, HSV saturation,
+        // and value.
+        let cases = [
+            (Color::RED, 0.0, 1.0, 0.5, 1.0, 1.0),
+            (Color::rgb(0x00, 0xff, 0x00), 120.0, 1.0, 0.5, 1.0, 1.0),
+            (Color::BLUE, 240.0, 1.0, 0.5, 1.0, 1.0),
+            (Color::rgb(0xff, 0x00, 0xff), 300.0, 1.0, 0.5, 1.0, 1.0),
+            (Color::WHITE, 0.0, 0.0, 1.0, 0.0, 1.0),
+            (Color::BLACK, 0.0, 0.0, 0.0, 0.0, 0.0),
+            (Color::rgb(0x80, 0x80, 0x80), 0.0, 0.0, gray, 0.0, gray),
+            (
+                Color::rgb(0x80, 0x00, 0x00),
+                0.0,
+                1.0,
+                gray / 2.0,
+                1.0,
+                gray,
+            ),
+        ];
+        for (color, hue, hsl_saturation, lightness, hsv_saturation, value) in cases {
+            let hsl = color.to_hsl();
+            assert_close(hsl.hue, hue, "hsl hue");
+            assert_close(hsl.saturation, hsl_saturation, "hsl saturation");
+            assert_close(hsl.lightness, lightness, "lightness");
+            let hsv = color.to_hsv();
+            assert_close(hsv.hue, hue, "hsv hue");
+            assert_close(hsv.saturation, hsv_saturation, "hsv saturation");
+            assert_close(hsv.value, value, "value");
+        }
+    }
+
+    #[test]
+    fn hsl_and_hsv_round_trip() {
+        for red in (0..=255).step_by(15) {
+            for green in (0..=255).step_by(51) {
+                for blue in (0..=255).step_by(85) {
+                    let color = Color::rgba(red, green, blue, 0x80);
+                    assert_eq!(Color::from_hsl(&color.to_hsl(), color.opacity()), color);
+                    assert_eq!(Color::from_hsv(&color.to_hsv(), color.opacity()), color);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clamps_out_of_range_components() {
+        let hsl = Hsl {
+            hue: 720.0,
+            saturation: 2.0,
+            lightness: -1.0,
+        };
+        assert_eq!(Color::from_hsl(&hsl, 1.5), Color::BLACK);
+        let hsv = Hsv {
+            hue: 360.0,
+            saturation: 1.0,
+            value: 1.0,
+        };
+        assert_eq!(Color::from_hsv(&hsv, 1.0), Color::RED);
+    }
+}
@@ -1,69 +1,250 @@
-// We want a better color model eventually, but an enumeration of fixed colors
-// will do for now.
+use crate::shapes::color::Color;
 
-#[derive(PartialEq, Clone)]
-pub enum Color {
-    Red,
-    Orange,
-    Yellow,
-    Green,
-    Blue,
-    Indigo,
-    Violet,
-    White,
-    Black,
-}
-
-impl Color {
-    // Cycle through the colors finding the next in the enumeration.
-    // Since the order in the enumeration is arbitrary -- with respect
-    // to black and white at least -- the cycling behavior is
-    // arbitrary.
-    pub fn advance(&mut self) {
-        *self = match self {
-            Color::Red => Color::Orange,
-            Color::Orange => Color::Yellow,
-            Color::Yellow => Color::Green,
-            Color::Green => Color::Blue,
-            Color::Blue => Color::Indigo,
-            Color::Indigo => Color::Violet,
-            Color::Violet => Color::Black,
-            Color::Black => Color::White,
-            Color::White => Color::Red,
-        };
-    }
-}
-
-// A shape has geometric information and style information.
+// A shape has geometric information and style information. The geometry
+// is in the shape's own coordinate space and the transform maps that
+// space into the document.
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Shape {
     pub geometry: Geometry,
     pub style: Style,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default = "Transform::identity",
+            skip_serializing_if = "Transform::is_identity"
+        )
+    )]
+    pub transform: Transform,
 }
 
 impl Shape {
+    // A shape with no transform.
     pub fn new(geometry: Geometry, style: Style) -> Shape {
-        Shape { geometry, style }
+        Shape {
+            geometry,
+            style,
+            transform: Transform::identity(),
+        }
+    }
+
+    pub fn with_transform(&self, transform: Transform) -> Shape {
+        Shape {
+            transform,
+            ..self.clone()
+        }
+    }
+
+    // The bounds in document space. For transformed shapes, these are the
+    // bounds of the transformed geometry bounds, which may be larger than
+    // the bounds of the transformed geometry -- e.g., for a rotated circle.
+    pub fn bounds(&self) -> Bounds {
+        let bounds = self.geometry.bounds();
+        if self.transform.is_identity() {
+            bounds
+        } else {
+            self.transform.apply_to_bounds(&bounds)
+        }
+    }
+
+    // The bounds of everything the shape paints in document space. These
+    // include the parts of the stroke outside the geometry. Miter joins can
+    // reach out to the miter limit -- four in SVG by default -- times half
+    // the stroke width, so we allow for that whatever the join.
+    pub fn painted_bounds(&self) -> Bounds {
+        let bounds = self.geometry.bounds();
+        let margin = self
+            .rendered_style()
+            .stroke
+            .map_or(0.0, |stroke| 2.0 * stroke.width);
+        let extent = XYPoint::new(margin, margin);
+        self.transform.apply_to_bounds(&Bounds::new(
+            bounds.min.subtract(&extent),
+            bounds.max.add(&extent),
+        ))
+    }
+
+    // The center of the geometry bounds in document space. Rotation is
+    // around this point.
+    pub fn center(&self) -> XYPoint {
+        self.transform.apply(&self.geometry.bounds().center())
+    }
+
+    // The style the shape is actually drawn with. Open shapes -- lines,
+    // polylines, and open paths -- are never filled. If they have no
+    // stroke, we stroke them in their fill color so that they remain
+    // visible.
+    pub fn rendered_style(&self) -> Style {
+        if !self.geometry.is_open() {
+            return self.style.clone();
+        }
+        let stroke = self.style.stroke.clone().or_else(|| {
+            self.style
+                .fill
+                .map(|color| Stroke::new(color, OPEN_SHAPE_STROKE_WIDTH))
+        });
+        Style { fill: None, stroke }
+    }
+
+    // Is the point on a painted part of the shape as rendered -- i.e.,
+    // inside the fill or on the stroke? This matches the browser's default
+    // hit-testing. Strokes are measured in the shape's own space, so they
+    // scale with the transform just as they do when drawn.
+    pub fn contains_point(&self, point: &XYPoint) -> bool {
+        let Some(inverse) = self.transform.inverse() else {
+            return false;
+        };
+        let local_point = inverse.apply(point);
+        let style = self.rendered_style();
+        (style.fill.is_some() && self.geometry.contains_point(&local_point))
+            || style.stroke.is_some_and(|stroke| {
+                self.geometry.distance_to_outline(&local_point) <= stroke.width / 2.0
+            })
+    }
+
+    // Move the shape in document space. Untransformed shapes move their
+    // geometry; transformed shapes move their transform instead so that
+    // the geometry stays put in its own space.
+    pub fn offset_by(&self, delta: &XYPoint) -> Shape {
+        if self.transform.is_identity() {
+            Shape {
+                geometry: self.geometry.offset_by(delta),
+                ..self.clone()
+            }
+        } else {
+            self.with_transform(
+                self.transform
+                    .then(&Transform::translation(delta.x, delta.y)),
+            )
+        }
     }
 }
 
-// Styles contain a fill color.
+// The stroke width for open shapes that only have a fill color.
+
+const OPEN_SHAPE_STROKE_WIDTH: f64 = 2.0;
+
+// Styles contain an optional fill color and an optional stroke. A shape
+// with neither is invisible, but we do not prevent that.
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Style {
-    pub fill: Color,
+    pub fill: Option<Color>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub stroke: Option<Stroke>,
 }
 
 impl Style {
+    // A filled style with no stroke.
     pub fn new(fill: Color) -> Style {
-        Style { fill }
+        Style {
+            fill: Some(fill),
+            stroke: None,
+        }
+    }
+
+    // An outline-only style with no fill.
+    pub fn new_outline(stroke: Stroke) -> Style {
+        Style {
+            fill: None,
+            stroke: Some(stroke),
+        }
+    }
+
+    pub fn with_stroke(&self, stroke: Option<Stroke>) -> Style {
+        Style {
+            fill: self.fill,
+            stroke,
+        }
+    }
+}
+
+// Strokes follow the SVG stroke properties. An empty dash array means a
+// solid stroke.
+
+#[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stroke {
+    pub color: Color,
+    pub width: f64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dash_array: Vec<f64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub line_join: LineJoin,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub line_cap: LineCap,
+}
+
+impl Stroke {
+    // A solid stroke with the SVG default joins and caps.
+    pub fn new(color: Color, width: f64) -> Stroke {
+        Stroke {
+            color,
+            width,
+            dash_array: Vec::new(),
+            line_join: LineJoin::Miter,
+            line_cap: LineCap::Butt,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum LineJoin {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl LineJoin {
+    // The value of the SVG stroke-linejoin property.
+    pub fn svg_name(&self) -> &'static str {
+        match self {
+            LineJoin::Miter => "miter",
+            LineJoin::Round => "round",
+            LineJoin::Bevel => "bevel",
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum LineCap {
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+impl LineCap {
+    // The value of the SVG stroke-linecap property.
+    pub fn svg_name(&self) -> &'static str {
+        match self {
+            LineCap::Butt => "butt",
+            LineCap::Round => "round",
+            LineCap::Square => "square",
+        }
     }
 }
 
 // We use xy pairs for much of our geometry.
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct XYPoint {
     pub x: f64,
     pub y: f64,
@@ -81,15 +262,278 @@ impl XYPoint {
     pub fn subtract(&self, other: &XYPoint) -> Self {
         Self::new(self.x - other.x, self.y - other.y)
     }
+
+    // Map the point from its position relative to one set of bounds to the
+    // same relative position in another. Along an axis where the old
+    // bounds are empty, the point just moves with the min edge.
+    pub fn scaled_between(&self, from: &Bounds, to: &Bounds) -> Self {
+        let scale = |value: f64, from_min: f64, from_span: f64, to_min: f64, to_span: f64| {
+            if from_span == 0.0 {
+                to_min + (value - from_min)
+            } else {
+                to_min + (value - from_min) * to_span / from_span
+            }
+        };
+        Self::new(
+            scale(self.x, from.min.x, from.width(), to.min.x, to.width()),
+            scale(self.y, from.min.y, from.height(), to.min.y, to.height()),
+        )
+    }
+}
+
+// Bounds are axis-aligned rectangles given by their minimum and maximum
+// corners.
+
+#[derive(PartialEq, Clone)]
+pub struct Bounds {
+    pub min: XYPoint,
+    pub max: XYPoint,
+}
+
+impl Bounds {
+    pub fn new(min: XYPoint, max: XYPoint) -> Self {
+        Self { min, max }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max.x - self.min.x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max.y - self.min.y
+    }
+
+    // Bounds from two opposite corners in any order.
+    pub fn from_corners(a: &XYPoint, b: &XYPoint) -> Self {
+        Self::new(
+            XYPoint::new(a.x.min(b.x), a.y.min(b.y)),
+            XYPoint::new(a.x.max(b.x), a.y.max(b.y)),
+        )
+    }
+
+    // Do the bounds overlap? Touching edges count as overlapping.
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+
+    // Do these bounds entirely contain the other bounds?
+    pub fn contains_bounds(&self, other: &Bounds) -> bool {
+        self.min.x <= other.min.x
+            && other.max.x <= self.max.x
+            && self.min.y <= other.min.y
+            && other.max.y <= self.max.y
+    }
+
+    pub fn center(&self) -> XYPoint {
+        XYPoint::new(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+        )
+    }
+
+    // The smallest bounds containing both bounds.
+    pub fn union(&self, other: &Bounds) -> Self {
+        Self::new(
+            XYPoint::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            XYPoint::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        )
+    }
+}
+
+// Transforms are 2D affine transforms as in SVG. They map (x, y) to
+// (a * x + c * y + e, b * x + d * y + f). Angles are in radians and a
+// positive angle rotates clockwise since y points down.
+
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
 }
 
-// Geometry can take multiple forms. For now, it just contains rectangles
-// and circles.
+impl Transform {
+    pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn translation(dx: f64, dy: f64) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, dx, dy)
+    }
+
+    // Negative scales flip.
+    pub fn scaling(sx: f64, sy: f64) -> Self {
+        Self::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    pub fn rotation(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    pub fn skew_x(angle: f64) -> Self {
+        Self::new(1.0, 0.0, angle.tan(), 1.0, 0.0, 0.0)
+    }
+
+    pub fn skew_y(angle: f64) -> Self {
+        Self::new(1.0, angle.tan(), 0.0, 1.0, 0.0, 0.0)
+    }
+
+    // Apply a transform around a point rather than around the origin.
+    pub fn about(&self, center: &XYPoint) -> Self {
+        Self::translation(-center.x, -center.y)
+            .then(self)
+            .then(&Self::translation(center.x, center.y))
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+
+    // The transform that applies this transform and then the other.
+    pub fn then(&self, other: &Transform) -> Self {
+        Self::new(
+            other.a * self.a + other.c * self.b,
+            other.b * self.a + other.d * self.b,
+            other.a * self.c + other.c * self.d,
+            other.b * self.c + other.d * self.d,
+            other.a * self.e + other.c * self.f + other.e,
+            other.b * self.e + other.d * self.f + other.f,
+        )
+    }
+
+    // The inverse if the transform is invertible -- i.e., if it does not
+    // collapse the plane onto a line or a point.
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.a * self.d - self.b * self.c;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        Some(Self::new(
+            self.d / determinant,
+            -self.b / determinant,
+            -self.c / determinant,
+            self.a / determinant,
+            (self.c * self.f - self.d * self.e) / determinant,
+            (self.b * self.e - self.a * self.f) / determinant,
+        ))
+    }
+
+    pub fn apply(&self, point: &XYPoint) -> XYPoint {
+        XYPoint::new(
+            self.a * point.x + self.c * point.y + self.e,
+            self.b * point.x + self.d * point.y + self.f,
+        )
+    }
+
+    // The bounds of the transformed corners of the bounds.
+    pub fn apply_to_bounds(&self, bounds: &Bounds) -> Bounds {
+        bounds_of_points(
+            [
+                XYPoint::new(bounds.min.x, bounds.min.y),
+                XYPoint::new(bounds.max.x, bounds.min.y),
+                XYPoint::new(bounds.max.x, bounds.max.y),
+                XYPoint::new(bounds.min.x, bounds.max.y),
+            ]
+            .iter()
+            .map(|corner| self.apply(corner)),
+        )
+    }
+
+    // The rotation part of the transform -- i.e., the angle that the x
+    // axis is rotated through.
+    pub fn rotation_angle(&self) -> f64 {
+        self.b.atan2(self.a)
+    }
+}
+
+// Paths are sequences of segments as in SVG path data. Each segment
+// starts where the previous one ended. Close returns to the start of the
+// current subpath, which begins with the most recent MoveTo.
+
+#[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+pub enum PathSegment {
+    MoveTo {
+        to: XYPoint,
+    },
+    LineTo {
+        to: XYPoint,
+    },
+    CubicTo {
+        control1: XYPoint,
+        control2: XYPoint,
+        to: XYPoint,
+    },
+    QuadTo {
+        control: XYPoint,
+        to: XYPoint,
+    },
+    Close,
+}
+
+impl PathSegment {
+    // The end point of the segment if it has one. This is the anchor that
+    // the segment contributes to the path.
+    pub fn end_point(&self) -> Option<&XYPoint> {
+        match self {
+            PathSegment::MoveTo { to }
+            | PathSegment::LineTo { to }
+            | PathSegment::CubicTo { to, .. }
+            | PathSegment::QuadTo { to, .. } => Some(to),
+            PathSegment::Close => None,
+        }
+    }
+
+    // Apply a function to every point in the segment.
+    pub fn map_points(&self, f: impl Fn(&XYPoint) -> XYPoint) -> PathSegment {
+        match self {
+            PathSegment::MoveTo { to } => PathSegment::MoveTo { to: f(to) },
+            PathSegment::LineTo { to } => PathSegment::LineTo { to: f(to) },
+            PathSegment::CubicTo {
+                control1,
+                control2,
+                to,
+            } => PathSegment::CubicTo {
+                control1: f(control1),
+                control2: f(control2),
+                to: f(to),
+            },
+            PathSegment::QuadTo { control, to } => PathSegment::QuadTo {
+                control: f(control),
+                to: f(to),
+            },
+            PathSegment::Close => PathSegment::Close,
+        }
+    }
+}
+
+// Geometry can take multiple forms: rectangles, circles, ellipses,
+// lines, open (polyline) or closed (polygon) sequences of points, and
+// paths made of line and Bezier segments.
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum Geometry {
     Rectangle { top_left: XYPoint, size: XYPoint },
     Circle { center: XYPoint, radius: f64 },
+    Ellipse { center: XYPoint, radii: XYPoint },
+    Line { start: XYPoint, end: XYPoint },
+    Polyline { points: Vec<XYPoint> },
+    Polygon { points: Vec<XYPoint> },
+    Path { segments: Vec<PathSegment> },
 }
 
 impl Geometry {
@@ -107,7 +551,22 @@ impl Geometry {
         }
     }
 
+    pub fn ellipse(cx: f64, cy: f64, rx: f64, ry: f64) -> Self {
+        Geometry::Ellipse {
+            center: XYPoint::new(cx, cy),
+            radii: XYPoint::new(rx, ry),
+        }
+    }
+
+    pub fn line(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
+        Geometry::Line {
+            start: XYPoint::new(x1, y1),
+            end: XYPoint::new(x2, y2),
+        }
+    }
+
     pub fn offset_by(&self, offset: &XYPoint) -> Geometry {
+        let offset_points = |points: &Vec<XYPoint>| points.iter().map(|p| p.add(offset)).collect();
         match self {
             Geometry::Rectangle { top_left, size } => Geometry::Rectangle {
                 top_left: top_left.add(offset),
@@ -117,6 +576,871 @@ impl Geometry {
                 center: center.add(offset),
                 radius: *radius,
             },
+            Geometry::Ellipse { center, radii } => Geometry::Ellipse {
+                center: center.add(offset),
+                radii: radii.clone(),
+            },
+            Geometry::Line { start, end } => Geometry::Line {
+                start: start.add(offset),
+                end: end.add(offset),
+            },
+            Geometry::Polyline { points } => Geometry::Polyline {
+                points: offset_points(points),
+            },
+            Geometry::Polygon { points } => Geometry::Polygon {
+                points: offset_points(points),
+            },
+            Geometry::Path { segments } => Geometry::Path {
+                segments: segments
+                    .iter()
+                    .map(|segment| segment.map_points(|p| p.add(offset)))
+                    .collect(),
+            },
+        }
+    }
+
+    // Open geometries -- lines, polylines, and paths that do not end with
+    // a Close -- have nothing to fill.
+    pub fn is_open(&self) -> bool {
+        match self {
+            Geometry::Line { .. } | Geometry::Polyline { .. } => true,
+            Geometry::Path { segments } => segments.last() != Some(&PathSegment::Close),
+            _ => false,
+        }
+    }
+
+    // The anchor points that can be edited individually. Only point-based
+    // geometries have them. A path that comes back to the start of a
+    // subpath before closing it has a single anchor there.
+    pub fn anchors(&self) -> Vec<XYPoint> {
+        match self {
+            Geometry::Line { start, end } => vec![start.clone(), end.clone()],
+            Geometry::Polyline { points } | Geometry::Polygon { points } => points.clone(),
+            Geometry::Path { segments } => path_anchor_segments(segments)
+                .iter()
+                .filter_map(|indices| segments[indices[0]].end_point().cloned())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Move a single anchor as numbered by anchors(). For paths, the
+    // control points on either side of the anchor move along with it so
+    // that the curve keeps its shape near the anchor.
+    pub fn with_anchor_moved(&self, anchor_index: usize, offset: &XYPoint) -> Geometry {
+        let offset_at = |index: usize, point: &XYPoint| {
+            if index == anchor_index {
+                point.add(offset)
+            } else {
+                point.clone()
+            }
+        };
+        match self {
+            Geometry::Line { start, end } => Geometry::Line {
+                start: offset_at(0, start),
+                end: offset_at(1, end),
+            },
+            Geometry::Polyline { points } => Geometry::Polyline {
+                points: points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| offset_at(i, p))
+                    .collect(),
+            },
+            Geometry::Polygon { points } => Geometry::Polygon {
+                points: points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| offset_at(i, p))
+                    .collect(),
+            },
+            Geometry::Path { segments } => Geometry::Path {
+                segments: path_with_anchor_moved(segments, anchor_index, offset),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    // Fit the geometry to new bounds. Rectangles and ellipses fill the
+    // bounds exactly. Circles stay circular: they are centered in the
+    // bounds with a diameter equal to the smaller of the width and the
+    // height. Points in lines, polylines, and polygons are scaled from the
+    // old bounds to the new bounds.
+    pub fn resized_to_bounds(&self, bounds: &Bounds) -> Geometry {
+        let old_bounds = self.bounds();
+        let scale_points = |points: &Vec<XYPoint>| {
+            points
+                .iter()
+                .map(|p| p.scaled_between(&old_bounds, bounds))
+                .collect()
+        };
+        match self {
+            Geometry::Rectangle { .. } => Geometry::Rectangle {
+                top_left: bounds.min.clone(),
+                size: XYPoint::new(bounds.width(), bounds.height()),
+            },
+            Geometry::Circle { .. } => Geometry::Circle {
+                center: bounds.center(),
+                radius: bounds.width().min(bounds.height()) / 2.0,
+            },
+            Geometry::Ellipse { .. } => Geometry::Ellipse {
+                center: bounds.center(),
+                radii: XYPoint::new(bounds.width() / 2.0, bounds.height() / 2.0),
+            },
+            Geometry::Line { start, end } => Geometry::Line {
+                start: start.scaled_between(&old_bounds, bounds),
+                end: end.scaled_between(&old_bounds, bounds),
+            },
+            Geometry::Polyline { points } => Geometry::Polyline {
+                points: scale_points(points),
+            },
+            Geometry::Polygon { points } => Geometry::Polygon {
+                points: scale_points(points),
+            },
+            Geometry::Path { segments } => Geometry::Path {
+                segments: segments
+                    .iter()
+                    .map(|segment| segment.map_points(|p| p.scaled_between(&old_bounds, bounds)))
+                    .collect(),
+            },
+        }
+    }
+
+    // The axis-aligned bounds of the geometry. Empty point lists have
+    // empty bounds at the origin.
+    pub fn bounds(&self) -> Bounds {
+        match self {
+            Geometry::Rectangle { top_left, size } => {
+                Bounds::new(top_left.clone(), top_left.add(size))
+            }
+            Geometry::Circle { center, radius } => {
+                let extent = XYPoint::new(*radius, *radius);
+                Bounds::new(center.subtract(&extent), center.add(&extent))
+            }
+            Geometry::Ellipse { center, radii } => {
+                Bounds::new(center.subtract(radii), center.add(radii))
+            }
+            Geometry::Line { start, end } => Bounds::from_corners(start, end),
+            Geometry::Polyline { points } | Geometry::Polygon { points } => {
+                bounds_of_points(points.iter().cloned())
+            }
+            Geometry::Path { segments } => bounds_of_points(path_extreme_points(segments)),
+        }
+    }
+
+    // Is the point inside the area that the geometry encloses? Open
+    // geometries enclose what they would if closed by a straight line back
+    // to their start, as when SVG fills them, so lines enclose nothing.
+    // Overlapping areas use the nonzero rule, which is SVG's default. Points
+    // on the boundary of the basic shapes count as inside.
+    pub fn contains_point(&self, point: &XYPoint) -> bool {
+        match self {
+            Geometry::Rectangle { .. } => self
+                .bounds()
+                .contains_bounds(&Bounds::new(point.clone(), point.clone())),
+            Geometry::Circle { center, radius } => {
+                let offset = point.subtract(center);
+                offset.x.hypot(offset.y) <= *radius
+            }
+            Geometry::Ellipse { center, radii } => {
+                if radii.x == 0.0 || radii.y == 0.0 {
+                    return false;
+                }
+                let offset = point.subtract(center);
+                (offset.x / radii.x).powi(2) + (offset.y / radii.y).powi(2) <= 1.0
+            }
+            Geometry::Line { .. } => false,
+            _ => {
+                let winding: i32 = self
+                    .outline_polylines()
+                    .iter()
+                    .map(|(points, _)| winding_number(points, point))
+                    .sum();
+                winding != 0
+            }
+        }
+    }
+
+    // The distance from the point to the nearest point on the outline.
+    // Curves other than circles are approximated by straight segments.
+    // Returns infinity for geometries with no points.
+    pub fn distance_to_outline(&self, point: &XYPoint) -> f64 {
+        if let Geometry::Circle { center, radius } = self {
+            let offset = point.subtract(center);
+            return (offset.x.hypot(offset.y) - radius).abs();
+        }
+        self.outline_polylines()
+            .iter()
+            .flat_map(|(points, closed)| polyline_segments(points, *closed))
+            .map(|(start, end)| distance_to_segment(point, start, end))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    // The outline as polylines, each with whether it is closed. Curves are
+    // approximated by CURVE_STEPS straight segments and ellipses by
+    // ELLIPSE_STEPS.
+    fn outline_polylines(&self) -> Vec<(Vec<XYPoint>, bool)> {
+        match self {
+            Geometry::Rectangle { top_left, size } => {
+                let bottom_right = top_left.add(size);
+                vec![(
+                    vec![
+                        top_left.clone(),
+                        XYPoint::new(bottom_right.x, top_left.y),
+                        bottom_right.clone(),
+                        XYPoint::new(top_left.x, bottom_right.y),
+                    ],
+                    true,
+                )]
+            }
+            Geometry::Circle { center, radius } => Geometry::Ellipse {
+                center: center.clone(),
+                radii: XYPoint::new(*radius, *radius),
+            }
+            .outline_polylines(),
+            Geometry::Ellipse { center, radii } => {
+                let points = (0..ELLIPSE_STEPS)
+                    .map(|step| {
+                        let angle =
+                            std::f64::consts::TAU * f64::from(step) / f64::from(ELLIPSE_STEPS);
+                        XYPoint::new(
+                            center.x + radii.x * angle.cos(),
+                            center.y + radii.y * angle.sin(),
+                        )
+                    })
+                    .collect();
+                vec![(points, true)]
+            }
+            Geometry::Line { start, end } => vec![(vec![start.clone(), end.clone()], false)],
+            Geometry::Polyline { points } => vec![(points.clone(), false)],
+            Geometry::Polygon { points } => vec![(points.clone(), true)],
+            Geometry::Path { segments } => flatten_path(segments),
+        }
+    }
+}
+
+const CURVE_STEPS: u32 = 16;
+const ELLIPSE_STEPS: u32 = 64;
+
+// Split a path into subpaths and approximate the curves by straight
+// segments.
+
+fn flatten_path(segments: &[PathSegment]) -> Vec<(Vec<XYPoint>, bool)> {
+    let mut subpaths = Vec::new();
+    let mut current: Vec<XYPoint> = Vec::new();
+    let mut subpath_start = XYPoint::new(0.0, 0.0);
+    for segment in segments {
+        // Drawing after a close continues from the start of the subpath.
+        if current.is_empty() && !matches!(segment, PathSegment::MoveTo { .. }) {
+            current.push(subpath_start.clone());
+        }
+        let position = current
+            .last()
+            .cloned()
+            .unwrap_or_else(|| subpath_start.clone());
+        match segment {
+            PathSegment::MoveTo { to } => {
+                if !current.is_empty() {
+                    subpaths.push((std::mem::take(&mut current), false));
+                }
+                subpath_start = to.clone();
+                current.push(to.clone());
+            }
+            PathSegment::LineTo { to } => current.push(to.clone()),
+            PathSegment::CubicTo {
+                control1,
+                control2,
+                to,
+            } => current.extend((1..=CURVE_STEPS).map(|step| {
+                let t = f64::from(step) / f64::from(CURVE_STEPS);
+                XYPoint::new(
+                    cubic_at(position.x, control1.x, control2.x, to.x, t),
+                    cubic_at(position.y, control1.y, control2.y, to.y, t),
+                )
+            })),
+            PathSegment::QuadTo { control, to } => current.extend((1..=CURVE_STEPS).map(|step| {
+                let t = f64::from(step) / f64::from(CURVE_STEPS);
+                XYPoint::new(
+                    quad_at(position.x, control.x, to.x, t),
+                    quad_at(position.y, control.y, to.y, t),
+                )
+            })),
+            PathSegment::Close => subpaths.push((std::mem::take(&mut current), true)),
+        }
+    }
+    if !current.is_empty() {
+        subpaths.push((current, false));
+    }
+    subpaths
+}
+
+// The segments of a polyline including the closing segment if it is
+// closed. A single point is a degenerate segment.
+
+fn polyline_segments(points: &[XYPoint], closed: bool) -> Vec<(&XYPoint, &XYPoint)> {
+    let mut segments: Vec<(&XYPoint, &XYPoint)> =
+        points.windows(2).map(|pair| (&pair[0], &pair[1])).collect();
+    match (points.first(), points.last()) {
+        (Some(first), Some(last)) if closed || points.len() == 1 => segments.push((last, first)),
+        _ => {}
+    }
+    segments
+}
+
+// The winding number of a closed polygon around a point: the number of
+// times the outline goes counterclockwise around the point less the
+// number of times it goes clockwise.
+
+fn winding_number(points: &[XYPoint], point: &XYPoint) -> i32 {
+    let mut winding = 0;
+    for (index, start) in points.iter().enumerate() {
+        let end = &points[(index + 1) % points.len()];
+        // Which side of the edge is the point on?
+        let side =
+            (end.x - start.x) * (point.y - start.y) - (point.x - start.x) * (end.y - start.y);
+        if start.y <= point.y {
+            if point.y < end.y && 0.0 < side {
+                winding += 1;
+            }
+        } else if end.y <= point.y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+fn distance_to_segment(point: &XYPoint, start: &XYPoint, end: &XYPoint) -> f64 {
+    let along = end.subtract(start);
+    let offset = point.subtract(start);
+    let length_squared = along.x * along.x + along.y * along.y;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        ((offset.x * along.x + offset.y * along.y) / length_squared).clamp(0.0, 1.0)
+    };
+    let nearest = XYPoint::new(start.x + along.x * t, start.y + along.y * t);
+    let difference = point.subtract(&nearest);
+    difference.x.hypot(difference.y)
+}
+
+fn bounds_of_points(points: impl Iterator<Item = XYPoint>) -> Bounds {
+    points
+        .map(|p| Bounds::new(p.clone(), p))
+        .reduce(|acc, b| acc.union(&b))
+        .unwrap_or_else(|| Bounds::new(XYPoint::new(0.0, 0.0), XYPoint::new(0.0, 0.0)))
+}
+
+// The points on a path that determine its bounds: the end points of the
+// segments plus any points where a curve reaches an extreme in x or y.
+// (The control points themselves generally lie outside the curve.)
+
+fn path_extreme_points(segments: &[PathSegment]) -> impl Iterator<Item = XYPoint> + '_ {
+    let mut current = XYPoint::new(0.0, 0.0);
+    let mut subpath_start = XYPoint::new(0.0, 0.0);
+    segments.iter().flat_map(move |segment| {
+        let mut points = Vec::new();
+        match segment {
+            PathSegment::CubicTo {
+                control1,
+                control2,
+                to,
+            } => {
+                let (p0, p1, p2, p3) = (&current, control1, control2, to);
+                let mut ts = cubic_extrema(p0.x, p1.x, p2.x, p3.x);
+                ts.extend(cubic_extrema(p0.y, p1.y, p2.y, p3.y));
+                points.extend(ts.into_iter().map(|t| {
+                    XYPoint::new(
+                        cubic_at(p0.x, p1.x, p2.x, p3.x, t),
+                        cubic_at(p0.y, p1.y, p2.y, p3.y, t),
+                    )
+                }));
+            }
+            PathSegment::QuadTo { control, to } => {
+                let (p0, p1, p2) = (&current, control, to);
+                let mut ts = quad_extremum(p0.x, p1.x, p2.x);
+                ts.extend(quad_extremum(p0.y, p1.y, p2.y));
+                points.extend(ts.into_iter().map(|t| {
+                    XYPoint::new(quad_at(p0.x, p1.x, p2.x, t), quad_at(p0.y, p1.y, p2.y, t))
+                }));
+            }
+            _ => {}
+        }
+        // Close returns to a point that is already included, and drawing
+        // after it continues from there.
+        if let Some(end) = segment.end_point() {
+            points.push(end.clone());
+            current = end.clone();
+        }
+        match segment {
+            PathSegment::MoveTo { to } => subpath_start = to.clone(),
+            PathSegment::Close => current = subpath_start.clone(),
+            _ => {}
+        }
+        points
+    })
+}
+
+fn cubic_at(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+    let u = 1.0 - t;
+    u * u * u * p0 + 3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t * p3
+}
+
+fn quad_at(p0: f64, p1: f64, p2: f64, t: f64) -> f64 {
+    let u = 1.0 - t;
+    u * u * p0 + 2.0 * u * t * p1 + t * t * p2
+}
+
+// The parameters strictly between 0 and 1 at which a cubic Bezier has a
+// zero derivative along one axis.
+
+fn cubic_extrema(p0: f64, p1: f64, p2: f64, p3: f64) -> Vec<f64> {
+    // The derivative is 3 (a t^2 + b t + c).
+    let a = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+    let b = 2.0 * (p0 - 2.0 * p1 + p2);
+    let c = p1 - p0;
+    let roots = if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            vec![]
+        } else {
+            vec![-c / b]
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            vec![]
+        } else {
+            let root = discriminant.sqrt();
+            vec![(-b + root) / (2.0 * a), (-b - root) / (2.0 * a)]
+        }
+    };
+    roots.into_iter().filter(|t| 0.0 < *t && *t < 1.0).collect()
+}
+
+// The parameter strictly between 0 and 1 at which a quadratic Bezier has
+// a zero derivative along one axis, if any.
+
+fn quad_extremum(p0: f64, p1: f64, p2: f64) -> Vec<f64> {
+    let denominator = p0 - 2.0 * p1 + p2;
+    if denominator.abs() < 1e-12 {
+        return vec![];
+    }
+    let t = (p0 - p1) / denominator;
+    if 0.0 < t && t < 1.0 { vec![t] } else { vec![] }
+}
+
+// The segments whose end points make up each anchor of a path, in order.
+// Each anchor is usually the end of a single segment, but a segment that
+// returns to the start of its subpath just before a Close ends at the same
+// anchor as the MoveTo that began the subpath.
+
+fn path_anchor_segments(segments: &[PathSegment]) -> Vec<Vec<usize>> {
+    let mut anchors: Vec<Vec<usize>> = Vec::new();
+    let mut subpath_start: Option<usize> = None;
+    for (index, segment) in segments.iter().enumerate() {
+        let Some(end) = segment.end_point() else {
+            continue;
+        };
+        if let PathSegment::MoveTo { .. } = segment {
+            subpath_start = Some(anchors.len());
+        } else if let Some(start) = subpath_start
+            && segments.get(index + 1) == Some(&PathSegment::Close)
+            && segments[anchors[start][0]].end_point() == Some(end)
+        {
+            anchors[start].push(index);
+            continue;
+        }
+        anchors.push(vec![index]);
+    }
+    anchors
+}
+
+// Move the anchor with the given index (as numbered by
+// path_anchor_segments) together with the adjacent control points.
+
+fn path_with_anchor_moved(
+    segments: &[PathSegment],
+    anchor_index: usize,
+    offset: &XYPoint,
+) -> Vec<PathSegment> {
+    let anchor_segments = path_anchor_segments(segments)
+        .into_iter()
+        .nth(anchor_index)
+        .unwrap_or_default();
+    let mut result = Vec::with_capacity(segments.len());
+    let mut previous_was_anchor = false;
+    for (index, segment) in segments.iter().enumerate() {
+        let is_anchor = anchor_segments.contains(&index);
+        let moved = match segment {
+            // The anchor itself plus its incoming control point.
+            PathSegment::CubicTo {
+                control1,
+                control2,
+                to,
+            } if is_anchor || previous_was_anchor => PathSegment::CubicTo {
+                control1: if previous_was_anchor {
+                    control1.add(offset)
+                } else {
+                    control1.clone()
+                },
+                control2: if is_anchor {
+                    control2.add(offset)
+                } else {
+                    control2.clone()
+                },
+                to: if is_anchor {
+                    to.add(offset)
+                } else {
+                    to.clone()
+                },
+            },
+            PathSegment::MoveTo { to } if is_anchor => PathSegment::MoveTo { to: to.add(offset) },
+            PathSegment::LineTo { to } if is_anchor => PathSegment::LineTo { to: to.add(offset) },
+            // The control point of a quadratic is shared by both ends, so
+            // it stays put.
+            PathSegment::QuadTo { control, to } if is_anchor => PathSegment::QuadTo {
+                control: control.clone(),
+                to: to.add(offset),
+            },
+            _ => segment.clone(),
+        };
+        result.push(moved);
+        previous_was_anchor = is_anchor;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64) -> XYPoint {
+        XYPoint::new(x, y)
+    }
+
+    fn path(segments: Vec<PathSegment>) -> Geometry {
+        Geometry::Path { segments }
+    }
+
+    fn line_to(x: f64, y: f64) -> PathSegment {
+        PathSegment::LineTo { to: point(x, y) }
+    }
+
+    fn move_to(x: f64, y: f64) -> PathSegment {
+        PathSegment::MoveTo { to: point(x, y) }
+    }
+
+    #[test]
+    fn closing_segment_back_to_the_start_shares_its_anchor() {
+        let triangle = path(vec![
+            move_to(0.0, 0.0),
+            line_to(10.0, 0.0),
+            line_to(10.0, 10.0),
+            line_to(0.0, 0.0),
+            PathSegment::Close,
+        ]);
+        assert!(triangle.anchors() == vec![point(0.0, 0.0), point(10.0, 0.0), point(10.0, 10.0)]);
+        let moved = triangle.with_anchor_moved(0, &point(-5.0, 5.0));
+        assert!(
+            moved
+                == path(vec![
+                    move_to(-5.0, 5.0),
+                    line_to(10.0, 0.0),
+                    line_to(10.0, 10.0),
+                    line_to(-5.0, 5.0),
+                    PathSegment::Close,
+                ])
+        );
+        assert!(moved.with_anchor_moved(2, &point(1.0, 1.0)).anchors()[2] == point(11.0, 11.0));
+    }
+
+    #[test]
+    fn curved_closing_segment_moves_its_incoming_handle_with_the_start() {
+        let closed = path(vec![
+            move_to(0.0, 0.0),
+            line_to(10.0, 0.0),
+            PathSegment::CubicTo {
+                control1: point(10.0, 10.0),
+                control2: point(0.0, 10.0),
+                to: point(0.0, 0.0),
+            },
+            PathSegment::Close,
+        ]);
+        assert_eq!(closed.anchors().len(), 2);
+        let moved = closed.with_anchor_moved(0, &point(0.0, -2.0));
+        assert!(
+            moved
+                == path(vec![
+                    move_to(0.0, -2.0),
+                    line_to(10.0, 0.0),
+                    PathSegment::CubicTo {
+                        control1: point(10.0, 10.0),
+                        control2: point(0.0, 8.0),
+                        to: point(0.0, -2.0),
+                    },
+                    PathSegment::Close,
+                ])
+        );
+    }
+
+    #[test]
+    fn open_path_ending_at_its_start_keeps_both_anchors() {
+        let loop_path = path(vec![
+            move_to(0.0, 0.0),
+            line_to(10.0, 0.0),
+            line_to(0.0, 0.0),
+        ]);
+        assert_eq!(loop_path.anchors().len(), 3);
+    }
+
+    #[test]
+    fn path_bounds_continue_from_the_subpath_start_after_a_close() {
+        // The curve starts back at the origin, so its leftmost point is at
+        // x = -10 halfway along.
+        let closed_then_curved = path(vec![
+            move_to(0.0, 0.0),
+            line_to(10.0, 0.0),
+            line_to(10.0, 10.0),
+            PathSegment::Close,
+            PathSegment::QuadTo {
+                control: point(-20.0, 5.0),
+                to: point(0.0, 10.0),
+            },
+        ]);
+        let bounds = closed_then_curved.bounds();
+        assert!(bounds == Bounds::new(point(-10.0, 0.0), point(10.0, 10.0)));
+    }
+
+    // Hit-testing cases: a geometry with points inside it, points outside
+    // it, and points on its outline.
+
+    struct HitCase {
+        geometry: Geometry,
+        inside: Vec<XYPoint>,
+        outside: Vec<XYPoint>,
+        on_outline: Vec<XYPoint>,
+    }
+
+    fn hit_case(
+        geometry: Geometry,
+        inside: &[XYPoint],
+        outside: &[XYPoint],
+        on_outline: &[XYPoint],
+    ) -> HitCase {
+        HitCase {
+            geometry,
+            inside: inside.to_vec(),
+            outside: outside.to_vec(),
+            on_outline: on_outline.to_vec(),
+        }
+    }
+
+    fn hit_cases() -> Vec<HitCase> {
+        vec![
+            hit_case(
+                Geometry::rectangle(0.0, 0.0, 20.0, 10.0),
+                &[point(10.0, 5.0), point(19.0, 9.0)],
+                &[point(21.0, 5.0), point(10.0, -1.0)],
+                &[point(0.0, 5.0), point(10.0, 10.0), point(20.0, 10.0)],
+            ),
+            hit_case(
+                Geometry::circle(0.0, 0.0, 10.0),
+                &[point(0.0, 0.0), point(6.0, 7.0)],
+                &[point(8.0, 8.0), point(0.0, 11.0)],
+                &[point(10.0, 0.0), point(6.0, 8.0)],
+            ),
+            hit_case(
+                Geometry::ellipse(0.0, 0.0, 20.0, 10.0),
+                &[point(0.0, 0.0), point(19.0, 0.0)],
+                &[point(0.0, 11.0), point(15.0, 8.0)],
+                &[point(20.0, 0.0), point(0.0, -10.0)],
+            ),
+            hit_case(
+                Geometry::line(0.0, 0.0, 10.0, 10.0),
+                &[],
+                &[point(5.0, 0.0), point(5.0, 5.0)],
+                &[point(5.0, 5.0), point(10.0, 10.0)],
+            ),
+            // A polyline encloses what it would if closed but its closing
+            // edge is not part of the outline.
+            hit_case(
+                Geometry::Polyline {
+                    points: vec![point(0.0, 0.0), point(10.0, 0.0), point(10.0, 10.0)],
+                },
+                &[point(8.0, 2.0)],
+                &[point(2.0, 8.0), point(11.0, 5.0)],
+                &[point(5.0, 0.0), point(10.0, 5.0)],
+            ),
+            hit_case(
+                Geometry::Polygon {
+                    points: vec![point(0.0, 0.0), point(10.0, 0.0), point(10.0, 10.0)],
+                },
+                &[point(8.0, 2.0)],
+                &[point(2.0, 8.0), point(11.0, 5.0)],
+                &[point(5.0, 0.0), point(5.0, 5.0)],
+            ),
+            hit_case(
+                path(vec![
+                    move_to(0.0, 0.0),
+                    line_to(10.0, 0.0),
+                    PathSegment::QuadTo {
+                        control: point(10.0, 10.0),
+                        to: point(0.0, 10.0),
+                    },
+                    PathSegment::Close,
+                ]),
+                &[point(5.0, 5.0), point(7.0, 7.0)],
+                &[point(9.0, 9.0), point(-1.0, 5.0)],
+                &[point(5.0, 0.0), point(0.0, 5.0), point(7.5, 7.5)],
+            ),
+        ]
+    }
+
+    #[test]
+    fn geometries_contain_points_inside_them() {
+        for case in hit_cases() {
+            for p in &case.inside {
+                assert!(case.geometry.contains_point(p), "({}, {}) inside", p.x, p.y);
+            }
+            for p in &case.outside {
+                assert!(
+                    !case.geometry.contains_point(p),
+                    "({}, {}) outside",
+                    p.x,
+                    p.y
+                );
+            }
         }
     }
+
+    #[test]
+    fn outline_distance_is_zero_on_the_outline() {
+        for case in hit_cases() {
+            for p in &case.on_outline {
+                let distance = case.geometry.distance_to_outline(p);
+                assert!(distance < 0.05, "({}, {}) is {distance} away", p.x, p.y);
+            }
+            for p in case.inside.iter().chain(&case.outside) {
+                if !case.on_outline.contains(p) {
+                    let distance = case.geometry.distance_to_outline(p);
+                    assert!(distance > 0.5, "({}, {}) is {distance} away", p.x, p.y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn outline_distance_is_measured_to_the_nearest_edge() {
+        let rectangle = Geometry::rectangle(0.0, 0.0, 20.0, 10.0);
+        assert_eq!(rectangle.distance_to_outline(&point(10.0, 4.0)), 4.0);
+        assert_eq!(rectangle.distance_to_outline(&point(23.0, 14.0)), 5.0);
+        let circle = Geometry::circle(0.0, 0.0, 10.0);
+        assert_eq!(circle.distance_to_outline(&point(0.0, 4.0)), 6.0);
+        assert_eq!(circle.distance_to_outline(&point(0.0, -13.0)), 3.0);
+        let empty = Geometry::Polyline { points: vec![] };
+        assert_eq!(empty.distance_to_outline(&point(0.0, 0.0)), f64::INFINITY);
+    }
+
+    #[test]
+    fn overlapping_subpaths_use_the_nonzero_rule() {
+        let square = |left: f64, clockwise: bool| {
+            let mut corners = [
+                point(left, 0.0),
+                point(left + 10.0, 0.0),
+                point(left + 10.0, 10.0),
+                point(left, 10.0),
+            ];
+            if !clockwise {
+                corners.reverse();
+            }
+            let mut segments = vec![PathSegment::MoveTo {
+                to: corners[0].clone(),
+            }];
+            segments.extend(
+                corners[1..]
+                    .iter()
+                    .map(|to| PathSegment::LineTo { to: to.clone() }),
+            );
+            segments.push(PathSegment::Close);
+            segments
+        };
+        let same_way = path([square(0.0, true), square(5.0, true)].concat());
+        assert!(same_way.contains_point(&point(7.0, 5.0)));
+        let opposite_ways = path([square(0.0, true), square(5.0, false)].concat());
+        assert!(!opposite_ways.contains_point(&point(7.0, 5.0)));
+        assert!(opposite_ways.contains_point(&point(2.0, 5.0)));
+    }
+
+    fn shape(geometry: Geometry, style: Style, transform: Transform) -> Shape {
+        Shape::new(geometry, style).with_transform(transform)
+    }
+
+    #[test]
+    fn shapes_hit_their_fill_and_stroke() {
+        let filled = Style::new(Color::BLACK);
+        let outlined = Style::new_outline(Stroke::new(Color::BLACK, 4.0));
+        let rectangle = Geometry::rectangle(0.0, 0.0, 20.0, 10.0);
+        let identity = Transform::identity();
+
+        let filled_rectangle = shape(rectangle.clone(), filled.clone(), identity.clone());
+        assert!(filled_rectangle.contains_point(&point(10.0, 5.0)));
+        assert!(!filled_rectangle.contains_point(&point(21.0, 5.0)));
+
+        let outlined_rectangle = shape(rectangle.clone(), outlined.clone(), identity.clone());
+        assert!(!outlined_rectangle.contains_point(&point(10.0, 5.0)));
+        assert!(outlined_rectangle.contains_point(&point(21.0, 5.0)));
+        assert!(outlined_rectangle.contains_point(&point(-2.0, 5.0)));
+        assert!(!outlined_rectangle.contains_point(&point(-3.0, 5.0)));
+
+        // Open shapes are stroked in their fill color but never filled.
+        let line = shape(
+            Geometry::line(0.0, 0.0, 10.0, 0.0),
+            filled,
+            identity.clone(),
+        );
+        assert!(line.contains_point(&point(5.0, 0.9)));
+        assert!(!line.contains_point(&point(5.0, 1.1)));
+
+        let invisible = shape(
+            rectangle.clone(),
+            Style {
+                fill: None,
+                stroke: None,
+            },
+            identity,
+        );
+        assert!(!invisible.contains_point(&point(10.0, 5.0)));
+    }
+
+    #[test]
+    fn transformed_shapes_hit_in_document_space() {
+        let filled = Style::new(Color::BLACK);
+        let rectangle = Geometry::rectangle(0.0, 0.0, 20.0, 10.0);
+
+        let moved = shape(
+            rectangle.clone(),
+            filled.clone(),
+            Transform::translation(100.0, 0.0),
+        );
+        assert!(moved.contains_point(&point(110.0, 5.0)));
+        assert!(!moved.contains_point(&point(10.0, 5.0)));
+
+        // A quarter turn clockwise about the origin takes the rectangle to
+        // x in [-10, 0] and y in [0, 20].
+        let turned = shape(
+            rectangle.clone(),
+            filled.clone(),
+            Transform::rotation(std::f64::consts::FRAC_PI_2),
+        );
+        assert!(turned.contains_point(&point(-5.0, 15.0)));
+        assert!(!turned.contains_point(&point(15.0, 5.0)));
+
+        // Strokes scale with the shape.
+        let outlined = Style::new_outline(Stroke::new(Color::BLACK, 2.0));
+        let scaled = shape(rectangle.clone(), outlined, Transform::scaling(3.0, 3.0));
+        assert!(scaled.contains_point(&point(62.5, 15.0)));
+        assert!(!scaled.contains_point(&point(64.0, 15.0)));
+
+        let collapsed = shape(rectangle, filled, Transform::scaling(0.0, 1.0));
+        assert!(!collapsed.contains_point(&point(0.0, 5.0)));
+    }
 }
@@ -1,5 +1,9 @@
-use crate::shapes::core::{Color, Geometry, Shape, Style};
-use std::collections::HashMap;
+use crate::shapes::color::Color;
+use crate::shapes::core::{Bounds, Geometry, Shape, Style, XYPoint};
+use crate::shapes::spatial_index::SpatialIndex;
+use crate::shapes::z_order::{Key, ZOrder};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::vec::Vec;
 
 // ShapeId provides a reference to shapes across changes in the document.
@@ -10,7 +14,9 @@ use std::vec::Vec;
 // but more difficult to generate (and, if one is squeamish about
 // randomization, more difficult to guarantee unique).
 
-#[derive(Eq, PartialEq, Clone, Copy, Hash)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct ShapeId {
     id: usize,
 }
@@ -25,6 +31,7 @@ impl std::fmt::Display for ShapeId {
 // the generator past any particular shape id.
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct ShapeIdGenerator {
     next_id: usize,
 }
@@ -50,65 +57,285 @@ impl ShapeIdGenerator {
     }
 }
 
-// Our document consists of a sequence of shape ids listing the shapes
+// Layers organize the document. Each layer has a name and flags for
+// whether it is drawn and whether its shapes can be edited.
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub locked: bool,
+}
+
+impl Layer {
+    // New layers are visible and unlocked.
+    pub fn new(name: &str) -> Layer {
+        Layer {
+            name: name.to_string(),
+            visible: true,
+            locked: false,
+        }
+    }
+}
+
+// The name for the layer in documents that start out with just one.
+const DEFAULT_LAYER_NAME: &str = "Layer 1";
+
+// Alignments line up the edges or centers of shapes and groups with the
+// bounds enclosing all of them. The horizontal alignments move things
+// left or right: left edges, horizontal centers, or right edges. The
+// vertical alignments move things up or down: top edges, vertical
+// centers, or bottom edges.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Alignment {
+    Left,
+    CenterHorizontal,
+    Right,
+    Top,
+    CenterVertical,
+    Bottom,
+}
+
+impl Alignment {
+    // All of the alignments in the order to present them.
+    pub fn all() -> &'static [Alignment] {
+        &[
+            Alignment::Left,
+            Alignment::CenterHorizontal,
+            Alignment::Right,
+            Alignment::Top,
+            Alignment::CenterVertical,
+            Alignment::Bottom,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Alignment::Left => "Align Left",
+            Alignment::CenterHorizontal => "Align Horizontal Centers",
+            Alignment::Right => "Align Right",
+            Alignment::Top => "Align Top",
+            Alignment::CenterVertical => "Align Vertical Centers",
+            Alignment::Bottom => "Align Bottom",
+        }
+    }
+}
+
+// Distributing spaces shapes and groups out evenly along an axis so that
+// the gaps between neighbors are equal.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Distribution {
+    Horizontal,
+    Vertical,
+}
+
+impl Distribution {
+    // All of the distributions in the order to present them.
+    pub fn all() -> &'static [Distribution] {
+        &[Distribution::Horizontal, Distribution::Vertical]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Distribution::Horizontal => "Distribute Horizontally",
+            Distribution::Vertical => "Distribute Vertically",
+        }
+    }
+}
+
+// Our document consists of a sequence of layer ids listing the layers
 // to render from bottom to top, a hash map of shapes keyed by shape ids,
 // and the next shape id to generate which should be greater than all
-// of the shape ids ever used or generated for this document.
+// of the shape ids ever used or generated for this document. We also
+// keep a spatial index of the shapes in sync with the shapes themselves
+// so that region and point queries scale to large documents.
+//
+// Shapes can be gathered into groups. Groups take their ids from the same
+// generator as shapes and can appear wherever a shape can: in a layer or
+// inside another group. Each group lists its children from bottom to top,
+// and we keep a map from each child to its group so that we can walk up
+// from a shape to its layer. Layers are groups at the top of the tree
+// that also have an entry in the layers map. A document always has at
+// least one layer.
 
 #[derive(Clone)]
 pub struct Document {
     shapes: HashMap<ShapeId, Shape>,
-    sequence: Vec<ShapeId>,
+    groups: HashMap<ShapeId, ZOrder>,
+    parents: HashMap<ShapeId, ShapeId>,
+    layers: HashMap<ShapeId, Layer>,
+    sequence: ZOrder,
     shape_id_generator: ShapeIdGenerator,
+    spatial_index: SpatialIndex,
 }
 
+#[derive(Debug)]
 pub enum DocError {
     DuplicateShapeId(ShapeId),
+    // The following errors come from loading a serialized document.
+    MalformedJson(String),
+    UnsupportedVersion(u32),
+    MissingShape(ShapeId),
+    UnsequencedShape(ShapeId),
+    NotALayer(ShapeId),
+    NestedLayer(ShapeId),
+    GroupCycle(ShapeId),
+    // Importing from SVG fails only if the SVG is not well-formed XML.
+    MalformedSvg(String),
 }
 
-// Note: We need the lifetime for the shapes iterator
+impl std::fmt::Display for DocError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            DocError::DuplicateShapeId(shape_id) => write!(f, "duplicate shape id {shape_id}"),
+            DocError::MalformedJson(message) => write!(f, "malformed document JSON: {message}"),
+            DocError::UnsupportedVersion(version) => {
+                write!(f, "unsupported document format version {version}")
+            }
+            DocError::MissingShape(shape_id) => {
+                write!(f, "sequence refers to missing shape id {shape_id}")
+            }
+            DocError::UnsequencedShape(shape_id) => {
+                write!(f, "shape id {shape_id} is not in the sequence")
+            }
+            DocError::NotALayer(shape_id) => {
+                write!(
+                    f,
+                    "sequence refers to shape id {shape_id} which is not a layer"
+                )
+            }
+            DocError::NestedLayer(shape_id) => {
+                write!(f, "layer id {shape_id} is inside a group")
+            }
+            DocError::GroupCycle(group_id) => {
+                write!(f, "group id {group_id} is inside itself")
+            }
+            DocError::MalformedSvg(message) => write!(f, "malformed SVG: {message}"),
+        }
+    }
+}
+
+// A partial snapshot records the state of some of the shapes in a
+// document -- None for shapes that do not exist -- along with the
+// placement of some shapes, groups, and layers in the z-order, whether
+// some groups exist, and the names and flags of some layers. Restoring a
+// snapshot returns those parts of the document to their recorded state
+// and leaves the rest alone. Partial snapshots are the basis for undo and
+// redo.
+//
+// Placements are recorded one id at a time as the group holding the id
+// (None for layers, which sit in the sequence) and its z-order key there.
+// Since moving a shape in the z-order never changes the keys of the shapes
+// around it, recording the shapes that move is enough to put everything
+// back, however long the sequence they move in.
+
+#[derive(PartialEq, Clone)]
+pub struct PartialSnapshot {
+    shapes: HashMap<ShapeId, Option<Shape>>,
+    groups: HashMap<ShapeId, bool>,
+    placements: HashMap<ShapeId, Option<Placement>>,
+    layers: HashMap<ShapeId, Option<Layer>>,
+}
+
+#[derive(PartialEq, Clone)]
+struct Placement {
+    parent_id: Option<ShapeId>,
+    key: Key,
+}
+
+impl PartialSnapshot {
+    // An empty snapshot records nothing and restores nothing.
+    pub fn new_empty() -> Self {
+        Self {
+            shapes: HashMap::new(),
+            groups: HashMap::new(),
+            placements: HashMap::new(),
+            layers: HashMap::new(),
+        }
+    }
+
+    // Does the snapshot already record the state of this shape id?
+    pub fn contains_shape_id(&self, shape_id: &ShapeId) -> bool {
+        self.shapes.contains_key(shape_id)
+    }
+}
 
-impl<'a> Document {
+impl Document {
     // Create a new empty document is easy (and unlike other functions that
-    // perform validation, does not fail).
+    // perform validation, does not fail). It has a single empty layer.
     pub fn new_empty() -> Self {
+        let mut doc = Self::new_without_layers();
+        doc.add_layer_with_children(Layer::new(DEFAULT_LAYER_NAME), ZOrder::new_empty());
+        doc
+    }
+
+    // The constructors below build up their contents before making layers
+    // so that the layer ids come after the shape ids.
+    fn new_without_layers() -> Self {
         Self {
-            sequence: Vec::new(),
+            sequence: ZOrder::new_empty(),
             shapes: HashMap::new(),
+            groups: HashMap::new(),
+            parents: HashMap::new(),
+            layers: HashMap::new(),
             shape_id_generator: ShapeIdGenerator::default(),
+            spatial_index: SpatialIndex::new_empty(),
+        }
+    }
+
+    // Add a new layer on top holding children that do not yet have a
+    // parent.
+    fn add_layer_with_children(&mut self, layer: Layer, children: ZOrder) -> ShapeId {
+        let layer_id = self.generate_shape_id();
+        for child_id in children.iter() {
+            self.parents.insert(*child_id, layer_id);
         }
+        self.groups.insert(layer_id, children);
+        self.layers.insert(layer_id, layer);
+        self.sequence.push_top(layer_id);
+        layer_id
     }
 
     // The standard way to create a new document (if the empty document
     // is insufficient) is to take a vector of ShapeId/Shape pairs containing
     // the shapes to display from bottom to top. Duplicate shape id's will result
-    // in an error.
+    // in an error. The shapes all go in a single layer.
     pub fn new_from_pairs(pairs: Vec<(ShapeId, Shape)>) -> Result<Self, DocError> {
-        let mut doc = Self::new_empty();
+        let mut doc = Self::new_without_layers();
+        let mut children = ZOrder::new_empty();
         for (shape_id, shape) in pairs {
             // Prevent multiple uses of the same shape_id
             if doc.shapes.contains_key(&shape_id) {
                 return Err(DocError::DuplicateShapeId(shape_id));
             }
             // Add the shape to the sequence
-            doc.sequence.push(shape_id);
+            children.push_top(shape_id);
             // Add the shape to the dictionary
             doc.shapes.insert(shape_id, shape);
             // Make sure that next_shape_id is larger than any of these shapes
             doc.shape_id_generator.advance_past(shape_id);
         }
+        doc.add_layer_with_children(Layer::new(DEFAULT_LAYER_NAME), children);
+        doc.spatial_index = SpatialIndex::new_from_shapes(doc.shapes.iter());
         Ok(doc)
     }
 
     // Create a new document from a vector of shapes -- e.g., from
     // reading a file in some external format. The shapes will
-    // be displayed in vector order from bottom to top.
+    // be displayed in vector order from bottom to top in a single layer.
     pub fn new_from_shapes(shapes: &Vec<Shape>) -> Self {
-        let mut doc = Self::new_empty();
+        let mut doc = Self::new_without_layers();
+        let mut children = ZOrder::new_empty();
         for shape in shapes {
             let shape_id = doc.generate_shape_id();
-            doc.upsert_shape_with_id(&shape_id, shape.clone());
+            children.push_top(shape_id);
+            doc.shapes.insert(shape_id, shape.clone());
         }
+        doc.add_layer_with_children(Layer::new(DEFAULT_LAYER_NAME), children);
+        doc.spatial_index = SpatialIndex::new_from_shapes(doc.shapes.iter());
         doc
     }
 
@@ -118,52 +345,96 @@ impl<'a> Document {
         Self::new_from_shapes(&vec![
             Shape::new(
                 Geometry::circle(100.0, 150.0, 80.0),
-                Style::new(Color::Blue),
+                Style::new(Color::BLUE),
             ),
             Shape::new(
                 Geometry::circle(120.0, 120.0, 100.0),
-                Style::new(Color::Red),
+                Style::new(Color::RED),
             ),
             Shape::new(
                 Geometry::circle(200.0, 90.0, 70.0),
-                Style::new(Color::Indigo),
+                Style::new(Color::INDIGO),
             ),
             Shape::new(
                 Geometry::rectangle(250.0, 80.0, 200.0, 20.0),
-                Style::new(Color::Violet),
+                Style::new(Color::VIOLET),
             ),
             Shape::new(
                 Geometry::rectangle(0.0, 0.0, 40.0, 40.0),
-                Style::new(Color::Black),
+                Style::new(Color::BLACK),
             ),
             Shape::new(
                 Geometry::rectangle(40.0, 80.0, 40.0, 40.0),
-                Style::new(Color::Green),
+                Style::new(Color::GREEN),
             ),
         ])
     }
 
-    // Get an iterator for the sequence of shape ids from bottom to top.
+    // Get an iterator for the sequence of layer ids from bottom to top.
 
-    pub fn shape_ids_sequence_iter(&self) -> std::slice::Iter<'_, ShapeId> {
+    pub fn layer_ids_iter(&self) -> impl DoubleEndedIterator<Item = &ShapeId> + '_ {
         self.sequence.iter()
     }
 
-    // Get an iterator for the sequence of ShapeId, Shape pairs from
-    // bottom to top.
+    // Get the id of the top layer.
 
-    pub fn shape_id_shapes_iter(
+    pub fn top_layer_id(&self) -> ShapeId {
+        *self
+            .sequence
+            .iter()
+            .next_back()
+            .expect("documents always have a layer")
+    }
+
+    // Get a layer if any with a particular id
+
+    pub fn get_layer_by_id(&self, layer_id: &ShapeId) -> Option<&Layer> {
+        self.layers.get(layer_id)
+    }
+
+    // Get the id of the layer holding a shape or group. The layer for a
+    // layer is itself.
+
+    pub fn layer_id_for(&self, shape_id: &ShapeId) -> Option<ShapeId> {
+        let mut current_id = *shape_id;
+        loop {
+            if self.layers.contains_key(&current_id) {
+                return Some(current_id);
+            }
+            current_id = *self.parents.get(&current_id)?;
+        }
+    }
+
+    // Can a shape or group be seen and changed? It can if its layer is
+    // visible and unlocked.
+
+    pub fn is_editable(&self, shape_id: &ShapeId) -> bool {
+        self.layer_id_for(shape_id)
+            .and_then(|layer_id| self.layers.get(&layer_id))
+            .is_some_and(|layer| layer.visible && !layer.locked)
+    }
+
+    // Get an iterator for the ShapeId, Shape pairs for all of the shapes,
+    // including those inside groups, in painting order from bottom to top.
+
+    pub fn shape_id_shapes_iter(&self) -> impl DoubleEndedIterator<Item = (ShapeId, &Shape)> + '_ {
+        let mut pairs = Vec::with_capacity(self.shapes.len());
+        self.collect_shapes_in(&self.sequence, &mut pairs);
+        pairs.into_iter()
+    }
+
+    fn collect_shapes_in<'a>(
         &'a self,
-    ) -> std::iter::FilterMap<
-        std::slice::Iter<'a, ShapeId>,
-        impl Fn(&'a ShapeId) -> Option<(ShapeId, &'a Shape)>,
-    > {
-        let to_opt_shape_id_shape = |shape_id: &ShapeId| {
-            self.get_shape_by_id(shape_id)
-                .map(|shape| (*shape_id, shape))
-        };
-        self.shape_ids_sequence_iter()
-            .filter_map(to_opt_shape_id_shape)
+        children: &'a ZOrder,
+        pairs: &mut Vec<(ShapeId, &'a Shape)>,
+    ) {
+        for shape_id in children.iter() {
+            if let Some(shape) = self.shapes.get(shape_id) {
+                pairs.push((*shape_id, shape));
+            } else if let Some(grandchildren) = self.groups.get(shape_id) {
+                self.collect_shapes_in(grandchildren, pairs);
+            }
+        }
     }
 
     // Get a shape if any with a particular id
@@ -172,6 +443,236 @@ impl<'a> Document {
         self.shapes.get(shape_id)
     }
 
+    // Is this the id of a group? Layers do not count as groups here.
+
+    pub fn is_group(&self, shape_id: &ShapeId) -> bool {
+        self.groups.contains_key(shape_id) && !self.layers.contains_key(shape_id)
+    }
+
+    // Get an iterator for the children of a group or layer from bottom to
+    // top. Anything else has no children.
+
+    pub fn group_child_ids_iter(
+        &self,
+        group_id: &ShapeId,
+    ) -> impl DoubleEndedIterator<Item = &ShapeId> + '_ {
+        self.groups
+            .get(group_id)
+            .into_iter()
+            .flat_map(|children| children.iter())
+    }
+
+    // Get the group or layer directly containing a shape or group, or None
+    // for layers.
+
+    pub fn parent_of(&self, shape_id: &ShapeId) -> Option<ShapeId> {
+        self.parents.get(shape_id).copied()
+    }
+
+    // Get the top-level shape or group -- i.e., the one directly in a layer
+    // -- that contains a shape or group. This is what clicking on it
+    // selects.
+
+    pub fn top_level_id_for(&self, shape_id: &ShapeId) -> ShapeId {
+        let mut top_level_id = *shape_id;
+        while let Some(parent_id) = self.parents.get(&top_level_id)
+            && !self.layers.contains_key(parent_id)
+        {
+            top_level_id = *parent_id;
+        }
+        top_level_id
+    }
+
+    // Get the ids of all of the shapes and groups inside a group or layer.
+
+    pub fn descendant_ids_of(&self, group_id: &ShapeId) -> Vec<ShapeId> {
+        let mut descendant_ids = Vec::new();
+        for child_id in self.group_child_ids_iter(group_id) {
+            descendant_ids.push(*child_id);
+            descendant_ids.extend(self.descendant_ids_of(child_id));
+        }
+        descendant_ids
+    }
+
+    // Get the ids of the shapes making up a shape, group, or layer from
+    // bottom to top. A shape is made up of just itself.
+
+    pub fn shape_ids_in(&self, shape_id: &ShapeId) -> Vec<ShapeId> {
+        match self.groups.get(shape_id) {
+            Some(children) => {
+                let mut pairs = Vec::new();
+                self.collect_shapes_in(children, &mut pairs);
+                pairs.into_iter().map(|(shape_id, _)| shape_id).collect()
+            }
+            None if self.shapes.contains_key(shape_id) => vec![*shape_id],
+            None => Vec::new(),
+        }
+    }
+
+    // Get the bounds of a shape or group, or None if there is no such
+    // shape or the group is empty.
+
+    pub fn bounds_for_id(&self, shape_id: &ShapeId) -> Option<Bounds> {
+        self.shape_ids_in(shape_id)
+            .iter()
+            .filter_map(|shape_id| self.shapes.get(shape_id))
+            .map(|shape| shape.bounds())
+            .reduce(|acc, bounds| acc.union(&bounds))
+    }
+
+    // Get the bounds enclosing all of the shapes in the document or None
+    // if the document is empty.
+
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.shape_id_shapes_iter()
+            .map(|(_, shape)| shape.bounds())
+            .reduce(|acc, bounds| acc.union(&bounds))
+    }
+
+    // Get the ids of the shapes whose bounds intersect the given bounds
+    // from bottom to top.
+
+    pub fn shape_ids_intersecting(&self, bounds: &Bounds) -> Vec<ShapeId> {
+        self.shape_ids_matching_in(bounds, |shape| bounds.intersects(&shape.bounds()))
+    }
+
+    // Get the ids of the shapes whose bounds lie entirely within the given
+    // bounds from bottom to top.
+
+    pub fn shape_ids_within(&self, bounds: &Bounds) -> Vec<ShapeId> {
+        self.shape_ids_matching_in(bounds, |shape| bounds.contains_bounds(&shape.bounds()))
+    }
+
+    // Get the ids of the shapes that paint anything within the viewport --
+    // i.e., the shapes that need to be drawn -- from bottom to top. This is
+    // conservative: shapes whose painted bounds reach the viewport count
+    // even if nothing they paint actually shows.
+
+    pub fn shape_ids_in_viewport(&self, viewport: &Bounds) -> Vec<ShapeId> {
+        self.sorted_by_z_order(self.spatial_index.shape_ids_intersecting(viewport))
+    }
+
+    // Get the id of the topmost shape that is painted at the point -- i.e.,
+    // the shape that a click at the point would hit -- or None if there is
+    // no shape there.
+
+    pub fn topmost_shape_at(&self, point: &XYPoint) -> Option<ShapeId> {
+        self.sorted_by_z_order(self.spatial_index.shape_ids_at(point))
+            .into_iter()
+            .rev()
+            .find(|shape_id| {
+                self.shapes
+                    .get(shape_id)
+                    .is_some_and(|shape| shape.contains_point(point))
+            })
+    }
+
+    // Compare the positions of two shapes or groups in painting order:
+    // Less means that the first is below the second. A group counts as
+    // below everything inside it. Returns None if either is not in the
+    // document.
+
+    pub fn compare_z_order(&self, a: &ShapeId, b: &ShapeId) -> Option<Ordering> {
+        Some(self.z_order_path(a)?.cmp(&self.z_order_path(b)?))
+    }
+
+    // The position of a shape or group in painting order as its z-order
+    // key within each enclosing group from the top level down. Comparing
+    // paths compares positions.
+
+    fn z_order_path(&self, shape_id: &ShapeId) -> Option<Vec<&Key>> {
+        let mut path = Vec::new();
+        let mut current_id = *shape_id;
+        loop {
+            let parent_id = self.parents.get(&current_id);
+            path.push(self.children_of(parent_id)?.key(&current_id)?);
+            match parent_id {
+                Some(parent_id) => current_id = *parent_id,
+                None => break,
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    // Sort shape and group ids into painting order, dropping any that are
+    // not in the document.
+
+    pub fn sorted_by_z_order(&self, shape_ids: impl IntoIterator<Item = ShapeId>) -> Vec<ShapeId> {
+        let mut keyed: Vec<(Vec<&Key>, ShapeId)> = shape_ids
+            .into_iter()
+            .filter_map(|shape_id| self.z_order_path(&shape_id).map(|path| (path, shape_id)))
+            .collect();
+        keyed.sort_unstable();
+        keyed.into_iter().map(|(_, shape_id)| shape_id).collect()
+    }
+
+    // The children of a group or layer or, for None, the sequence of
+    // layers.
+
+    fn children_of(&self, group_id: Option<&ShapeId>) -> Option<&ZOrder> {
+        match group_id {
+            Some(group_id) => self.groups.get(group_id),
+            None => Some(&self.sequence),
+        }
+    }
+
+    fn children_of_mut(&mut self, group_id: Option<&ShapeId>) -> Option<&mut ZOrder> {
+        match group_id {
+            Some(group_id) => self.groups.get_mut(group_id),
+            None => Some(&mut self.sequence),
+        }
+    }
+
+    // Take a shape or group out of whatever contains it.
+
+    fn detach(&mut self, shape_id: &ShapeId) {
+        let parent_id = self.parents.remove(shape_id);
+        if let Some(children) = self.children_of_mut(parent_id.as_ref()) {
+            children.remove(shape_id);
+        }
+    }
+
+    // Put a shape or group at the top of a group (or the top level).
+
+    fn attach(&mut self, shape_id: &ShapeId, parent_id: Option<ShapeId>) {
+        if let Some(children) = self.children_of_mut(parent_id.as_ref()) {
+            children.push_top(*shape_id);
+        }
+        if let Some(parent_id) = parent_id {
+            self.parents.insert(*shape_id, parent_id);
+        }
+    }
+
+    // Is the shape or group inside any of the groups?
+
+    fn is_inside_any(&self, shape_id: &ShapeId, group_ids: &HashSet<ShapeId>) -> bool {
+        let mut current_id = shape_id;
+        while let Some(parent_id) = self.parents.get(current_id) {
+            if group_ids.contains(parent_id) {
+                return true;
+            }
+            current_id = parent_id;
+        }
+        false
+    }
+
+    // The shapes near the bounds that pass a test, from bottom to top.
+    // Since painted bounds contain geometric bounds, the spatial index
+    // finds every shape whose geometric bounds intersect the bounds.
+
+    fn shape_ids_matching_in(
+        &self,
+        bounds: &Bounds,
+        test: impl Fn(&Shape) -> bool,
+    ) -> Vec<ShapeId> {
+        let matching = self
+            .spatial_index
+            .shape_ids_intersecting(bounds)
+            .filter(|shape_id| self.shapes.get(shape_id).is_some_and(&test));
+        self.sorted_by_z_order(matching)
+    }
+
     // Generate the next unused (for this document) shape id
 
     pub fn generate_shape_id(&mut self) -> ShapeId {
@@ -179,14 +680,32 @@ impl<'a> Document {
     }
 
     // Upsert a shape with an id into the document.
-    // If this is an insert, the shape is added at the top.
+    // If this is an insert, the shape is added at the top of the top layer.
 
     pub fn upsert_shape_with_id(&mut self, shape_id: &ShapeId, shape: Shape) {
-        // If the shape id is not listed in the sequence, we add it at the top.
-        if !self.sequence.contains(shape_id) {
-            self.sequence.push(*shape_id)
+        self.upsert_shape_with_id_in_layer(shape_id, shape, &self.top_layer_id());
+    }
+
+    // Upsert a shape with an id into the document. If this is an insert,
+    // the shape is added at the top of the layer or, if there is no such
+    // layer, the top layer. Existing shapes stay where they are.
+
+    pub fn upsert_shape_with_id_in_layer(
+        &mut self,
+        shape_id: &ShapeId,
+        shape: Shape,
+        layer_id: &ShapeId,
+    ) {
+        if !self.shapes.contains_key(shape_id) {
+            let layer_id = if self.layers.contains_key(layer_id) {
+                *layer_id
+            } else {
+                self.top_layer_id()
+            };
+            self.attach(shape_id, Some(layer_id));
         }
-        // Upsert into the shapes hash map.
+        // Upsert into the shapes hash map and the spatial index.
+        self.spatial_index.upsert(shape_id, &shape);
         self.shapes.insert(*shape_id, shape);
         // Make sure that next_shape_id is greater than all other
         // shape id's seen within the document.
@@ -197,28 +716,1521 @@ impl<'a> Document {
     // and the shape definitions. If there is no shape with this id, then
     // the operation is a no-op.
     pub fn delete_shape_with_id(&mut self, shape_id: &ShapeId) {
-        if let Some(idx) = self.sequence.iter().position(|&seq_id| seq_id == *shape_id) {
-            self.sequence.remove(idx);
+        if self.shapes.remove(shape_id).is_some() {
+            self.detach(shape_id);
+            self.spatial_index.remove(shape_id);
         }
+    }
+
+    // Delete shapes and groups along with everything inside the groups.
+    // Ids that are not in the document or that are layers are ignored.
+
+    pub fn delete_shape_ids(&mut self, shape_ids: &HashSet<ShapeId>) {
+        for shape_id in self.movable_shape_ids(shape_ids) {
+            for inner_id in self.descendant_ids_of(&shape_id) {
+                self.forget_shape_id(&inner_id);
+            }
+            self.detach(&shape_id);
+            self.forget_shape_id(&shape_id);
+        }
+    }
+
+    // Drop a shape or group from all of the bookkeeping without fixing up
+    // its parent. The caller takes care of that.
+
+    fn forget_shape_id(&mut self, shape_id: &ShapeId) {
         self.shapes.remove(shape_id);
+        self.groups.remove(shape_id);
+        self.parents.remove(shape_id);
+        self.spatial_index.remove(shape_id);
     }
 
     // If a shape with the given id exists, update its geometry with new geometry.
     // If there is no shape with this id, the operation is a no-op.
     pub fn set_geometry_for_shape_id(&mut self, shape_id: &ShapeId, new_geometry: Geometry) {
-        self.shapes
-            .entry(*shape_id)
-            .and_modify(|shape| shape.geometry = new_geometry);
+        if let Some(shape) = self.shapes.get_mut(shape_id) {
+            shape.geometry = new_geometry;
+            self.spatial_index.upsert(shape_id, shape);
+        }
     }
 
     // If there is a shape with the given id, pull it to the top of the shapes
-    // display sequence -- i.e., to the last position in the sequence.
+    // display sequence -- i.e., to the last position in the sequence. For
+    // shapes inside a group, this is the top of the group.
     pub fn move_shape_with_id_to_top(&mut self, shape_id: &ShapeId) {
-        if let Some(idx) = self.sequence.iter().position(|seq_id| *seq_id == *shape_id)
-            && idx != self.sequence.len() - 1
-        {
-            self.sequence.remove(idx);
-            self.sequence.push(*shape_id);
+        self.move_shape_ids_to_top(&HashSet::from([*shape_id]));
+    }
+
+    // Z-order changes for sets of shapes and groups. Each moves within
+    // its own group or layer, and layers move within the sequence of
+    // layers. Ids that are not in the document
+    // are ignored and the shapes that move keep their relative order.
+    // Moving forward or backward moves each shape one place in the
+    // sequence, past a shape that is not moving.
+
+    pub fn move_shape_ids_to_top(&mut self, shape_ids: &HashSet<ShapeId>) {
+        self.rearrange(shape_ids, ZOrder::move_to_top);
+    }
+
+    pub fn move_shape_ids_to_bottom(&mut self, shape_ids: &HashSet<ShapeId>) {
+        self.rearrange(shape_ids, ZOrder::move_to_bottom);
+    }
+
+    pub fn move_shape_ids_forward(&mut self, shape_ids: &HashSet<ShapeId>) {
+        self.rearrange(shape_ids, ZOrder::move_forward);
+    }
+
+    pub fn move_shape_ids_backward(&mut self, shape_ids: &HashSet<ShapeId>) {
+        self.rearrange(shape_ids, ZOrder::move_backward);
+    }
+
+    // Move the shapes so that they sit together starting at the index in
+    // their group or layer, where 0 is the bottom.
+    // Indices past the end move the shapes to the top. Finding the index
+    // takes time proportional to it, unlike the other moves.
+
+    pub fn move_shape_ids_to_index(&mut self, shape_ids: &HashSet<ShapeId>, index: usize) {
+        self.rearrange(shape_ids, |children, shape_ids| {
+            children.move_to_index(shape_ids, index)
+        });
+    }
+
+    // Apply a z-order change to each group or layer (or the sequence of
+    // layers) holding any of the shapes.
+
+    fn rearrange(
+        &mut self,
+        shape_ids: &HashSet<ShapeId>,
+        change: impl Fn(&mut ZOrder, &HashSet<ShapeId>),
+    ) {
+        let parent_ids: HashSet<Option<ShapeId>> = shape_ids
+            .iter()
+            .map(|shape_id| self.parent_of(shape_id))
+            .collect();
+        for parent_id in parent_ids {
+            if let Some(children) = self.children_of_mut(parent_id.as_ref()) {
+                change(children, shape_ids);
+            }
+        }
+    }
+
+    // Gather shapes and groups into a new group with the given id. The
+    // group takes the place of the topmost of them, and they keep their
+    // painting order within it. Ids that are not in the document, that are
+    // layers, or that are inside others being grouped are left alone. If
+    // there is nothing left to group, we do not make a group.
+
+    pub fn group_shape_ids_with_id(&mut self, group_id: &ShapeId, shape_ids: &HashSet<ShapeId>) {
+        let members = self.movable_shape_ids(shape_ids);
+        let Some(&topmost_id) = members.last() else {
+            return;
+        };
+        let parent_id = self.parent_of(&topmost_id);
+        for shape_id in &members {
+            if *shape_id != topmost_id {
+                self.detach(shape_id);
+            }
+        }
+        if let Some(children) = self.children_of_mut(parent_id.as_ref()) {
+            children.replace(&topmost_id, &[*group_id]);
+        }
+        for shape_id in &members {
+            self.parents.insert(*shape_id, *group_id);
+        }
+        if let Some(parent_id) = parent_id {
+            self.parents.insert(*group_id, parent_id);
+        }
+        self.groups
+            .insert(*group_id, ZOrder::new_from_sequence(&members));
+        self.shape_id_generator.advance_past(*group_id);
+    }
+
+    // Replace a group with its children, which take its place in its own
+    // group or layer. Returns the ids of the children from bottom to top,
+    // or nothing if the id is not a group.
+
+    pub fn ungroup_shape_id(&mut self, group_id: &ShapeId) -> Vec<ShapeId> {
+        if !self.is_group(group_id) {
+            return Vec::new();
+        }
+        let parent_id = self.parent_of(group_id);
+        let Some(children) = self.groups.remove(group_id) else {
+            return Vec::new();
+        };
+        let child_ids = children.to_vec();
+        if let Some(siblings) = self.children_of_mut(parent_id.as_ref()) {
+            siblings.replace(group_id, &child_ids);
+        }
+        self.parents.remove(group_id);
+        for child_id in &child_ids {
+            match parent_id {
+                Some(parent_id) => self.parents.insert(*child_id, parent_id),
+                None => self.parents.remove(child_id),
+            };
+        }
+        child_ids
+    }
+
+    // The shapes and groups that can move from a set, in painting order.
+    // Layers cannot move out of the sequence and anything inside another
+    // member of the set moves along with it.
+
+    fn movable_shape_ids(&self, shape_ids: &HashSet<ShapeId>) -> Vec<ShapeId> {
+        self.sorted_by_z_order(
+            shape_ids
+                .iter()
+                .filter(|shape_id| {
+                    !self.layers.contains_key(shape_id) && !self.is_inside_any(shape_id, shape_ids)
+                })
+                .copied(),
+        )
+    }
+
+    // Move shapes and groups to the top of a layer, keeping their painting
+    // order.
+
+    pub fn move_shape_ids_to_layer(&mut self, shape_ids: &HashSet<ShapeId>, layer_id: &ShapeId) {
+        if !self.layers.contains_key(layer_id) {
+            return;
+        }
+        for shape_id in self.movable_shape_ids(shape_ids) {
+            self.detach(&shape_id);
+            self.attach(&shape_id, Some(*layer_id));
+        }
+    }
+
+    // Align shapes and groups with the bounds enclosing all of them.
+    // Groups move as a whole and anything inside another member of the set
+    // moves along with it. Aligning fewer than two things does nothing.
+
+    pub fn align_shape_ids(&mut self, shape_ids: &HashSet<ShapeId>, alignment: Alignment) {
+        let members = self.bounded_shape_ids(shape_ids);
+        if members.len() < 2 {
+            return;
+        }
+        let Some(overall) = members
+            .iter()
+            .map(|(_, bounds)| bounds.clone())
+            .reduce(|acc, bounds| acc.union(&bounds))
+        else {
+            return;
+        };
+        for (shape_id, bounds) in &members {
+            let delta = match alignment {
+                Alignment::Left => XYPoint::new(overall.min.x - bounds.min.x, 0.0),
+                Alignment::CenterHorizontal => {
+                    XYPoint::new(overall.center().x - bounds.center().x, 0.0)
+                }
+                Alignment::Right => XYPoint::new(overall.max.x - bounds.max.x, 0.0),
+                Alignment::Top => XYPoint::new(0.0, overall.min.y - bounds.min.y),
+                Alignment::CenterVertical => {
+                    XYPoint::new(0.0, overall.center().y - bounds.center().y)
+                }
+                Alignment::Bottom => XYPoint::new(0.0, overall.max.y - bounds.max.y),
+            };
+            self.offset_shape_id_by(shape_id, &delta);
+        }
+    }
+
+    // Space shapes and groups out evenly along an axis. We order them by
+    // their centers and lay them out across the extent of the bounds
+    // enclosing all of them so that the gaps between neighbors are equal.
+    // If they are too wide to fit, the gaps are equal overlaps instead.
+    // Distributing fewer than three things does nothing.
+
+    pub fn distribute_shape_ids(
+        &mut self,
+        shape_ids: &HashSet<ShapeId>,
+        distribution: Distribution,
+    ) {
+        // Work with the spans along the axis.
+        let span = |bounds: &Bounds| match distribution {
+            Distribution::Horizontal => (bounds.min.x, bounds.max.x),
+            Distribution::Vertical => (bounds.min.y, bounds.max.y),
+        };
+        let mut members: Vec<(ShapeId, (f64, f64))> = self
+            .bounded_shape_ids(shape_ids)
+            .into_iter()
+            .map(|(shape_id, bounds)| (shape_id, span(&bounds)))
+            .collect();
+        if members.len() < 3 {
+            return;
+        }
+        members.sort_by(|(_, a), (_, b)| (a.0 + a.1).total_cmp(&(b.0 + b.1)));
+        let start = members
+            .iter()
+            .map(|(_, (min, _))| *min)
+            .fold(f64::INFINITY, f64::min);
+        let end = members
+            .iter()
+            .map(|(_, (_, max))| *max)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let total_length: f64 = members.iter().map(|(_, (min, max))| max - min).sum();
+        let gap = (end - start - total_length) / (members.len() - 1) as f64;
+        let mut position = start;
+        for (shape_id, (min, max)) in &members {
+            let distance = position - min;
+            let delta = match distribution {
+                Distribution::Horizontal => XYPoint::new(distance, 0.0),
+                Distribution::Vertical => XYPoint::new(0.0, distance),
+            };
+            self.offset_shape_id_by(shape_id, &delta);
+            position += max - min + gap;
+        }
+    }
+
+    // The shapes and groups that can move from a set along with their
+    // bounds, leaving out empty groups.
+
+    fn bounded_shape_ids(&self, shape_ids: &HashSet<ShapeId>) -> Vec<(ShapeId, Bounds)> {
+        self.movable_shape_ids(shape_ids)
+            .into_iter()
+            .filter_map(|shape_id| {
+                self.bounds_for_id(&shape_id)
+                    .map(|bounds| (shape_id, bounds))
+            })
+            .collect()
+    }
+
+    // Move shapes and groups in document space. Anything inside another
+    // member of the set moves along with it rather than twice.
+
+    pub fn offset_shape_ids_by(&mut self, shape_ids: &HashSet<ShapeId>, delta: &XYPoint) {
+        for shape_id in self.movable_shape_ids(shape_ids) {
+            self.offset_shape_id_by(&shape_id, delta);
+        }
+    }
+
+    // Move a shape, or all of the shapes in a group, in document space.
+
+    fn offset_shape_id_by(&mut self, shape_id: &ShapeId, delta: &XYPoint) {
+        if delta.x == 0.0 && delta.y == 0.0 {
+            return;
+        }
+        for shape_id in self.shape_ids_in(shape_id) {
+            if let Some(shape) = self.shapes.get(&shape_id) {
+                let shape = shape.offset_by(delta);
+                self.spatial_index.upsert(&shape_id, &shape);
+                self.shapes.insert(shape_id, shape);
+            }
+        }
+    }
+
+    // Insert or update a layer. New layers go on top and start out empty.
+    // Ids that already belong to shapes or groups are left alone, since
+    // making them layers would cut them loose from the tree.
+
+    pub fn upsert_layer_with_id(&mut self, layer_id: &ShapeId, layer: Layer) {
+        if !self.layers.contains_key(layer_id) {
+            if self.shapes.contains_key(layer_id)
+                || self.groups.contains_key(layer_id)
+                || self.parents.contains_key(layer_id)
+            {
+                return;
+            }
+            self.groups.insert(*layer_id, ZOrder::new_empty());
+            self.sequence.push_top(*layer_id);
+            self.shape_id_generator.advance_past(*layer_id);
+        }
+        self.layers.insert(*layer_id, layer);
+    }
+
+    // Delete a layer along with everything in it. We never delete the last
+    // layer.
+
+    pub fn delete_layer_with_id(&mut self, layer_id: &ShapeId) {
+        if !self.layers.contains_key(layer_id) || self.layers.len() == 1 {
+            return;
+        }
+        for shape_id in self.descendant_ids_of(layer_id) {
+            self.forget_shape_id(&shape_id);
+        }
+        self.groups.remove(layer_id);
+        self.layers.remove(layer_id);
+        self.sequence.remove(layer_id);
+    }
+
+    // Record the current state of a shape in a partial snapshot. If the
+    // snapshot already records this shape, we keep the earlier state.
+    pub fn record_shape_in_snapshot(&self, snapshot: &mut PartialSnapshot, shape_id: &ShapeId) {
+        snapshot
+            .shapes
+            .entry(*shape_id)
+            .or_insert_with(|| self.shapes.get(shape_id).cloned());
+    }
+
+    // Record whether a group or layer exists in a partial snapshot. Its
+    // children are recorded by their placements. If the snapshot already
+    // records this group, we keep the earlier state.
+    pub fn record_group_in_snapshot(&self, snapshot: &mut PartialSnapshot, group_id: &ShapeId) {
+        snapshot
+            .groups
+            .entry(*group_id)
+            .or_insert_with(|| self.groups.contains_key(group_id));
+    }
+
+    // Record where a shape, group, or layer currently sits in the z-order
+    // -- or None if it is not in the document -- in a partial snapshot. If
+    // the snapshot already records this placement, we keep the earlier
+    // state.
+    pub fn record_placement_in_snapshot(&self, snapshot: &mut PartialSnapshot, shape_id: &ShapeId) {
+        snapshot
+            .placements
+            .entry(*shape_id)
+            .or_insert_with(|| self.placement_of(shape_id));
+    }
+
+    fn placement_of(&self, shape_id: &ShapeId) -> Option<Placement> {
+        let parent_id = self.parent_of(shape_id);
+        let key = self.children_of(parent_id.as_ref())?.key(shape_id)?;
+        Some(Placement {
+            parent_id,
+            key: key.clone(),
+        })
+    }
+
+    // Record the current name and flags for a layer -- or None if there is
+    // no such layer -- in a partial snapshot. If the snapshot already
+    // records this layer, we keep the earlier state.
+    pub fn record_layer_in_snapshot(&self, snapshot: &mut PartialSnapshot, layer_id: &ShapeId) {
+        snapshot
+            .layers
+            .entry(*layer_id)
+            .or_insert_with(|| self.layers.get(layer_id).cloned());
+    }
+
+    // Take a snapshot of the current state of everything recorded in
+    // another snapshot. This gives us the "after" to go with a "before".
+    pub fn snapshot_matching(&self, other: &PartialSnapshot) -> PartialSnapshot {
+        let mut snapshot = PartialSnapshot::new_empty();
+        for shape_id in other.shapes.keys() {
+            self.record_shape_in_snapshot(&mut snapshot, shape_id);
+        }
+        for group_id in other.groups.keys() {
+            self.record_group_in_snapshot(&mut snapshot, group_id);
+        }
+        for shape_id in other.placements.keys() {
+            self.record_placement_in_snapshot(&mut snapshot, shape_id);
+        }
+        for layer_id in other.layers.keys() {
+            self.record_layer_in_snapshot(&mut snapshot, layer_id);
+        }
+        snapshot
+    }
+
+    // Return the parts of the document recorded in the snapshot to their
+    // recorded state. A snapshot that records a change in which shapes
+    // exist or where they sit must also record the placements of every
+    // shape, group, and layer that moved and every group that came or went
+    // so that everything stays consistent.
+    pub fn restore_snapshot(&mut self, snapshot: &PartialSnapshot) {
+        // Take everything that moved out of where it is now before putting
+        // anything back. Doing all of the former first handles shapes
+        // trading places and groups coming and going.
+        for shape_id in snapshot.placements.keys() {
+            self.detach(shape_id);
+        }
+        for (group_id, exists) in &snapshot.groups {
+            if *exists {
+                self.groups
+                    .entry(*group_id)
+                    .or_insert_with(ZOrder::new_empty);
+                self.shape_id_generator.advance_past(*group_id);
+            } else {
+                self.groups.remove(group_id);
+            }
+        }
+        for (shape_id, opt_placement) in &snapshot.placements {
+            if let Some(Placement { parent_id, key }) = opt_placement
+                && let Some(children) = self.children_of_mut(parent_id.as_ref())
+            {
+                children.insert_with_key(*shape_id, key.clone());
+                if let Some(parent_id) = parent_id {
+                    self.parents.insert(*shape_id, *parent_id);
+                }
+            }
+        }
+        for (layer_id, opt_layer) in &snapshot.layers {
+            match opt_layer {
+                Some(layer) => self.layers.insert(*layer_id, layer.clone()),
+                None => self.layers.remove(layer_id),
+            };
+        }
+        for (shape_id, opt_shape) in &snapshot.shapes {
+            match opt_shape {
+                Some(shape) => {
+                    self.spatial_index.upsert(shape_id, shape);
+                    self.shapes.insert(*shape_id, shape.clone());
+                    self.shape_id_generator.advance_past(*shape_id);
+                }
+                None => {
+                    self.spatial_index.remove(shape_id);
+                    self.shapes.remove(shape_id);
+                }
+            }
+        }
+    }
+}
+
+// JSON persistence (requires the "serde" feature). The format stores the
+// shapes keyed by shape id, the children of each group and layer keyed by
+// its id, the names and flags of the layers keyed by layer id, the display
+// sequence of layers, and the shape id generator so that ids are not
+// reused after a round trip. The format is versioned so that we can evolve
+// it; we check the version before trying to interpret anything else.
+//
+// Version history:
+//   1: Initial format.
+//   2: Colors are hex strings rather than names. Version 1 documents can
+//      still be read since color parsing accepts the old names.
+//   3: Styles have an optional stroke and the fill may be null. Earlier
+//      versions read as filled shapes without strokes.
+//   4: Shapes have an optional transform. Earlier versions read as
+//      untransformed shapes.
+//   5: Documents have optional groups. Earlier versions read as documents
+//      without groups.
+//   6: Documents have layers and the sequence lists layers. Earlier
+//      versions read as documents with everything in a single layer.
+
+#[cfg(feature = "serde")]
+const JSON_FORMAT_VERSION: u32 = 6;
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct JsonVersion {
+    version: u32,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonDocumentOut<'a> {
+    version: u32,
+    shapes: std::collections::BTreeMap<ShapeId, &'a Shape>,
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    groups: std::collections::BTreeMap<ShapeId, Vec<ShapeId>>,
+    layers: std::collections::BTreeMap<ShapeId, &'a Layer>,
+    sequence: Vec<ShapeId>,
+    shape_id_generator: &'a ShapeIdGenerator,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct JsonDocumentIn {
+    shapes: HashMap<ShapeId, Shape>,
+    #[serde(default)]
+    groups: HashMap<ShapeId, Vec<ShapeId>>,
+    #[serde(default)]
+    layers: HashMap<ShapeId, Layer>,
+    sequence: Vec<ShapeId>,
+    shape_id_generator: ShapeIdGenerator,
+}
+
+#[cfg(feature = "serde")]
+impl Document {
+    // Write the document out as JSON.
+    pub fn to_json(&self) -> String {
+        let out = JsonDocumentOut {
+            version: JSON_FORMAT_VERSION,
+            shapes: self
+                .shapes
+                .iter()
+                .map(|(shape_id, shape)| (*shape_id, shape))
+                .collect(),
+            groups: self
+                .groups
+                .iter()
+                .map(|(group_id, children)| (*group_id, children.to_vec()))
+                .collect(),
+            layers: self
+                .layers
+                .iter()
+                .map(|(layer_id, layer)| (*layer_id, layer))
+                .collect(),
+            sequence: self.sequence.to_vec(),
+            shape_id_generator: &self.shape_id_generator,
+        };
+        serde_json::to_string_pretty(&out).expect("documents always serialize")
+    }
+
+    // Read a document from JSON. Every shape and group must appear exactly
+    // once, either in the sequence or in a group reachable from the
+    // sequence, and every entry in the sequence or a group must have a
+    // shape or group. The sequence must list exactly the layers, unless
+    // there are no layers, in which case everything goes into a new one.
+    pub fn from_json(json: &str) -> Result<Self, DocError> {
+        let to_doc_error = |err: serde_json::Error| DocError::MalformedJson(err.to_string());
+        let JsonVersion { version } = serde_json::from_str(json).map_err(to_doc_error)?;
+        if !(1..=JSON_FORMAT_VERSION).contains(&version) {
+            return Err(DocError::UnsupportedVersion(version));
+        }
+        let JsonDocumentIn {
+            mut shapes,
+            mut groups,
+            layers,
+            sequence,
+            shape_id_generator,
+        } = serde_json::from_str(json).map_err(to_doc_error)?;
+        let mut doc = Self::new_without_layers();
+        let top_level = doc.adopt_json_children(None, sequence, &mut shapes, &mut groups)?;
+        if let Some(shape_id) = shapes.keys().chain(groups.keys()).min() {
+            return Err(DocError::UnsequencedShape(*shape_id));
+        }
+        if doc.shape_id_generator.next_id < shape_id_generator.next_id {
+            doc.shape_id_generator = shape_id_generator;
+        }
+        if layers.is_empty() {
+            doc.add_layer_with_children(Layer::new(DEFAULT_LAYER_NAME), top_level);
+        } else {
+            if let Some(shape_id) = top_level
+                .iter()
+                .filter(|shape_id| {
+                    !layers.contains_key(shape_id) || !doc.groups.contains_key(shape_id)
+                })
+                .min()
+            {
+                return Err(DocError::NotALayer(*shape_id));
+            }
+            if let Some(layer_id) = layers
+                .keys()
+                .filter(|layer_id| !top_level.contains(layer_id))
+                .min()
+            {
+                return Err(if doc.groups.contains_key(layer_id) {
+                    DocError::NestedLayer(*layer_id)
+                } else {
+                    DocError::MissingShape(*layer_id)
+                });
+            }
+            doc.sequence = top_level;
+            doc.layers = layers;
+        }
+        doc.spatial_index = SpatialIndex::new_from_shapes(doc.shapes.iter());
+        Ok(doc)
+    }
+
+    // Move the shapes and groups for some children from what we read into
+    // the document, recursing into groups. Since we remove what we move,
+    // anything listed twice is either a duplicate or, if it is a group
+    // that we are still filling, part of a cycle.
+    fn adopt_json_children(
+        &mut self,
+        parent_id: Option<ShapeId>,
+        child_ids: Vec<ShapeId>,
+        shapes: &mut HashMap<ShapeId, Shape>,
+        groups: &mut HashMap<ShapeId, Vec<ShapeId>>,
+    ) -> Result<ZOrder, DocError> {
+        let mut children = ZOrder::new_empty();
+        for child_id in child_ids {
+            if let Some(parent_id) = parent_id {
+                self.parents.insert(child_id, parent_id);
+            }
+            if let Some(shape) = shapes.remove(&child_id) {
+                self.shapes.insert(child_id, shape);
+            } else if let Some(grandchild_ids) = groups.remove(&child_id) {
+                let grandchildren =
+                    self.adopt_json_children(Some(child_id), grandchild_ids, shapes, groups)?;
+                self.groups.insert(child_id, grandchildren);
+            } else if self.shapes.contains_key(&child_id) || self.groups.contains_key(&child_id) {
+                return Err(DocError::DuplicateShapeId(child_id));
+            } else if self.is_json_ancestor(&child_id, parent_id) {
+                return Err(DocError::GroupCycle(child_id));
+            } else {
+                return Err(DocError::MissingShape(child_id));
+            }
+            children.push_top(child_id);
+            self.shape_id_generator.advance_past(child_id);
+        }
+        Ok(children)
+    }
+
+    // Is a group one of the groups that we are still filling above some
+    // children? We link children to their parents before recursing, so
+    // these are the parents of the parents.
+    fn is_json_ancestor(&self, group_id: &ShapeId, parent_id: Option<ShapeId>) -> bool {
+        let mut ancestor_id = parent_id;
+        while let Some(shape_id) = ancestor_id {
+            if shape_id == *group_id {
+                return true;
+            }
+            ancestor_id = self.parents.get(&shape_id).copied();
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::history::History;
+
+    fn shape_id(id: usize) -> ShapeId {
+        ShapeId { id }
+    }
+
+    fn square(left: f64, top: f64) -> Shape {
+        Shape::new(
+            Geometry::rectangle(left, top, 10.0, 10.0),
+            Style::new(Color::BLACK),
+        )
+    }
+
+    #[test]
+    fn duplicate_pairs_are_rejected() {
+        let pairs = vec![
+            (shape_id(1), square(0.0, 0.0)),
+            (shape_id(1), square(20.0, 0.0)),
+        ];
+        assert!(matches!(
+            Document::new_from_pairs(pairs),
+            Err(DocError::DuplicateShapeId(id)) if id == shape_id(1)
+        ));
+    }
+
+    fn ids(ids: &[usize]) -> HashSet<ShapeId> {
+        ids.iter().map(|id| shape_id(*id)).collect()
+    }
+
+    #[test]
+    fn topmost_shape_is_the_highest_in_z_order() {
+        // Three squares all covering (15, 15), in order from bottom to top.
+        let mut doc = Document::new_from_shapes(&vec![
+            square(10.0, 10.0),
+            square(12.0, 12.0),
+            square(14.0, 14.0),
+        ]);
+        let center = XYPoint::new(15.0, 15.0);
+        assert_eq!(doc.topmost_shape_at(&center), Some(shape_id(3)));
+        assert_eq!(
+            doc.topmost_shape_at(&XYPoint::new(11.0, 11.0)),
+            Some(shape_id(1))
+        );
+        assert_eq!(doc.topmost_shape_at(&XYPoint::new(50.0, 50.0)), None);
+
+        doc.move_shape_ids_to_top(&ids(&[1]));
+        assert_eq!(doc.topmost_shape_at(&center), Some(shape_id(1)));
+        doc.move_shape_ids_to_bottom(&ids(&[1]));
+        doc.move_shape_ids_backward(&ids(&[3]));
+        assert_eq!(doc.topmost_shape_at(&center), Some(shape_id(2)));
+    }
+
+    #[test]
+    fn topmost_shape_breaks_ties_across_groups_and_layers() {
+        let mut doc = Document::new_from_shapes(&vec![
+            square(10.0, 10.0),
+            square(12.0, 12.0),
+            square(14.0, 14.0),
+        ]);
+        let center = XYPoint::new(15.0, 15.0);
+        // Grouping the bottom two puts them where the lower one was, so the
+        // top shape stays on top. Raising the group raises both, and the
+        // upper of the two is on top within it.
+        let group_id = doc.generate_shape_id();
+        doc.group_shape_ids_with_id(&group_id, &ids(&[1, 2]));
+        assert_eq!(doc.topmost_shape_at(&center), Some(shape_id(3)));
+        doc.move_shape_ids_to_top(&ids(&[group_id.id]));
+        assert_eq!(doc.topmost_shape_at(&center), Some(shape_id(2)));
+
+        // Everything in a higher layer is above everything in a lower one
+        // whatever its position within its layer.
+        let layer_id = doc.generate_shape_id();
+        doc.upsert_layer_with_id(&layer_id, Layer::new("Top"));
+        let top_shape_id = doc.generate_shape_id();
+        doc.upsert_shape_with_id_in_layer(&top_shape_id, square(14.0, 14.0), &layer_id);
+        doc.move_shape_ids_to_top(&ids(&[3]));
+        assert_eq!(doc.topmost_shape_at(&center), Some(top_shape_id));
+    }
+
+    // Four squares in a row, bottom to top, in a single layer.
+    fn four_squares() -> Document {
+        Document::new_from_shapes(&vec![
+            square(0.0, 0.0),
+            square(20.0, 0.0),
+            square(40.0, 0.0),
+            square(60.0, 0.0),
+        ])
+    }
+
+    fn child_ids(doc: &Document, group_id: &ShapeId) -> Vec<ShapeId> {
+        doc.group_child_ids_iter(group_id).copied().collect()
+    }
+
+    fn id_list(ids: &[usize]) -> Vec<ShapeId> {
+        ids.iter().map(|id| shape_id(*id)).collect()
+    }
+
+    // Group the way the app does, announcing everything that changes.
+    fn group_with_history(
+        doc: &mut Document,
+        history: &mut History,
+        shape_ids: &HashSet<ShapeId>,
+    ) -> ShapeId {
+        history.begin_transaction();
+        let group_id = doc.generate_shape_id();
+        for shape_id in shape_ids {
+            history.will_change_placement(doc, shape_id);
+        }
+        history.will_change_group(doc, &group_id);
+        history.will_change_placement(doc, &group_id);
+        doc.group_shape_ids_with_id(&group_id, shape_ids);
+        history.commit_transaction(doc);
+        group_id
+    }
+
+    #[test]
+    fn grouping_can_be_undone_and_redone() {
+        let mut doc = four_squares();
+        let layer_id = doc.top_layer_id();
+        let mut history = History::new_empty();
+        let group_id = group_with_history(&mut doc, &mut history, &ids(&[2, 4]));
+
+        let check_grouped = |doc: &Document| {
+            assert_eq!(
+                child_ids(doc, &layer_id),
+                vec![shape_id(1), shape_id(3), group_id]
+            );
+            assert_eq!(child_ids(doc, &group_id), id_list(&[2, 4]));
+            assert_eq!(doc.parent_of(&shape_id(2)), Some(group_id));
+            assert_eq!(doc.parent_of(&group_id), Some(layer_id));
+        };
+        check_grouped(&doc);
+
+        assert!(history.undo(&mut doc));
+        assert_eq!(child_ids(&doc, &layer_id), id_list(&[1, 2, 3, 4]));
+        assert!(!doc.is_group(&group_id));
+        assert_eq!(doc.parent_of(&shape_id(2)), Some(layer_id));
+        assert_eq!(doc.parent_of(&group_id), None);
+
+        assert!(history.redo(&mut doc));
+        check_grouped(&doc);
+        // The group id stays taken after the redo.
+        assert!(doc.generate_shape_id().id > group_id.id);
+    }
+
+    #[test]
+    fn abandoning_a_transaction_restores_the_document() {
+        let mut doc = four_squares();
+        let layer_id = doc.top_layer_id();
+        let key_path = |doc: &Document, shape_id: &ShapeId| -> Option<Vec<Key>> {
+            doc.z_order_path(shape_id)
+                .map(|path| path.into_iter().cloned().collect())
+        };
+        let original_key_path = key_path(&doc, &shape_id(1));
+        let mut history = History::new_empty();
+
+        // Add a shape, then move another both on the canvas and in the
+        // z-order, as a cancelled drag might.
+        history.begin_transaction();
+        let new_id = doc.generate_shape_id();
+        history.will_change_shape(&doc, &new_id);
+        history.will_change_placement(&doc, &new_id);
+        doc.upsert_shape_with_id(&new_id, square(100.0, 100.0));
+        history.will_change_shape(&doc, &shape_id(1));
+        history.will_change_placement(&doc, &shape_id(1));
+        doc.upsert_shape_with_id(&shape_id(1), square(50.0, 50.0));
+        doc.move_shape_ids_to_top(&ids(&[1]));
+        assert!(key_path(&doc, &shape_id(1)) != original_key_path);
+
+        history.abandon_transaction(&mut doc);
+        assert!(doc.get_shape_by_id(&new_id).is_none());
+        assert_eq!(doc.topmost_shape_at(&XYPoint::new(105.0, 105.0)), None);
+        assert!(doc.get_shape_by_id(&shape_id(1)).unwrap().geometry == square(0.0, 0.0).geometry);
+        assert_eq!(
+            doc.topmost_shape_at(&XYPoint::new(5.0, 5.0)),
+            Some(shape_id(1))
+        );
+        assert_eq!(key_path(&doc, &shape_id(1)), original_key_path);
+        assert_eq!(child_ids(&doc, &layer_id), id_list(&[1, 2, 3, 4]));
+        assert!(!history.is_recording());
+        assert!(!history.can_undo());
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn ungrouping_can_be_undone_and_redone() {
+        let mut doc = four_squares();
+        let layer_id = doc.top_layer_id();
+        let group_id = doc.generate_shape_id();
+        doc.group_shape_ids_with_id(&group_id, &ids(&[2, 3]));
+        let mut history = History::new_empty();
+        history.begin_transaction();
+        history.will_change_group(&doc, &group_id);
+        history.will_change_placement(&doc, &group_id);
+        for child_id in doc.group_child_ids_iter(&group_id) {
+            history.will_change_placement(&doc, child_id);
+        }
+        assert_eq!(doc.ungroup_shape_id(&group_id), id_list(&[2, 3]));
+        history.commit_transaction(&doc);
+
+        let check_ungrouped = |doc: &Document| {
+            assert_eq!(child_ids(doc, &layer_id), id_list(&[1, 2, 3, 4]));
+            assert!(!doc.is_group(&group_id));
+            assert_eq!(doc.parent_of(&shape_id(3)), Some(layer_id));
+        };
+        check_ungrouped(&doc);
+
+        assert!(history.undo(&mut doc));
+        assert_eq!(
+            child_ids(&doc, &layer_id),
+            vec![shape_id(1), group_id, shape_id(4)]
+        );
+        assert_eq!(child_ids(&doc, &group_id), id_list(&[2, 3]));
+        assert_eq!(doc.parent_of(&shape_id(3)), Some(group_id));
+
+        assert!(history.redo(&mut doc));
+        check_ungrouped(&doc);
+    }
+
+    #[test]
+    fn nested_groups_move_and_ungroup_as_units() {
+        let mut doc = four_squares();
+        let layer_id = doc.top_layer_id();
+        let mut history = History::new_empty();
+        let inner_id = group_with_history(&mut doc, &mut history, &ids(&[1, 2]));
+        // Grouping a shape along with a group containing it groups just the
+        // group.
+        let outer_id = group_with_history(
+            &mut doc,
+            &mut history,
+            &HashSet::from([inner_id, shape_id(1), shape_id(3)]),
+        );
+        assert_eq!(child_ids(&doc, &layer_id), vec![outer_id, shape_id(4)]);
+        assert_eq!(child_ids(&doc, &outer_id), vec![inner_id, shape_id(3)]);
+        assert_eq!(child_ids(&doc, &inner_id), id_list(&[1, 2]));
+        assert_eq!(doc.top_level_id_for(&shape_id(1)), outer_id);
+        assert_eq!(doc.shape_ids_in(&outer_id), id_list(&[1, 2, 3]));
+        assert_eq!(
+            doc.descendant_ids_of(&outer_id),
+            vec![inner_id, shape_id(1), shape_id(2), shape_id(3)]
+        );
+
+        assert!(history.undo(&mut doc));
+        assert!(!doc.is_group(&outer_id));
+        assert_eq!(
+            child_ids(&doc, &layer_id),
+            vec![inner_id, shape_id(3), shape_id(4)]
+        );
+        assert!(history.undo(&mut doc));
+        assert_eq!(child_ids(&doc, &layer_id), id_list(&[1, 2, 3, 4]));
+        assert!(history.redo(&mut doc));
+        assert!(history.redo(&mut doc));
+        assert_eq!(child_ids(&doc, &outer_id), vec![inner_id, shape_id(3)]);
+        assert_eq!(child_ids(&doc, &inner_id), id_list(&[1, 2]));
+
+        doc.move_shape_ids_to_top(&HashSet::from([outer_id]));
+        assert_eq!(child_ids(&doc, &layer_id), vec![shape_id(4), outer_id]);
+        assert_eq!(doc.shape_ids_in(&layer_id), id_list(&[4, 1, 2, 3]));
+        assert_eq!(doc.ungroup_shape_id(&outer_id), vec![inner_id, shape_id(3)]);
+        assert_eq!(
+            child_ids(&doc, &layer_id),
+            vec![shape_id(4), inner_id, shape_id(3)]
+        );
+        assert_eq!(doc.parent_of(&shape_id(1)), Some(inner_id));
+    }
+
+    #[test]
+    fn spatial_index_follows_undo_and_redo() {
+        let mut doc = Document::new_from_shapes(&vec![square(0.0, 0.0), square(20.0, 0.0)]);
+        let mut history = History::new_empty();
+        let near = Bounds::new(XYPoint::new(0.0, 0.0), XYPoint::new(50.0, 50.0));
+        let far = Bounds::new(XYPoint::new(90.0, 90.0), XYPoint::new(150.0, 150.0));
+        let intersecting = |doc: &Document, bounds: &Bounds| -> HashSet<ShapeId> {
+            doc.shape_ids_intersecting(bounds).into_iter().collect()
+        };
+        let (first, second) = (XYPoint::new(5.0, 5.0), XYPoint::new(25.0, 5.0));
+        let moved = XYPoint::new(105.0, 105.0);
+
+        history.begin_transaction();
+        history.will_change_shape(&doc, &shape_id(1));
+        doc.upsert_shape_with_id(&shape_id(1), square(100.0, 100.0));
+        history.commit_transaction(&doc);
+        history.begin_transaction();
+        history.will_change_shape(&doc, &shape_id(2));
+        history.will_change_placement(&doc, &shape_id(2));
+        doc.delete_shape_with_id(&shape_id(2));
+        history.commit_transaction(&doc);
+
+        let check_edited = |doc: &Document| {
+            assert_eq!(doc.topmost_shape_at(&first), None);
+            assert_eq!(doc.topmost_shape_at(&second), None);
+            assert_eq!(doc.topmost_shape_at(&moved), Some(shape_id(1)));
+            assert_eq!(intersecting(doc, &near), ids(&[]));
+            assert_eq!(intersecting(doc, &far), ids(&[1]));
+        };
+        check_edited(&doc);
+
+        assert!(history.undo(&mut doc));
+        assert_eq!(doc.topmost_shape_at(&second), Some(shape_id(2)));
+        assert_eq!(intersecting(&doc, &near), ids(&[2]));
+        assert!(history.undo(&mut doc));
+        assert_eq!(doc.topmost_shape_at(&first), Some(shape_id(1)));
+        assert_eq!(doc.topmost_shape_at(&moved), None);
+        assert_eq!(intersecting(&doc, &near), ids(&[1, 2]));
+        assert_eq!(intersecting(&doc, &far), ids(&[]));
+
+        assert!(history.redo(&mut doc));
+        assert!(history.redo(&mut doc));
+        check_edited(&doc);
+    }
+
+    #[test]
+    fn layers_do_not_take_over_shape_or_group_ids() {
+        let mut doc = four_squares();
+        let layer_id = doc.top_layer_id();
+        let group_id = doc.generate_shape_id();
+        doc.group_shape_ids_with_id(&group_id, &ids(&[1, 2]));
+        for shape_id in [group_id, shape_id(3)] {
+            doc.upsert_layer_with_id(&shape_id, Layer::new("Taken"));
+            assert_eq!(doc.get_layer_by_id(&shape_id), None);
+        }
+        assert_eq!(
+            doc.layer_ids_iter().copied().collect::<Vec<_>>(),
+            [layer_id]
+        );
+        assert_eq!(child_ids(&doc, &group_id), id_list(&[1, 2]));
+        assert_eq!(doc.parent_of(&group_id), Some(layer_id));
+        assert_eq!(doc.shape_ids_in(&layer_id), id_list(&[1, 2, 3, 4]));
+
+        // Existing layers can still be updated, and new ids still work.
+        let mut hidden = Layer::new("Hidden");
+        hidden.visible = false;
+        doc.upsert_layer_with_id(&layer_id, hidden.clone());
+        assert_eq!(doc.get_layer_by_id(&layer_id), Some(&hidden));
+        let new_layer_id = doc.generate_shape_id();
+        doc.upsert_layer_with_id(&new_layer_id, Layer::new("New"));
+        assert_eq!(doc.top_layer_id(), new_layer_id);
+    }
+
+    #[test]
+    fn z_order_paths_compare_through_groups_and_layers() {
+        let mut doc = four_squares();
+        let group_id = doc.generate_shape_id();
+        doc.group_shape_ids_with_id(&group_id, &ids(&[1, 2]));
+        doc.move_shape_ids_to_top(&ids(&[1]));
+        let everything = || [4, 1, 3, 2].into_iter().map(shape_id).chain([group_id]);
+        // A group comes before everything inside it, and the shapes inside
+        // it sort by their order within it.
+        assert_eq!(
+            doc.sorted_by_z_order(everything()),
+            vec![group_id, shape_id(2), shape_id(1), shape_id(3), shape_id(4)]
+        );
+        assert_eq!(
+            doc.compare_z_order(&shape_id(1), &shape_id(3)),
+            Some(Ordering::Less)
+        );
+
+        doc.move_shape_ids_to_top(&HashSet::from([group_id]));
+        assert_eq!(
+            doc.sorted_by_z_order(everything()),
+            vec![shape_id(3), shape_id(4), group_id, shape_id(2), shape_id(1)]
+        );
+
+        // A higher layer sorts above everything in a lower one, and ids not
+        // in the document are dropped.
+        let layer_id = doc.generate_shape_id();
+        doc.upsert_layer_with_id(&layer_id, Layer::new("Top"));
+        let top_shape_id = doc.generate_shape_id();
+        doc.upsert_shape_with_id_in_layer(&top_shape_id, square(0.0, 0.0), &layer_id);
+        doc.move_shape_ids_to_top(&ids(&[3]));
+        assert_eq!(
+            doc.sorted_by_z_order([top_shape_id, shape_id(3), shape_id(99), shape_id(1)]),
+            vec![shape_id(1), shape_id(3), top_shape_id]
+        );
+        assert_eq!(
+            doc.compare_z_order(&top_shape_id, &layer_id),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(doc.compare_z_order(&top_shape_id, &shape_id(99)), None);
+    }
+
+    fn rectangle(left: f64, top: f64, width: f64, height: f64) -> Shape {
+        Shape::new(
+            Geometry::rectangle(left, top, width, height),
+            Style::new(Color::BLACK),
+        )
+    }
+
+    // The bounds of a shape or group as (left, top, right, bottom).
+    fn edges(doc: &Document, shape_id: &ShapeId) -> (f64, f64, f64, f64) {
+        let bounds = doc.bounds_for_id(shape_id).unwrap();
+        (bounds.min.x, bounds.min.y, bounds.max.x, bounds.max.y)
+    }
+
+    fn all_edges(doc: &Document) -> Vec<(f64, f64, f64, f64)> {
+        doc.shape_id_shapes_iter()
+            .map(|(shape_id, _)| edges(doc, &shape_id))
+            .collect()
+    }
+
+    #[test]
+    fn align_moves_shapes_to_the_shared_edge_or_center() {
+        // Together the rectangles span 0 to 80 across and 0 to 60 down.
+        let doc = Document::new_from_shapes(&vec![
+            rectangle(0.0, 0.0, 10.0, 10.0),
+            rectangle(30.0, 20.0, 20.0, 40.0),
+            rectangle(70.0, 5.0, 10.0, 20.0),
+        ]);
+        let cases = [
+            (
+                Alignment::Left,
+                [
+                    (0.0, 0.0, 10.0, 10.0),
+                    (0.0, 20.0, 20.0, 60.0),
+                    (0.0, 5.0, 10.0, 25.0),
+                ],
+            ),
+            (
+                Alignment::CenterHorizontal,
+                [
+                    (35.0, 0.0, 45.0, 10.0),
+                    (30.0, 20.0, 50.0, 60.0),
+                    (35.0, 5.0, 45.0, 25.0),
+                ],
+            ),
+            (
+                Alignment::Right,
+                [
+                    (70.0, 0.0, 80.0, 10.0),
+                    (60.0, 20.0, 80.0, 60.0),
+                    (70.0, 5.0, 80.0, 25.0),
+                ],
+            ),
+            (
+                Alignment::Top,
+                [
+                    (0.0, 0.0, 10.0, 10.0),
+                    (30.0, 0.0, 50.0, 40.0),
+                    (70.0, 0.0, 80.0, 20.0),
+                ],
+            ),
+            (
+                Alignment::CenterVertical,
+                [
+                    (0.0, 25.0, 10.0, 35.0),
+                    (30.0, 10.0, 50.0, 50.0),
+                    (70.0, 20.0, 80.0, 40.0),
+                ],
+            ),
+            (
+                Alignment::Bottom,
+                [
+                    (0.0, 50.0, 10.0, 60.0),
+                    (30.0, 20.0, 50.0, 60.0),
+                    (70.0, 40.0, 80.0, 60.0),
+                ],
+            ),
+        ];
+        for (alignment, expected) in cases {
+            let mut doc = doc.clone();
+            doc.align_shape_ids(&ids(&[1, 2, 3]), alignment);
+            assert_eq!(all_edges(&doc), expected, "{alignment:?}");
+        }
+
+        // A single shape has nothing to align with.
+        let mut aligned = doc.clone();
+        aligned.align_shape_ids(&ids(&[2]), Alignment::Left);
+        assert_eq!(all_edges(&aligned), all_edges(&doc));
+    }
+
+    #[test]
+    fn distribute_evens_out_the_gaps_between_shapes() {
+        // Shapes are ordered by their centers, and the outermost ones stay
+        // put.
+        let mut doc = Document::new_from_shapes(&vec![
+            rectangle(100.0, 0.0, 10.0, 10.0),
+            rectangle(0.0, 2.0, 20.0, 10.0),
+            rectangle(30.0, 50.0, 10.0, 10.0),
+        ]);
+        doc.distribute_shape_ids(&ids(&[1, 2, 3]), Distribution::Horizontal);
+        assert_eq!(
+            all_edges(&doc),
+            [
+                (100.0, 0.0, 110.0, 10.0),
+                (0.0, 2.0, 20.0, 12.0),
+                (55.0, 50.0, 65.0, 60.0),
+            ]
+        );
+        doc.distribute_shape_ids(&ids(&[1, 2, 3]), Distribution::Vertical);
+        assert_eq!(
+            all_edges(&doc),
+            [
+                (100.0, 0.0, 110.0, 10.0),
+                (0.0, 25.0, 20.0, 35.0),
+                (55.0, 50.0, 65.0, 60.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn distribute_overlaps_shapes_evenly_when_they_do_not_fit() {
+        // The spans add up to 110 across 60, so each neighbor overlaps the
+        // next by 25.
+        let mut doc = Document::new_from_shapes(&vec![
+            rectangle(0.0, 0.0, 40.0, 10.0),
+            rectangle(10.0, 20.0, 40.0, 10.0),
+            rectangle(30.0, 40.0, 30.0, 10.0),
+        ]);
+        doc.distribute_shape_ids(&ids(&[1, 2, 3]), Distribution::Horizontal);
+        assert_eq!(
+            all_edges(&doc),
+            [
+                (0.0, 0.0, 40.0, 10.0),
+                (15.0, 20.0, 55.0, 30.0),
+                (30.0, 40.0, 60.0, 50.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_align_and_distribute_as_units() {
+        let mut doc = Document::new_from_shapes(&vec![
+            rectangle(20.0, 0.0, 10.0, 10.0),
+            rectangle(40.0, 20.0, 10.0, 10.0),
+            rectangle(0.0, 40.0, 10.0, 10.0),
+            rectangle(0.0, 100.0, 10.0, 10.0),
+        ]);
+        let group_id = doc.generate_shape_id();
+        doc.group_shape_ids_with_id(&group_id, &ids(&[1, 2]));
+
+        doc.align_shape_ids(&HashSet::from([group_id, shape_id(3)]), Alignment::Left);
+        assert_eq!(edges(&doc, &group_id), (0.0, 0.0, 30.0, 30.0));
+        assert_eq!(edges(&doc, &shape_id(1)), (0.0, 0.0, 10.0, 10.0));
+        assert_eq!(edges(&doc, &shape_id(2)), (20.0, 20.0, 30.0, 30.0));
+        assert_eq!(edges(&doc, &shape_id(3)), (0.0, 40.0, 10.0, 50.0));
+
+        // A shape inside a group that is also being aligned moves only with
+        // the group.
+        doc.align_shape_ids(
+            &HashSet::from([group_id, shape_id(1), shape_id(3)]),
+            Alignment::Right,
+        );
+        assert_eq!(edges(&doc, &shape_id(1)), (0.0, 0.0, 10.0, 10.0));
+        assert_eq!(edges(&doc, &shape_id(3)), (20.0, 40.0, 30.0, 50.0));
+
+        // The group spans 30 and the shapes 10 each across 110, leaving gaps
+        // of 30.
+        doc.distribute_shape_ids(
+            &HashSet::from([group_id, shape_id(3), shape_id(4)]),
+            Distribution::Vertical,
+        );
+        assert_eq!(edges(&doc, &group_id), (0.0, 0.0, 30.0, 30.0));
+        assert_eq!(edges(&doc, &shape_id(3)), (20.0, 60.0, 30.0, 70.0));
+        assert_eq!(edges(&doc, &shape_id(4)), (0.0, 100.0, 10.0, 110.0));
+    }
+
+    #[cfg(feature = "serde")]
+    mod json {
+        use super::*;
+        use crate::shapes::core::{Stroke, Transform};
+
+        // One fixture per format version, each written the way that version
+        // of the code wrote documents. Every version holds a red circle below
+        // a blue square.
+
+        const VERSION_1: &str = r#"{
+            "version": 1,
+            "shapes": {
+                "1": {"geometry": {"type": "Circle", "center": {"x": 100.0, "y": 150.0}, "radius": 80.0},
+                      "style": {"fill": "Red"}},
+                "2": {"geometry": {"type": "Rectangle", "top_left": {"x": 0.0, "y": 0.0}, "size": {"x": 40.0, "y": 40.0}},
+                      "style": {"fill": "Blue"}}
+            },
+            "sequence": [1, 2],
+            "shape_id_generator": {"next_id": 3}
+        }"#;
+
+        const VERSION_2: &str = r##"{
+            "version": 2,
+            "shapes": {
+                "1": {"geometry": {"type": "Circle", "center": {"x": 100.0, "y": 150.0}, "radius": 80.0},
+                      "style": {"fill": "#ff0000"}},
+                "2": {"geometry": {"type": "Rectangle", "top_left": {"x": 0.0, "y": 0.0}, "size": {"x": 40.0, "y": 40.0}},
+                      "style": {"fill": "#0000ff"}}
+            },
+            "sequence": [1, 2],
+            "shape_id_generator": {"next_id": 3}
+        }"##;
+
+        const VERSION_3: &str = r##"{
+            "version": 3,
+            "shapes": {
+                "1": {"geometry": {"type": "Circle", "center": {"x": 100.0, "y": 150.0}, "radius": 80.0},
+                      "style": {"fill": "#ff0000", "stroke": {"color": "#000000", "width": 2.0}}},
+                "2": {"geometry": {"type": "Rectangle", "top_left": {"x": 0.0, "y": 0.0}, "size": {"x": 40.0, "y": 40.0}},
+                      "style": {"fill": "#0000ff"}}
+            },
+            "sequence": [1, 2],
+            "shape_id_generator": {"next_id": 3}
+        }"##;
+
+        const VERSION_4: &str = r##"{
+            "version": 4,
+            "shapes": {
+                "1": {"geometry": {"type": "Circle", "center": {"x": 100.0, "y": 150.0}, "radius": 80.0},
+                      "style": {"fill": "#ff0000"}},
+                "2": {"geometry": {"type": "Rectangle", "top_left": {"x": 0.0, "y": 0.0}, "size": {"x": 40.0, "y": 40.0}},
+                      "style": {"fill": "#0000ff"},
+                      "transform": {"a": 1.0, "b": 0.0, "c": 0.0, "d": 1.0, "e": 5.0, "f": 7.0}}
+            },
+            "sequence": [1, 2],
+            "shape_id_generator": {"next_id": 3}
+        }"##;
+
+        const VERSION_5: &str = r##"{
+            "version": 5,
+            "shapes": {
+                "1": {"geometry": {"type": "Circle", "center": {"x": 100.0, "y": 150.0}, "radius": 80.0},
+                      "style": {"fill": "#ff0000"}},
+                "2": {"geometry": {"type": "Rectangle", "top_left": {"x": 0.0, "y": 0.0}, "size": {"x": 40.0, "y": 40.0}},
+                      "style": {"fill": "#0000ff"}}
+            },
+            "groups": {"3": [1, 2]},
+            "sequence": [3],
+            "shape_id_generator": {"next_id": 4}
+        }"##;
+
+        fn load(json: &str) -> Document {
+            match Document::from_json(json) {
+                Ok(doc) => doc,
+                Err(err) => panic!("failed to load: {err}"),
+            }
+        }
+
+        fn load_error(json: &str) -> DocError {
+            match Document::from_json(json) {
+                Ok(_) => panic!("loaded a document that should fail"),
+                Err(err) => err,
+            }
+        }
+
+        fn painted_ids(doc: &Document) -> Vec<ShapeId> {
+            doc.shape_id_shapes_iter()
+                .map(|(shape_id, _)| shape_id)
+                .collect()
+        }
+
+        // Check what every fixture has in common: the two shapes in order
+        // in a single layer with a fresh id.
+        fn check_fixture(doc: &Document) {
+            assert!(painted_ids(doc) == vec![shape_id(1), shape_id(2)]);
+            assert_eq!(doc.layer_ids_iter().count(), 1);
+            let layer_id = doc.top_layer_id();
+            assert!(layer_id.id >= 3);
+            assert_eq!(
+                doc.get_layer_by_id(&layer_id),
+                Some(&Layer::new(DEFAULT_LAYER_NAME))
+            );
+            let circle = doc.get_shape_by_id(&shape_id(1)).unwrap();
+            assert!(circle.geometry == Geometry::circle(100.0, 150.0, 80.0));
+            assert!(circle.style.fill == Some(Color::RED));
+            let square = doc.get_shape_by_id(&shape_id(2)).unwrap();
+            assert!(square.geometry == Geometry::rectangle(0.0, 0.0, 40.0, 40.0));
+            assert!(square.style == Style::new(Color::BLUE));
+            let mut doc = doc.clone();
+            assert!(doc.generate_shape_id().id > layer_id.id);
+        }
+
+        #[test]
+        fn loads_version_1() {
+            let doc = load(VERSION_1);
+            check_fixture(&doc);
+            assert!(doc.get_shape_by_id(&shape_id(1)).unwrap().style == Style::new(Color::RED));
+        }
+
+        #[test]
+        fn loads_version_2() {
+            let doc = load(VERSION_2);
+            check_fixture(&doc);
+            assert!(doc.get_shape_by_id(&shape_id(1)).unwrap().style == Style::new(Color::RED));
+        }
+
+        #[test]
+        fn loads_version_3() {
+            let doc = load(VERSION_3);
+            check_fixture(&doc);
+            assert!(
+                doc.get_shape_by_id(&shape_id(1)).unwrap().style.stroke
+                    == Some(Stroke::new(Color::BLACK, 2.0))
+            );
+        }
+
+        #[test]
+        fn loads_version_4() {
+            let doc = load(VERSION_4);
+            check_fixture(&doc);
+            assert!(
+                doc.get_shape_by_id(&shape_id(1))
+                    .unwrap()
+                    .transform
+                    .is_identity()
+            );
+            assert_eq!(
+                doc.get_shape_by_id(&shape_id(2)).unwrap().transform,
+                Transform::translation(5.0, 7.0)
+            );
+        }
+
+        #[test]
+        fn loads_version_5() {
+            let doc = load(VERSION_5);
+            check_fixture(&doc);
+            let group_id = shape_id(3);
+            assert!(doc.is_group(&group_id));
+            assert!(
+                doc.group_child_ids_iter(&group_id)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    == vec![shape_id(1), shape_id(2)]
+            );
+            assert_eq!(doc.parent_of(&group_id), Some(doc.top_layer_id()));
+            assert_eq!(doc.parent_of(&shape_id(1)), Some(group_id));
+            assert!(doc.top_layer_id().id >= 4);
+        }
+
+        #[test]
+        fn round_trips_the_current_version() {
+            let doc = load(VERSION_5);
+            let reloaded = load(&doc.to_json());
+            assert_eq!(reloaded.to_json(), doc.to_json());
+            assert_eq!(reloaded.top_layer_id(), doc.top_layer_id());
+        }
+
+        #[test]
+        fn round_trips_groups_from_version_5() {
+            let doc = load(&load(VERSION_5).to_json());
+            check_fixture(&doc);
+            let group_id = shape_id(3);
+            assert!(doc.is_group(&group_id));
+            assert_eq!(child_ids(&doc, &group_id), id_list(&[1, 2]));
+            assert_eq!(doc.parent_of(&group_id), Some(doc.top_layer_id()));
+            assert_eq!(doc.parent_of(&shape_id(2)), Some(group_id));
+        }
+
+        #[test]
+        fn round_trips_nested_groups() {
+            let mut doc = four_squares();
+            let inner_id = doc.generate_shape_id();
+            doc.group_shape_ids_with_id(&inner_id, &ids(&[1, 3]));
+            let outer_id = doc.generate_shape_id();
+            doc.group_shape_ids_with_id(&outer_id, &HashSet::from([inner_id, shape_id(4)]));
+            let reloaded = load(&doc.to_json());
+            assert_eq!(
+                child_ids(&reloaded, &reloaded.top_layer_id()),
+                vec![shape_id(2), outer_id]
+            );
+            assert_eq!(child_ids(&reloaded, &outer_id), vec![inner_id, shape_id(4)]);
+            assert_eq!(child_ids(&reloaded, &inner_id), id_list(&[1, 3]));
+            assert_eq!(reloaded.top_level_id_for(&shape_id(3)), outer_id);
+            assert!(painted_ids(&reloaded) == id_list(&[2, 1, 3, 4]));
+        }
+
+        #[test]
+        fn rejects_malformed_json() {
+            assert!(matches!(load_error("{"), DocError::MalformedJson(_)));
+            assert!(matches!(
+                load_error(r#"{"version": 6, "shapes": []}"#),
+                DocError::MalformedJson(_)
+            ));
+        }
+
+        #[test]
+        fn rejects_unsupported_versions() {
+            for version in [0, JSON_FORMAT_VERSION + 1] {
+                let json = format!(r#"{{"version": {version}}}"#);
+                assert!(matches!(
+                    load_error(&json),
+                    DocError::UnsupportedVersion(v) if v == version
+                ));
+            }
+        }
+
+        // A version 6 document with the given groups, layers, and sequence
+        // around a single square with id 1.
+        fn version_6(groups: &str, layers: &str, sequence: &str) -> String {
+            format!(
+                r##"{{
+                    "version": 6,
+                    "shapes": {{
+                        "1": {{"geometry": {{"type": "Rectangle", "top_left": {{"x": 0.0, "y": 0.0}}, "size": {{"x": 40.0, "y": 40.0}}}},
+                               "style": {{"fill": "#0000ff"}}}}
+                    }},
+                    "groups": {{{groups}}},
+                    "layers": {{{layers}}},
+                    "sequence": [{sequence}],
+                    "shape_id_generator": {{"next_id": 10}}
+                }}"##
+            )
+        }
+
+        const LAYER: &str = r#"{"name": "Layer", "visible": true, "locked": false}"#;
+
+        #[test]
+        fn loads_version_6() {
+            let mut doc = load(&version_6(r#""2": [1]"#, &format!(r#""2": {LAYER}"#), "2"));
+            assert_eq!(doc.top_layer_id(), shape_id(2));
+            assert!(painted_ids(&doc) == vec![shape_id(1)]);
+            assert_eq!(doc.generate_shape_id(), shape_id(10));
+        }
+
+        #[test]
+        fn rejects_duplicate_ids() {
+            let json = version_6(r#""2": [1, 1]"#, &format!(r#""2": {LAYER}"#), "2");
+            assert!(matches!(
+                load_error(&json),
+                DocError::DuplicateShapeId(id) if id == shape_id(1)
+            ));
+        }
+
+        #[test]
+        fn rejects_missing_shapes() {
+            let json = version_6(r#""2": [1, 5]"#, &format!(r#""2": {LAYER}"#), "2");
+            assert!(matches!(
+                load_error(&json),
+                DocError::MissingShape(id) if id == shape_id(5)
+            ));
+        }
+
+        #[test]
+        fn rejects_unsequenced_shapes() {
+            let json = version_6(r#""2": []"#, &format!(r#""2": {LAYER}"#), "2");
+            assert!(matches!(
+                load_error(&json),
+                DocError::UnsequencedShape(id) if id == shape_id(1)
+            ));
+        }
+
+        #[test]
+        fn rejects_sequenced_shapes_that_are_not_layers() {
+            let json = version_6(r#""2": []"#, &format!(r#""2": {LAYER}"#), "2, 1");
+            assert!(matches!(
+                load_error(&json),
+                DocError::NotALayer(id) if id == shape_id(1)
+            ));
+        }
+
+        #[test]
+        fn rejects_group_cycles() {
+            let layer = format!(r#""2": {LAYER}"#);
+            for groups in [
+                r#""2": [3], "3": [1, 3]"#,
+                r#""2": [3], "3": [4], "4": [1, 3]"#,
+            ] {
+                let error = load_error(&version_6(groups, &layer, "2"));
+                assert!(matches!(error, DocError::GroupCycle(id) if id == shape_id(3)));
+                assert_eq!(error.to_string(), "group id 3 is inside itself");
+            }
+        }
+
+        #[test]
+        fn rejects_nested_layers() {
+            let json = version_6(
+                r#""2": [3], "3": [1]"#,
+                &format!(r#""2": {LAYER}, "3": {LAYER}"#),
+                "2",
+            );
+            assert!(matches!(
+                load_error(&json),
+                DocError::NestedLayer(id) if id == shape_id(3)
+            ));
         }
     }
 }
@@ -0,0 +1,82 @@
+// The grid helps line shapes up. It has major lines every `spacing` units
+// of the document, divided into `subdivisions` steps by minor lines. The
+// grid can be shown or hidden and, separately, dragging can snap to it.
+// Snapping goes to every grid point, minor as well as major.
+
+use crate::shapes::core::{Bounds, XYPoint};
+
+#[derive(Clone, PartialEq)]
+pub struct Grid {
+    pub spacing: f64,
+    pub subdivisions: u32,
+    pub visible: bool,
+    pub snap: bool,
+}
+
+impl Grid {
+    // A visible grid with major lines every 50 units and minor lines
+    // every 10. Snapping starts out off.
+    pub fn new_default() -> Self {
+        Self {
+            spacing: 50.0,
+            subdivisions: 5,
+            visible: true,
+            snap: false,
+        }
+    }
+
+    // The distance between neighboring grid points.
+    pub fn step(&self) -> f64 {
+        self.spacing / f64::from(self.subdivisions.max(1))
+    }
+
+    // The nearest grid coordinate to a coordinate.
+    pub fn snap_coordinate(&self, coordinate: f64) -> f64 {
+        let step = self.step();
+        if step.is_finite() && 0.0 < step {
+            (coordinate / step).round() * step
+        } else {
+            coordinate
+        }
+    }
+
+    // The nearest grid point to a point if snapping is on.
+    pub fn snap_point(&self, point: &XYPoint) -> XYPoint {
+        if !self.snap {
+            return point.clone();
+        }
+        XYPoint::new(self.snap_coordinate(point.x), self.snap_coordinate(point.y))
+    }
+
+    // Adjust an offset for moving bounds so that an edge of the moved
+    // bounds lands on the grid if snapping is on. Along each axis, we snap
+    // whichever edge needs the smaller adjustment.
+    pub fn snap_offset_for_bounds(&self, bounds: &Bounds, offset: &XYPoint) -> XYPoint {
+        if !self.snap {
+            return offset.clone();
+        }
+        XYPoint::new(
+            self.snap_offset_for_span(bounds.min.x, bounds.max.x, offset.x),
+            self.snap_offset_for_span(bounds.min.y, bounds.max.y, offset.y),
+        )
+    }
+
+    fn snap_offset_for_span(&self, min: f64, max: f64, offset: f64) -> f64 {
+        let min_offset = self.snap_coordinate(min + offset) - min;
+        let max_offset = self.snap_coordinate(max + offset) - max;
+        if (min_offset - offset).abs() <= (max_offset - offset).abs() {
+            min_offset
+        } else {
+            max_offset
+        }
+    }
+
+    // Adjust the offset for moving an edge at a coordinate so that the
+    // moved edge lands on the grid if snapping is on.
+    pub fn snap_offset_for_edge(&self, edge: f64, offset: f64) -> f64 {
+        if !self.snap {
+            return offset;
+        }
+        self.snap_coordinate(edge + offset) - edge
+    }
+}
@@ -0,0 +1,171 @@
+// Smart guides show when something being dragged lines up with other
+// shapes. Along each axis, a bounds has three lines that can line up: its
+// two edges and its center. While dragging, we snap the moving lines to
+// nearby lines of the other shapes -- the targets -- and draw a guide
+// along each line where they meet.
+//
+// Everything here is in document coordinates. The caller converts its
+// tolerance from canvas pixels.
+
+use crate::shapes::core::{Bounds, XYPoint};
+
+// Lines closer than this are the same line. This absorbs floating point
+// noise from snapping.
+const SAME_LINE_EPSILON: f64 = 1e-6;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GuideAxis {
+    // A vertical line at an x coordinate.
+    Vertical,
+    // A horizontal line at a y coordinate.
+    Horizontal,
+}
+
+impl GuideAxis {
+    // The lines of a bounds along this axis that can line up: the edges
+    // and the center.
+    fn lines(&self, bounds: &Bounds) -> [f64; 3] {
+        match self {
+            GuideAxis::Vertical => [bounds.min.x, bounds.center().x, bounds.max.x],
+            GuideAxis::Horizontal => [bounds.min.y, bounds.center().y, bounds.max.y],
+        }
+    }
+
+    // The extent of a bounds along guides on this axis.
+    fn span(&self, bounds: &Bounds) -> (f64, f64) {
+        match self {
+            GuideAxis::Vertical => (bounds.min.y, bounds.max.y),
+            GuideAxis::Horizontal => (bounds.min.x, bounds.max.x),
+        }
+    }
+}
+
+// A guide line at a position along one axis running from one coordinate to
+// another along the other axis -- e.g., a vertical guide at x = position
+// from y = from to y = to. It covers the moving bounds and every target
+// that lines up.
+#[derive(Clone, PartialEq)]
+pub struct Guide {
+    pub axis: GuideAxis,
+    pub position: f64,
+    pub from: f64,
+    pub to: f64,
+}
+
+impl Guide {
+    // The ends of the guide line.
+    pub fn ends(&self) -> (XYPoint, XYPoint) {
+        match self.axis {
+            GuideAxis::Vertical => (
+                XYPoint::new(self.position, self.from),
+                XYPoint::new(self.position, self.to),
+            ),
+            GuideAxis::Horizontal => (
+                XYPoint::new(self.from, self.position),
+                XYPoint::new(self.to, self.position),
+            ),
+        }
+    }
+}
+
+// The bounds of the shapes that moving shapes can line up with.
+#[derive(Clone)]
+pub struct GuideTargets {
+    bounds: Vec<Bounds>,
+}
+
+impl GuideTargets {
+    pub fn new(bounds: Vec<Bounds>) -> Self {
+        Self { bounds }
+    }
+
+    // Find the offset adjustments that would snap some line of the moved
+    // bounds to the nearest target line within the tolerance. Each axis
+    // gets the adjusted offset if it snaps and None if it does not.
+    pub fn snap_offset(
+        &self,
+        moving: &Bounds,
+        offset: &XYPoint,
+        tolerance: f64,
+    ) -> (Option<f64>, Option<f64>) {
+        let moved = offset_bounds(moving, offset);
+        let (vertical, horizontal) = (GuideAxis::Vertical, GuideAxis::Horizontal);
+        (
+            self.nearest_adjustment(vertical, &vertical.lines(&moved), tolerance)
+                .map(|adjustment| offset.x + adjustment),
+            self.nearest_adjustment(horizontal, &horizontal.lines(&moved), tolerance)
+                .map(|adjustment| offset.y + adjustment),
+        )
+    }
+
+    // Find the coordinates that would snap a point to the nearest target
+    // line within the tolerance along each axis -- e.g., for the corner of
+    // a shape being dragged out.
+    pub fn snap_point(&self, point: &XYPoint, tolerance: f64) -> (Option<f64>, Option<f64>) {
+        (
+            self.nearest_adjustment(GuideAxis::Vertical, &[point.x], tolerance)
+                .map(|adjustment| point.x + adjustment),
+            self.nearest_adjustment(GuideAxis::Horizontal, &[point.y], tolerance)
+                .map(|adjustment| point.y + adjustment),
+        )
+    }
+
+    // The guides for the lines of some bounds that line up exactly with
+    // target lines.
+    pub fn guides_for(&self, bounds: &Bounds) -> Vec<Guide> {
+        [GuideAxis::Vertical, GuideAxis::Horizontal]
+            .into_iter()
+            .flat_map(|axis| {
+                axis.lines(bounds)
+                    .into_iter()
+                    .filter_map(move |position| self.guide_at(axis, position, bounds))
+            })
+            .collect()
+    }
+
+    // The smallest change that moves one of the moving lines onto a target
+    // line, if any is within the tolerance.
+    fn nearest_adjustment(
+        &self,
+        axis: GuideAxis,
+        moving_lines: &[f64],
+        tolerance: f64,
+    ) -> Option<f64> {
+        self.bounds
+            .iter()
+            .flat_map(|target| axis.lines(target))
+            .flat_map(|target| moving_lines.iter().map(move |line| target - line))
+            .filter(|adjustment| adjustment.abs() <= tolerance)
+            .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+    }
+
+    // A guide along a line through the bounds if any target lines up with
+    // it. The guide spans the bounds and all of the targets that line up.
+    fn guide_at(&self, axis: GuideAxis, position: f64, bounds: &Bounds) -> Option<Guide> {
+        let mut aligned = self
+            .bounds
+            .iter()
+            .filter(|target| {
+                axis.lines(target)
+                    .iter()
+                    .any(|line| (line - position).abs() <= SAME_LINE_EPSILON)
+            })
+            .peekable();
+        aligned.peek()?;
+        let (from, to) = aligned
+            .map(|target| axis.span(target))
+            .fold(axis.span(bounds), |(from, to), (min, max)| {
+                (from.min(min), to.max(max))
+            });
+        Some(Guide {
+            axis,
+            position,
+            from,
+            to,
+        })
+    }
+}
+
+fn offset_bounds(bounds: &Bounds, offset: &XYPoint) -> Bounds {
+    Bounds::new(bounds.min.add(offset), bounds.max.add(offset))
+}
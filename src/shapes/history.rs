@@ -0,0 +1,146 @@
+use crate::shapes::doc::{Document, PartialSnapshot, ShapeId};
+use std::vec::Vec;
+
+// Undo and redo are built from transactions. A transaction is a single
+// undoable step -- e.g., an entire drag gesture from mouse down to mouse
+// up -- and consists of partial snapshots of the document from before and
+// after the step.
+//
+// The history does not perform edits itself. Instead, whoever edits the
+// document first announces which shapes, groups, and layers are about to
+// change -- and which of them are about to move in the z-order -- so that
+// the history can record their prior state in the open transaction.
+
+#[derive(Clone)]
+struct Transaction {
+    before: PartialSnapshot,
+    after: PartialSnapshot,
+}
+
+pub struct History {
+    // The "before" snapshot for the transaction in progress if any.
+    open_transaction: Option<PartialSnapshot>,
+    undo_stack: Vec<Transaction>,
+    redo_stack: Vec<Transaction>,
+}
+
+impl History {
+    // A new history has nothing to undo or redo.
+    pub fn new_empty() -> Self {
+        Self {
+            open_transaction: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    // Start recording a transaction. If a transaction is already open,
+    // the new one just becomes part of it.
+    pub fn begin_transaction(&mut self) {
+        if self.open_transaction.is_none() {
+            self.open_transaction = Some(PartialSnapshot::new_empty());
+        }
+    }
+
+    // Is a transaction currently being recorded?
+    pub fn is_recording(&self) -> bool {
+        self.open_transaction.is_some()
+    }
+
+    // Note that a shape is about to be inserted, replaced, or deleted.
+    // Outside of a transaction this is a no-op.
+    pub fn will_change_shape(&mut self, doc: &Document, shape_id: &ShapeId) {
+        if let Some(before) = &mut self.open_transaction {
+            doc.record_shape_in_snapshot(before, shape_id);
+        }
+    }
+
+    // Note that a group or layer is about to be created or deleted.
+    // Changes to its children go through will_change_placement. Outside of
+    // a transaction this is a no-op.
+    pub fn will_change_group(&mut self, doc: &Document, group_id: &ShapeId) {
+        if let Some(before) = &mut self.open_transaction {
+            doc.record_group_in_snapshot(before, group_id);
+        }
+    }
+
+    // Note that a shape, group, or layer is about to be added to, removed
+    // from, or moved within the group or layer holding it -- or, for a
+    // layer, the sequence of layers. Outside of a transaction this is a
+    // no-op.
+    pub fn will_change_placement(&mut self, doc: &Document, shape_id: &ShapeId) {
+        if let Some(before) = &mut self.open_transaction {
+            doc.record_placement_in_snapshot(before, shape_id);
+        }
+    }
+
+    // Note that a layer is about to be created, deleted, renamed, or have
+    // its flags change. Outside of a transaction this is a no-op.
+    pub fn will_change_layer(&mut self, doc: &Document, layer_id: &ShapeId) {
+        if let Some(before) = &mut self.open_transaction {
+            doc.record_layer_in_snapshot(before, layer_id);
+        }
+    }
+
+    // Finish recording the open transaction and push it on to the undo
+    // stack. Transactions that leave the document unchanged are dropped
+    // so that, e.g., clicking on a shape does not create an undo step.
+    pub fn commit_transaction(&mut self, doc: &Document) {
+        if let Some(before) = self.open_transaction.take() {
+            let after = doc.snapshot_matching(&before);
+            if before != after {
+                self.undo_stack.push(Transaction { before, after });
+                self.redo_stack.clear();
+            }
+        }
+    }
+
+    // Abandon the open transaction and put the document back the way it
+    // was when the transaction began -- e.g., when a drag is cancelled.
+    // Nothing goes on the undo stack.
+    pub fn abandon_transaction(&mut self, doc: &mut Document) {
+        if let Some(before) = self.open_transaction.take() {
+            doc.restore_snapshot(&before);
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.open_transaction.is_none() && !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.open_transaction.is_none() && !self.redo_stack.is_empty()
+    }
+
+    // Undo the most recent transaction. Returns false if there was nothing
+    // to undo. We refuse to undo while a transaction is being recorded.
+    pub fn undo(&mut self, doc: &mut Document) -> bool {
+        if !self.can_undo() {
+            return false;
+        }
+        match self.undo_stack.pop() {
+            Some(transaction) => {
+                doc.restore_snapshot(&transaction.before);
+                self.redo_stack.push(transaction);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Redo the most recently undone transaction. Returns false if there
+    // was nothing to redo.
+    pub fn redo(&mut self, doc: &mut Document) -> bool {
+        if !self.can_redo() {
+            return false;
+        }
+        match self.redo_stack.pop() {
+            Some(transaction) => {
+                doc.restore_snapshot(&transaction.after);
+                self.undo_stack.push(transaction);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -1,3 +1,12 @@
-pub mod app;
-mod core;
-mod doc;
+pub(crate) mod app;
+pub mod color;
+pub mod core;
+pub mod doc;
+mod grid;
+mod guides;
+pub mod history;
+mod spatial_index;
+pub mod svg_export;
+pub mod svg_import;
+mod viewport;
+mod z_order;
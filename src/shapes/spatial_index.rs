@@ -0,0 +1,99 @@
+use crate::shapes::core::{Bounds, Shape, XYPoint};
+use crate::shapes::doc::ShapeId;
+use rstar::{AABB, RTree, RTreeObject};
+use std::collections::HashMap;
+
+// A spatial index over the painted bounds of the shapes in a document so
+// that region and point queries only look at nearby shapes. The index is
+// an R-tree plus a map from shape ids to their entries so that we can find
+// an entry to remove without knowing its old bounds.
+//
+// Queries return candidates in no particular order. Since painted bounds
+// include strokes, they contain the geometric bounds and callers can filter
+// the candidates by whatever exact test they need.
+
+#[derive(Clone)]
+pub(crate) struct SpatialIndex {
+    tree: RTree<IndexEntry>,
+    entries: HashMap<ShapeId, IndexEntry>,
+}
+
+#[derive(Clone, PartialEq)]
+struct IndexEntry {
+    shape_id: ShapeId,
+    envelope: AABB<[f64; 2]>,
+}
+
+impl IndexEntry {
+    fn new(shape_id: ShapeId, shape: &Shape) -> Self {
+        Self {
+            shape_id,
+            envelope: envelope_for_bounds(&shape.painted_bounds()),
+        }
+    }
+}
+
+impl RTreeObject for IndexEntry {
+    type Envelope = AABB<[f64; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        self.envelope
+    }
+}
+
+fn envelope_for_bounds(bounds: &Bounds) -> AABB<[f64; 2]> {
+    AABB::from_corners([bounds.min.x, bounds.min.y], [bounds.max.x, bounds.max.y])
+}
+
+impl SpatialIndex {
+    pub fn new_empty() -> Self {
+        Self {
+            tree: RTree::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    // Bulk loading builds a better balanced tree much faster than
+    // inserting the shapes one by one.
+    pub fn new_from_shapes<'a>(shapes: impl Iterator<Item = (&'a ShapeId, &'a Shape)>) -> Self {
+        let entries: HashMap<ShapeId, IndexEntry> = shapes
+            .map(|(shape_id, shape)| (*shape_id, IndexEntry::new(*shape_id, shape)))
+            .collect();
+        Self {
+            tree: RTree::bulk_load(entries.values().cloned().collect()),
+            entries,
+        }
+    }
+
+    // Add a shape or update the bounds for a shape already in the index.
+    pub fn upsert(&mut self, shape_id: &ShapeId, shape: &Shape) {
+        let entry = IndexEntry::new(*shape_id, shape);
+        if self.entries.get(shape_id) == Some(&entry) {
+            return;
+        }
+        self.remove(shape_id);
+        self.tree.insert(entry.clone());
+        self.entries.insert(*shape_id, entry);
+    }
+
+    pub fn remove(&mut self, shape_id: &ShapeId) {
+        if let Some(entry) = self.entries.remove(shape_id) {
+            self.tree.remove(&entry);
+        }
+    }
+
+    // The shapes whose painted bounds intersect the bounds.
+    pub fn shape_ids_intersecting<'a>(
+        &'a self,
+        bounds: &Bounds,
+    ) -> impl Iterator<Item = ShapeId> + use<'a> {
+        self.tree
+            .locate_in_envelope_intersecting(&envelope_for_bounds(bounds))
+            .map(|entry| entry.shape_id)
+    }
+
+    // The shapes whose painted bounds contain the point.
+    pub fn shape_ids_at<'a>(&'a self, point: &XYPoint) -> impl Iterator<Item = ShapeId> + use<'a> {
+        self.shape_ids_intersecting(&Bounds::new(point.clone(), point.clone()))
+    }
+}
@@ -0,0 +1,295 @@
+// Export a document as a standalone SVG 1.1 file. This is plain string
+// generation with no dependency on the DOM or on wasm so that it can also
+// be used outside of the browser -- e.g., to generate thumbnails.
+
+use crate::shapes::color::Color;
+use crate::shapes::core::{
+    Geometry, LineCap, LineJoin, PathSegment, Shape, Style, Transform, XYPoint,
+};
+use crate::shapes::doc::{Document, ShapeId};
+use std::fmt::Write;
+
+// Convert a document to an SVG string. The view box is the painted bounds
+// of the shapes on visible layers so that the drawing fills the image
+// without clipping strokes; hidden layers are exported but do not show, so
+// they do not count. With nothing visible there is nothing to fit, so we
+// leave out the view box and size and let viewers use their defaults
+// rather than writing an empty image.
+
+pub fn document_to_svg(doc: &Document) -> String {
+    let size = match doc
+        .layer_ids_iter()
+        .filter(|layer_id| {
+            doc.get_layer_by_id(layer_id)
+                .is_some_and(|layer| layer.visible)
+        })
+        .flat_map(|layer_id| doc.shape_ids_in(layer_id))
+        .filter_map(|shape_id| doc.get_shape_by_id(&shape_id))
+        .map(|shape| shape.painted_bounds())
+        .reduce(|acc, bounds| acc.union(&bounds))
+    {
+        Some(bounds) => {
+            let (left, top) = (bounds.min.x, bounds.min.y);
+            let (width, height) = (bounds.width(), bounds.height());
+            format!(
+                " viewBox=\"{left} {top} {width} {height}\" width=\"{width}\" height=\"{height}\""
+            )
+        }
+        None => String::new(),
+    };
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n");
+    // Writing to a String cannot fail, so we ignore the results here and
+    // below.
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\"{size}>"
+    );
+    for layer_id in doc.layer_ids_iter() {
+        write_layer(&mut out, doc, layer_id);
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+// Write a layer as a group. Hidden layers are kept but not displayed.
+
+fn write_layer(out: &mut String, doc: &Document, layer_id: &ShapeId) {
+    let Some(layer) = doc.get_layer_by_id(layer_id) else {
+        return;
+    };
+    let display = if layer.visible {
+        ""
+    } else {
+        " display=\"none\""
+    };
+    let _ = writeln!(out, "  <g id=\"layer_{layer_id}\"{display}>");
+    for child_id in doc.group_child_ids_iter(layer_id) {
+        write_item(out, doc, child_id, 2);
+    }
+    out.push_str("  </g>\n");
+}
+
+// Write a shape or a group with its children, indented by depth.
+
+fn write_item(out: &mut String, doc: &Document, shape_id: &ShapeId, depth: usize) {
+    let indent = "  ".repeat(depth);
+    if let Some(shape) = doc.get_shape_by_id(shape_id) {
+        out.push_str(&indent);
+        write_shape(out, *shape_id, shape);
+    } else if doc.is_group(shape_id) {
+        let _ = writeln!(out, "{indent}<g id=\"group_{shape_id}\">");
+        for child_id in doc.group_child_ids_iter(shape_id) {
+            write_item(out, doc, child_id, depth + 1);
+        }
+        let _ = writeln!(out, "{indent}</g>");
+    }
+}
+
+fn write_shape(out: &mut String, shape_id: ShapeId, shape: &Shape) {
+    let mut style = svg_style_attributes(&shape.rendered_style());
+    if !shape.transform.is_identity() {
+        let _ = write!(style, " transform=\"{}\"", svg_transform(&shape.transform));
+    }
+
+    let _ = match &shape.geometry {
+        Geometry::Circle { center, radius } => writeln!(
+            out,
+            "<circle id=\"shape_{shape_id}\" cx=\"{}\" cy=\"{}\" r=\"{radius}\"{style}/>",
+            center.x, center.y
+        ),
+        Geometry::Rectangle { top_left, size } => writeln!(
+            out,
+            "<rect id=\"shape_{shape_id}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{style}/>",
+            top_left.x, top_left.y, size.x, size.y
+        ),
+        Geometry::Ellipse { center, radii } => writeln!(
+            out,
+            "<ellipse id=\"shape_{shape_id}\" cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\"{style}/>",
+            center.x, center.y, radii.x, radii.y
+        ),
+        Geometry::Line { start, end } => writeln!(
+            out,
+            "<line id=\"shape_{shape_id}\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"{style}/>",
+            start.x, start.y, end.x, end.y
+        ),
+        Geometry::Polyline { points } => writeln!(
+            out,
+            "<polyline id=\"shape_{shape_id}\" points=\"{}\"{style}/>",
+            svg_points(points)
+        ),
+        Geometry::Polygon { points } => writeln!(
+            out,
+            "<polygon id=\"shape_{shape_id}\" points=\"{}\"{style}/>",
+            svg_points(points)
+        ),
+        Geometry::Path { segments } => writeln!(
+            out,
+            "<path id=\"shape_{shape_id}\" d=\"{}\"{style}/>",
+            svg_path_data(segments)
+        ),
+    };
+}
+
+// Format a transform for the transform attribute. This is shared with the
+// live view.
+
+pub fn svg_transform(transform: &Transform) -> String {
+    let Transform { a, b, c, d, e, f } = transform;
+    format!("matrix({a} {b} {c} {d} {e} {f})")
+}
+
+// The fill and stroke attributes for a style, each with a leading space.
+// Only non-default stroke properties are written.
+
+fn svg_style_attributes(style: &Style) -> String {
+    let mut attributes = match &style.fill {
+        Some(color) => svg_paint("fill", color),
+        None => " fill=\"none\"".to_string(),
+    };
+    if let Some(stroke) = &style.stroke {
+        attributes.push_str(&svg_paint("stroke", &stroke.color));
+        let _ = write!(attributes, " stroke-width=\"{}\"", stroke.width);
+        if !stroke.dash_array.is_empty() {
+            let _ = write!(
+                attributes,
+                " stroke-dasharray=\"{}\"",
+                svg_dash_array(&stroke.dash_array)
+            );
+        }
+        if stroke.line_join != LineJoin::Miter {
+            let _ = write!(
+                attributes,
+                " stroke-linejoin=\"{}\"",
+                stroke.line_join.svg_name()
+            );
+        }
+        if stroke.line_cap != LineCap::Butt {
+            let _ = write!(
+                attributes,
+                " stroke-linecap=\"{}\"",
+                stroke.line_cap.svg_name()
+            );
+        }
+    }
+    attributes
+}
+
+// Format a dash array for the stroke-dasharray attribute. This is shared
+// with the live view.
+
+pub fn svg_dash_array(dash_array: &[f64]) -> String {
+    dash_array
+        .iter()
+        .map(|length| length.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Format points for the points attribute of a polyline or polygon. This
+// is shared with the live view.
+
+pub fn svg_points(points: &[XYPoint]) -> String {
+    points
+        .iter()
+        .map(|point| format!("{},{}", point.x, point.y))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Format path segments as SVG path data using absolute commands. This is
+// shared with the live view.
+
+pub fn svg_path_data(segments: &[PathSegment]) -> String {
+    segments
+        .iter()
+        .map(|segment| match segment {
+            PathSegment::MoveTo { to } => format!("M {} {}", to.x, to.y),
+            PathSegment::LineTo { to } => format!("L {} {}", to.x, to.y),
+            PathSegment::CubicTo {
+                control1,
+                control2,
+                to,
+            } => format!(
+                "C {} {} {} {} {} {}",
+                control1.x, control1.y, control2.x, control2.y, to.x, to.y
+            ),
+            PathSegment::QuadTo { control, to } => {
+                format!("Q {} {} {} {}", control.x, control.y, to.x, to.y)
+            }
+            PathSegment::Close => "Z".to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// The paint attribute for a color with a leading space. SVG 1.1 has no
+// alpha in colors, so translucent colors get a separate opacity attribute
+// as well.
+
+fn svg_paint(property: &str, color: &Color) -> String {
+    let rgb = color.to_hex_rgb();
+    if color.is_opaque() {
+        format!(" {property}=\"{rgb}\"")
+    } else {
+        let opacity = (color.opacity() * 1000.0).round() / 1000.0;
+        format!(" {property}=\"{rgb}\" {property}-opacity=\"{opacity}\"")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::core::Stroke;
+    use crate::shapes::doc::Layer;
+
+    #[test]
+    fn empty_documents_have_no_view_box() {
+        let svg = document_to_svg(&Document::new_empty());
+        assert!(!svg.contains("viewBox"));
+        assert!(!svg.contains("width="));
+    }
+
+    #[test]
+    fn hidden_layers_do_not_count_toward_the_view_box() {
+        let square = |left: f64| {
+            Shape::new(
+                Geometry::rectangle(left, 20.0, 30.0, 40.0),
+                Style::new(Color::BLACK),
+            )
+        };
+        let mut doc = Document::new_from_shapes(&vec![square(10.0)]);
+        let visible_layer_id = doc.top_layer_id();
+        let hidden_layer_id = doc.generate_shape_id();
+        let mut hidden_layer = Layer::new("Hidden");
+        hidden_layer.visible = false;
+        doc.upsert_layer_with_id(&hidden_layer_id, hidden_layer);
+        let shape_id = doc.generate_shape_id();
+        doc.upsert_shape_with_id_in_layer(&shape_id, square(500.0), &hidden_layer_id);
+
+        let svg = document_to_svg(&doc);
+        assert!(svg.contains("viewBox=\"10 20 30 40\" width=\"30\" height=\"40\""));
+        assert!(svg.contains(&format!(
+            "<g id=\"layer_{hidden_layer_id}\" display=\"none\">"
+        )));
+
+        let mut visible_layer = doc.get_layer_by_id(&visible_layer_id).unwrap().clone();
+        visible_layer.visible = false;
+        doc.upsert_layer_with_id(&visible_layer_id, visible_layer);
+        assert!(!document_to_svg(&doc).contains("viewBox"));
+    }
+
+    #[test]
+    fn view_box_fits_transformed_strokes() {
+        // Painted bounds allow twice the stroke width around the geometry
+        // for miter joins, and the scale applies to the stroke as well.
+        let shape = Shape::new(
+            Geometry::rectangle(0.0, 0.0, 10.0, 10.0),
+            Style::new(Color::BLACK).with_stroke(Some(Stroke::new(Color::RED, 2.0))),
+        )
+        .with_transform(Transform::scaling(3.0, 3.0));
+        let svg = document_to_svg(&Document::new_from_shapes(&vec![shape]));
+        assert!(svg.contains("viewBox=\"-12 -12 54 54\" width=\"54\" height=\"54\""));
+    }
+}
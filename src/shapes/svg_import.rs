@@ -0,0 +1,899 @@
+// Import shapes from an SVG file. We only understand a small subset of
+// SVG: basic shapes with their fills, strokes, and transforms, possibly
+// nested inside groups and nested <svg> elements. Anything else is skipped
+// and reported as a warning so that the rest of the file can still be
+// imported.
+
+use crate::shapes::color::Color;
+use crate::shapes::core::{Geometry, LineCap, LineJoin, Shape, Stroke, Style, Transform, XYPoint};
+use crate::shapes::doc::{DocError, Document};
+use std::vec::Vec;
+
+// The result of a successful import: the document plus warnings about
+// anything that could not be imported.
+
+pub struct SvgImport {
+    pub doc: Document,
+    pub warnings: Vec<ImportWarning>,
+}
+
+pub enum ImportWarning {
+    // An element that we do not know how to import, by tag name.
+    UnsupportedElement(String),
+    // An element that we skipped because it was missing a required attribute.
+    MissingAttribute {
+        element: String,
+        attribute: String,
+    },
+    // An attribute value that we could not interpret.
+    UnsupportedValue {
+        element: String,
+        attribute: String,
+        value: String,
+    },
+}
+
+impl std::fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ImportWarning::UnsupportedElement(element) => {
+                write!(f, "unsupported element <{element}> was skipped")
+            }
+            ImportWarning::MissingAttribute { element, attribute } => {
+                write!(f, "<{element}> without {attribute} was skipped")
+            }
+            ImportWarning::UnsupportedValue {
+                element,
+                attribute,
+                value,
+            } => write!(f, "<{element}> has unsupported {attribute}=\"{value}\""),
+        }
+    }
+}
+
+// Parse an SVG string into a document. Only malformed XML is an error.
+
+pub fn document_from_svg(svg: &str) -> Result<SvgImport, DocError> {
+    let xml =
+        roxmltree::Document::parse(svg).map_err(|err| DocError::MalformedSvg(err.to_string()))?;
+    let mut importer = Importer {
+        shapes: Vec::new(),
+        warnings: Vec::new(),
+    };
+    importer.import_container(xml.root_element(), &Inherited::initial());
+    Ok(SvgImport {
+        doc: Document::new_from_shapes(&importer.shapes),
+        warnings: importer.warnings,
+    })
+}
+
+// The presentation attributes that elements inherit from their ancestors.
+// Colors and their opacities are inherited separately as in SVG and only
+// combined when we make a shape. A color of None is the paint "none".
+// Transforms are not inherited as such, but each element's transform
+// applies on top of its ancestors' so we accumulate them here as well.
+
+#[derive(Clone)]
+struct Inherited {
+    fill: Option<Color>,
+    fill_opacity: f64,
+    stroke: Option<Color>,
+    stroke_opacity: f64,
+    stroke_width: f64,
+    dash_array: Vec<f64>,
+    line_join: LineJoin,
+    line_cap: LineCap,
+    transform: Transform,
+}
+
+impl Inherited {
+    // The initial values as specified by SVG.
+    fn initial() -> Self {
+        Self {
+            fill: Some(Color::BLACK),
+            fill_opacity: 1.0,
+            stroke: None,
+            stroke_opacity: 1.0,
+            stroke_width: 1.0,
+            dash_array: Vec::new(),
+            line_join: LineJoin::Miter,
+            line_cap: LineCap::Butt,
+            transform: Transform::identity(),
+        }
+    }
+
+    fn style(&self) -> Style {
+        let with_opacity =
+            |color: &Color, opacity: f64| color.with_opacity(color.opacity() * opacity);
+        Style {
+            fill: self
+                .fill
+                .map(|color| with_opacity(&color, self.fill_opacity)),
+            stroke: self.stroke.map(|color| Stroke {
+                color: with_opacity(&color, self.stroke_opacity),
+                width: self.stroke_width,
+                dash_array: self.dash_array.clone(),
+                line_join: self.line_join,
+                line_cap: self.line_cap,
+            }),
+        }
+    }
+}
+
+// The importer accumulates shapes from bottom to top -- i.e., in document
+// order -- together with any warnings.
+
+struct Importer {
+    shapes: Vec<Shape>,
+    warnings: Vec<ImportWarning>,
+}
+
+impl Importer {
+    // Import the children of an <svg> or <g> element. Presentation
+    // attributes are inherited from the container as they are in SVG. An
+    // <svg> inside another sets up a new viewport for its children, while
+    // the outermost one just holds the document.
+    fn import_container(&mut self, container: roxmltree::Node, inherited: &Inherited) {
+        let mut inherited = self.inherited_for_element(container, inherited);
+        if container.tag_name().name() == "svg" && container.parent_element().is_some() {
+            inherited.transform = self
+                .nested_svg_transform(container)
+                .then(&inherited.transform);
+        }
+        for child in container.children().filter(|node| node.is_element()) {
+            match child.tag_name().name() {
+                "svg" | "g" => self.import_container(child, &inherited),
+                "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => {
+                    self.import_shape(child, &inherited)
+                }
+                // Non-rendering elements can be safely ignored.
+                "title" | "desc" | "metadata" => {}
+                other => self
+                    .warnings
+                    .push(ImportWarning::UnsupportedElement(other.to_string())),
+            }
+        }
+    }
+
+    fn import_shape(&mut self, element: roxmltree::Node, inherited: &Inherited) {
+        let inherited = self.inherited_for_element(element, inherited);
+        if let Some(geometry) = self.geometry_for_element(element) {
+            let shape = Shape::new(geometry, inherited.style());
+            self.shapes
+                .push(shape.with_transform(inherited.transform.clone()));
+        }
+    }
+
+    // The transform from the user space of a nested <svg> to that of its
+    // parent. The viewBox, if any, is fitted into the viewport given by x,
+    // y, width, and height, either centered and scaled uniformly as with
+    // the default preserveAspectRatio of "xMidYMid meet" or, for "none",
+    // stretched to fill it. We do not clip to the viewport.
+    fn nested_svg_transform(&mut self, element: roxmltree::Node) -> Transform {
+        let x = self.optional_length(element, "x").unwrap_or(0.0);
+        let y = self.optional_length(element, "y").unwrap_or(0.0);
+        let offset = Transform::translation(x, y);
+        let Some(view_box) = element.attribute("viewBox") else {
+            return offset;
+        };
+        // Without a width and height the viewport is a percentage of one
+        // that we do not know.
+        let size = |attribute| {
+            element
+                .attribute(attribute)
+                .and_then(parse_length)
+                .filter(|length| 0.0 < *length)
+        };
+        let (Some([min_x, min_y, view_width, view_height]), Some(width), Some(height)) =
+            (parse_view_box(view_box), size("width"), size("height"))
+        else {
+            self.push_unsupported_value(element, "viewBox", view_box);
+            return offset;
+        };
+        let (scale_x, scale_y) = (width / view_width, height / view_height);
+        let stretch = match element.attribute("preserveAspectRatio").map(str::trim) {
+            None | Some("xMidYMid") | Some("xMidYMid meet") => false,
+            Some("none") => true,
+            Some(value) => {
+                self.push_unsupported_value(element, "preserveAspectRatio", value);
+                false
+            }
+        };
+        if stretch {
+            return Transform::new(
+                scale_x,
+                0.0,
+                0.0,
+                scale_y,
+                x - min_x * scale_x,
+                y - min_y * scale_y,
+            );
+        }
+        let scale = scale_x.min(scale_y);
+        Transform::new(
+            scale,
+            0.0,
+            0.0,
+            scale,
+            x + (width - view_width * scale) / 2.0 - min_x * scale,
+            y + (height - view_height * scale) / 2.0 - min_y * scale,
+        )
+    }
+
+    // Map the element onto a geometry. Returns None (with a warning) if the
+    // element cannot be represented.
+    fn geometry_for_element(&mut self, element: roxmltree::Node) -> Option<Geometry> {
+        match element.tag_name().name() {
+            "rect" => {
+                let x = self.optional_length(element, "x")?;
+                let y = self.optional_length(element, "y")?;
+                let width = self.required_size(element, "width")?;
+                let height = self.required_size(element, "height")?;
+                // We have no rounded rectangles, so rounded corners come
+                // out square.
+                for attribute in ["rx", "ry"] {
+                    if let Some(value) = element.attribute(attribute)
+                        && parse_length(value) != Some(0.0)
+                    {
+                        self.push_unsupported_value(element, attribute, value);
+                    }
+                }
+                Some(Geometry::rectangle(x, y, width, height))
+            }
+            "circle" => {
+                let cx = self.optional_length(element, "cx")?;
+                let cy = self.optional_length(element, "cy")?;
+                let r = self.required_size(element, "r")?;
+                Some(Geometry::circle(cx, cy, r))
+            }
+            "ellipse" => {
+                let cx = self.optional_length(element, "cx")?;
+                let cy = self.optional_length(element, "cy")?;
+                let rx = self.required_size(element, "rx")?;
+                let ry = self.required_size(element, "ry")?;
+                Some(Geometry::ellipse(cx, cy, rx, ry))
+            }
+            "line" => {
+                let x1 = self.optional_length(element, "x1")?;
+                let y1 = self.optional_length(element, "y1")?;
+                let x2 = self.optional_length(element, "x2")?;
+                let y2 = self.optional_length(element, "y2")?;
+                Some(Geometry::line(x1, y1, x2, y2))
+            }
+            "polyline" => {
+                let points = self.required_points(element)?;
+                Some(Geometry::Polyline { points })
+            }
+            "polygon" => {
+                let points = self.required_points(element)?;
+                Some(Geometry::Polygon { points })
+            }
+            other => {
+                self.warnings
+                    .push(ImportWarning::UnsupportedElement(other.to_string()));
+                None
+            }
+        }
+    }
+
+    // Apply an element's presentation attributes to what it inherits.
+    // Attributes that are missing or that we cannot interpret leave the
+    // inherited values alone.
+    fn inherited_for_element(
+        &mut self,
+        element: roxmltree::Node,
+        inherited: &Inherited,
+    ) -> Inherited {
+        let mut result = inherited.clone();
+        self.apply_attribute(element, "fill", parse_paint, &mut result.fill);
+        self.apply_attribute(
+            element,
+            "fill-opacity",
+            parse_opacity,
+            &mut result.fill_opacity,
+        );
+        self.apply_attribute(element, "stroke", parse_paint, &mut result.stroke);
+        self.apply_attribute(
+            element,
+            "stroke-opacity",
+            parse_opacity,
+            &mut result.stroke_opacity,
+        );
+        self.apply_attribute(
+            element,
+            "stroke-width",
+            parse_stroke_width,
+            &mut result.stroke_width,
+        );
+        self.apply_attribute(
+            element,
+            "stroke-dasharray",
+            parse_dash_array,
+            &mut result.dash_array,
+        );
+        self.apply_attribute(
+            element,
+            "stroke-linejoin",
+            parse_line_join,
+            &mut result.line_join,
+        );
+        self.apply_attribute(
+            element,
+            "stroke-linecap",
+            parse_line_cap,
+            &mut result.line_cap,
+        );
+        let mut transform = Transform::identity();
+        self.apply_attribute(element, "transform", parse_transform, &mut transform);
+        result.transform = transform.then(&inherited.transform);
+        result
+    }
+
+    // Parse an attribute into target if it is specified. If it cannot be
+    // parsed, we record a warning and leave target alone.
+    fn apply_attribute<T>(
+        &mut self,
+        element: roxmltree::Node,
+        attribute: &str,
+        parse: fn(&str) -> Option<T>,
+        target: &mut T,
+    ) {
+        if let Some(value) = self.specified_attribute(element, attribute) {
+            match parse(value.trim()) {
+                Some(parsed) => *target = parsed,
+                None => self.push_unsupported_value(element, attribute, value),
+            }
+        }
+    }
+
+    // A presentation attribute's value unless it is missing or explicitly
+    // inherited. Declarations in the style attribute -- which is how many
+    // editors write paint -- take precedence over the attribute itself, as
+    // they do in CSS.
+    fn specified_attribute<'a>(
+        &self,
+        element: roxmltree::Node<'a, '_>,
+        attribute: &str,
+    ) -> Option<&'a str> {
+        style_declaration(element, attribute)
+            .or_else(|| element.attribute(attribute))
+            .filter(|value| value.trim() != "inherit")
+    }
+
+    // A length that defaults to zero if it is missing. We return None if
+    // it is present but cannot be interpreted.
+    fn optional_length(&mut self, element: roxmltree::Node, attribute: &str) -> Option<f64> {
+        match element.attribute(attribute) {
+            None => Some(0.0),
+            Some(_) => self.required_length(element, attribute),
+        }
+    }
+
+    // A length that must be present and must be a plain number (or a
+    // number of pixels). We return None after recording a warning if not.
+    fn required_length(&mut self, element: roxmltree::Node, attribute: &str) -> Option<f64> {
+        let Some(value) = element.attribute(attribute) else {
+            self.warnings.push(ImportWarning::MissingAttribute {
+                element: element.tag_name().name().to_string(),
+                attribute: attribute.to_string(),
+            });
+            return None;
+        };
+        let length = parse_length(value);
+        if length.is_none() {
+            self.push_unsupported_value(element, attribute, value);
+        }
+        length
+    }
+
+    // A width, height, or radius. SVG treats negative values as errors, so
+    // we skip the element with a warning for those as well.
+    fn required_size(&mut self, element: roxmltree::Node, attribute: &str) -> Option<f64> {
+        let size = self.required_length(element, attribute)?;
+        if size < 0.0 {
+            let value = element.attribute(attribute).unwrap_or_default();
+            self.push_unsupported_value(element, attribute, value);
+            return None;
+        }
+        Some(size)
+    }
+
+    // The points attribute of a polyline or polygon: pairs of numbers
+    // separated by whitespace and/or commas. We return None after
+    // recording a warning if it is missing or malformed.
+    fn required_points(&mut self, element: roxmltree::Node) -> Option<Vec<XYPoint>> {
+        let Some(value) = element.attribute("points") else {
+            self.warnings.push(ImportWarning::MissingAttribute {
+                element: element.tag_name().name().to_string(),
+                attribute: "points".to_string(),
+            });
+            return None;
+        };
+        let numbers: Result<Vec<f64>, _> = value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|number| !number.is_empty())
+            .map(|number| number.parse::<f64>())
+            .collect();
+        match numbers {
+            Ok(numbers) if numbers.len() % 2 == 0 && numbers.iter().all(|n| n.is_finite()) => Some(
+                numbers
+                    .chunks(2)
+                    .map(|pair| XYPoint::new(pair[0], pair[1]))
+                    .collect(),
+            ),
+            _ => {
+                self.push_unsupported_value(element, "points", value);
+                None
+            }
+        }
+    }
+
+    fn push_unsupported_value(&mut self, element: roxmltree::Node, attribute: &str, value: &str) {
+        self.warnings.push(ImportWarning::UnsupportedValue {
+            element: element.tag_name().name().to_string(),
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+        });
+    }
+}
+
+// The value of the last declaration of a property in an element's style
+// attribute, if any. Declarations are separated by semicolons and we drop
+// any "!important" since there is nothing for it to override.
+
+fn style_declaration<'a>(element: roxmltree::Node<'a, '_>, property: &str) -> Option<&'a str> {
+    element
+        .attribute("style")?
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .filter(|(name, _)| name.trim() == property)
+        .map(|(_, value)| {
+            let value = value.trim();
+            value.strip_suffix("!important").unwrap_or(value).trim()
+        })
+        .next_back()
+}
+
+// A viewBox is four numbers -- the left, top, width, and height --
+// separated by whitespace and/or commas. The width and height must be
+// positive.
+
+fn parse_view_box(value: &str) -> Option<[f64; 4]> {
+    let numbers: Vec<f64> = value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|number| !number.is_empty())
+        .map(|number| number.parse::<f64>().ok().filter(|n| n.is_finite()))
+        .collect::<Option<Vec<f64>>>()?;
+    match numbers.as_slice() {
+        [left, top, width, height] if 0.0 < *width && 0.0 < *height => {
+            Some([*left, *top, *width, *height])
+        }
+        _ => None,
+    }
+}
+
+// A plain number or a number of pixels.
+
+fn parse_length(value: &str) -> Option<f64> {
+    let number = value.trim();
+    let number = number.strip_suffix("px").unwrap_or(number);
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|length| length.is_finite())
+}
+
+// A paint is either "none" or a color. We do not support gradients,
+// patterns, or currentColor.
+
+fn parse_paint(value: &str) -> Option<Option<Color>> {
+    match value {
+        "none" => Some(None),
+        _ => Color::from_css(value).map(Some),
+    }
+}
+
+// An opacity is a number or a percentage, clamped to [0, 1].
+
+fn parse_opacity(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let opacity = match value.strip_suffix('%') {
+        Some(percentage) => percentage.parse::<f64>().ok()? / 100.0,
+        None => value.parse::<f64>().ok()?,
+    };
+    opacity.is_finite().then(|| opacity.clamp(0.0, 1.0))
+}
+
+fn parse_stroke_width(value: &str) -> Option<f64> {
+    parse_length(value).filter(|width| 0.0 <= *width)
+}
+
+// A dash array is "none" or non-negative lengths separated by whitespace
+// and/or commas.
+
+fn parse_dash_array(value: &str) -> Option<Vec<f64>> {
+    if value == "none" {
+        return Some(Vec::new());
+    }
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|length| !length.is_empty())
+        .map(|length| parse_length(length).filter(|length| 0.0 <= *length))
+        .collect()
+}
+
+fn parse_line_join(value: &str) -> Option<LineJoin> {
+    match value {
+        "miter" => Some(LineJoin::Miter),
+        "round" => Some(LineJoin::Round),
+        "bevel" => Some(LineJoin::Bevel),
+        _ => None,
+    }
+}
+
+fn parse_line_cap(value: &str) -> Option<LineCap> {
+    match value {
+        "butt" => Some(LineCap::Butt),
+        "round" => Some(LineCap::Round),
+        "square" => Some(LineCap::Square),
+        _ => None,
+    }
+}
+
+// A transform list is a sequence of transform functions separated by
+// whitespace and/or commas. The rightmost function applies first. Angles
+// are in degrees.
+
+fn parse_transform(value: &str) -> Option<Transform> {
+    let mut transform = Transform::identity();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        let (name, after_name) = rest.split_once('(')?;
+        let (arguments, after_arguments) = after_name.split_once(')')?;
+        let numbers: Vec<f64> = arguments
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|number| !number.is_empty())
+            .map(|number| number.parse::<f64>().ok().filter(|n| n.is_finite()))
+            .collect::<Option<Vec<f64>>>()?;
+        let function = match (name.trim(), numbers.as_slice()) {
+            ("matrix", [a, b, c, d, e, f]) => Transform::new(*a, *b, *c, *d, *e, *f),
+            ("translate", [dx]) => Transform::translation(*dx, 0.0),
+            ("translate", [dx, dy]) => Transform::translation(*dx, *dy),
+            ("scale", [scale]) => Transform::scaling(*scale, *scale),
+            ("scale", [sx, sy]) => Transform::scaling(*sx, *sy),
+            ("rotate", [angle]) => Transform::rotation(angle.to_radians()),
+            ("rotate", [angle, cx, cy]) => {
+                Transform::rotation(angle.to_radians()).about(&XYPoint::new(*cx, *cy))
+            }
+            ("skewX", [angle]) => Transform::skew_x(angle.to_radians()),
+            ("skewY", [angle]) => Transform::skew_y(angle.to_radians()),
+            _ => return None,
+        };
+        transform = function.then(&transform);
+        rest = after_arguments.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+    Some(transform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(content: &str) -> SvgImport {
+        let svg = format!(r#"<svg xmlns="http://www.w3.org/2000/svg">{content}</svg>"#);
+        match document_from_svg(&svg) {
+            Ok(import) => import,
+            Err(err) => panic!("failed to import {content}: {err}"),
+        }
+    }
+
+    fn shapes(import: &SvgImport) -> Vec<Shape> {
+        import
+            .doc
+            .shape_id_shapes_iter()
+            .map(|(_, shape)| shape.clone())
+            .collect()
+    }
+
+    fn warnings(import: &SvgImport) -> Vec<String> {
+        import
+            .warnings
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect()
+    }
+
+    // Import content that should make exactly one shape without warnings.
+    fn import_one(content: &str) -> Shape {
+        let import = import(content);
+        assert_eq!(warnings(&import), Vec::<String>::new(), "{content}");
+        let mut shapes = shapes(&import);
+        assert_eq!(shapes.len(), 1, "{content}");
+        shapes.remove(0)
+    }
+
+    fn points(coordinates: &[(f64, f64)]) -> Vec<XYPoint> {
+        coordinates
+            .iter()
+            .map(|(x, y)| XYPoint::new(*x, *y))
+            .collect()
+    }
+
+    #[test]
+    fn imports_each_element() {
+        let cases = [
+            (
+                r#"<rect x="1" y="2" width="3" height="4"/>"#,
+                Geometry::rectangle(1.0, 2.0, 3.0, 4.0),
+            ),
+            (
+                r#"<rect width="3px" height="4" rx="0"/>"#,
+                Geometry::rectangle(0.0, 0.0, 3.0, 4.0),
+            ),
+            (
+                r#"<circle cx="5" cy="6" r="7"/>"#,
+                Geometry::circle(5.0, 6.0, 7.0),
+            ),
+            (
+                r#"<ellipse cx="5" cy="6" rx="7" ry="8"/>"#,
+                Geometry::ellipse(5.0, 6.0, 7.0, 8.0),
+            ),
+            (
+                r#"<line x1="1" y1="2" x2="3" y2="4"/>"#,
+                Geometry::line(1.0, 2.0, 3.0, 4.0),
+            ),
+            (
+                r#"<polyline points="0,0 10,0 10 10"/>"#,
+                Geometry::Polyline {
+                    points: points(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]),
+                },
+            ),
+            (
+                r#"<polygon points="0 0, 10 0, 10 10"/>"#,
+                Geometry::Polygon {
+                    points: points(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]),
+                },
+            ),
+        ];
+        for (content, geometry) in cases {
+            let shape = import_one(content);
+            assert!(shape.geometry == geometry, "{content}");
+            assert!(shape.style == Style::new(Color::BLACK), "{content}");
+            assert!(shape.transform.is_identity(), "{content}");
+        }
+    }
+
+    #[test]
+    fn imports_shapes_in_document_order() {
+        let import =
+            import(r#"<title>Two</title><g><circle r="1"/></g><rect width="1" height="1"/>"#);
+        assert_eq!(warnings(&import), Vec::<String>::new());
+        let shapes = shapes(&import);
+        assert!(shapes[0].geometry == Geometry::circle(0.0, 0.0, 1.0));
+        assert!(shapes[1].geometry == Geometry::rectangle(0.0, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn inherits_presentation_attributes() {
+        let stroke = |color: Color, width: f64| Some(Stroke::new(color, width));
+        let dashed = Stroke {
+            dash_array: vec![1.0, 2.0],
+            line_join: LineJoin::Round,
+            line_cap: LineCap::Square,
+            ..Stroke::new(Color::BLACK, 1.0)
+        };
+        let cases = [
+            (
+                r#"<g fill="red" stroke="blue" stroke-width="3"><rect width="1" height="1"/></g>"#,
+                Style {
+                    fill: Some(Color::RED),
+                    stroke: stroke(Color::BLUE, 3.0),
+                },
+            ),
+            (
+                r#"<g fill="red"><g><rect fill="inherit" width="1" height="1"/></g></g>"#,
+                Style::new(Color::RED),
+            ),
+            (
+                r#"<g fill="red" stroke="blue"><rect fill="none" stroke="none" width="1" height="1"/></g>"#,
+                Style {
+                    fill: None,
+                    stroke: None,
+                },
+            ),
+            (
+                r##"<g fill-opacity="50%"><rect fill="#ff0000" width="1" height="1"/></g>"##,
+                Style::new(Color::RED.with_opacity(0.5)),
+            ),
+            (
+                r#"<g stroke-opacity="0.25" stroke-width="2"><line stroke="blue" x2="1"/></g>"#,
+                Style {
+                    fill: Some(Color::BLACK),
+                    stroke: stroke(Color::BLUE.with_opacity(0.25), 2.0),
+                },
+            ),
+            (
+                r#"<rect stroke="black" stroke-dasharray="1, 2" stroke-linejoin="round" stroke-linecap="square" width="1" height="1"/>"#,
+                Style {
+                    fill: Some(Color::BLACK),
+                    stroke: Some(dashed.clone()),
+                },
+            ),
+            // Style declarations beat attributes and are inherited like
+            // them.
+            (
+                r##"<rect fill="red" style="fill: #0000ff; stroke:red ;stroke-width: 2 !important" width="1" height="1"/>"##,
+                Style {
+                    fill: Some(Color::BLUE),
+                    stroke: stroke(Color::RED, 2.0),
+                },
+            ),
+            (
+                r#"<g style="fill:blue;stroke-dasharray:1 2;stroke:black;stroke-linejoin:round"><rect stroke-linecap="square" width="1" height="1"/></g>"#,
+                Style {
+                    fill: Some(Color::BLUE),
+                    stroke: Some(dashed),
+                },
+            ),
+        ];
+        for (content, style) in cases {
+            assert!(import_one(content).style == style, "{content}");
+        }
+    }
+
+    #[test]
+    fn applies_transform_lists_and_nested_viewports() {
+        let degrees = |angle: f64| angle.to_radians();
+        let cases = [
+            (
+                r#"<rect transform="translate(10)" width="1" height="1"/>"#,
+                Transform::translation(10.0, 0.0),
+            ),
+            (
+                r#"<rect transform="translate(10, 20) scale(2)" width="1" height="1"/>"#,
+                Transform::scaling(2.0, 2.0).then(&Transform::translation(10.0, 20.0)),
+            ),
+            (
+                r#"<rect transform="scale(2 3)" width="1" height="1"/>"#,
+                Transform::scaling(2.0, 3.0),
+            ),
+            (
+                r#"<rect transform="rotate(90 5 5)" width="1" height="1"/>"#,
+                Transform::rotation(degrees(90.0)).about(&XYPoint::new(5.0, 5.0)),
+            ),
+            (
+                r#"<rect transform="matrix(1 2 3 4 5 6)" width="1" height="1"/>"#,
+                Transform::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0),
+            ),
+            (
+                r#"<rect transform="skewX(45),skewY(30)" width="1" height="1"/>"#,
+                Transform::skew_y(degrees(30.0)).then(&Transform::skew_x(degrees(45.0))),
+            ),
+            (
+                r#"<g transform="translate(10 0)"><rect transform="scale(2)" width="1" height="1"/></g>"#,
+                Transform::scaling(2.0, 2.0).then(&Transform::translation(10.0, 0.0)),
+            ),
+            // A nested <svg> moves its contents to x and y and fits its view
+            // box into its width and height.
+            (
+                r#"<svg x="10" y="20"><rect width="1" height="1"/></svg>"#,
+                Transform::translation(10.0, 20.0),
+            ),
+            (
+                r#"<svg x="10" width="100" height="50" viewBox="0 0 10 10"><rect width="1" height="1"/></svg>"#,
+                Transform::new(5.0, 0.0, 0.0, 5.0, 35.0, 0.0),
+            ),
+            (
+                r#"<svg x="10" width="100" height="50" viewBox="5,5,10,10" preserveAspectRatio="none"><rect width="1" height="1"/></svg>"#,
+                Transform::new(10.0, 0.0, 0.0, 5.0, -40.0, -25.0),
+            ),
+            (
+                r#"<g transform="translate(1 2)"><svg x="10"><rect transform="scale(2)" width="1" height="1"/></svg></g>"#,
+                Transform::scaling(2.0, 2.0)
+                    .then(&Transform::translation(10.0, 0.0))
+                    .then(&Transform::translation(1.0, 2.0)),
+            ),
+        ];
+        for (content, transform) in cases {
+            assert_eq!(import_one(content).transform, transform, "{content}");
+        }
+    }
+
+    #[test]
+    fn warns_about_what_it_cannot_import() {
+        // Each case has the warnings and the number of shapes imported.
+        let cases: [(&str, &[&str], usize); 15] = [
+            (
+                r#"<text>Hello</text>"#,
+                &["unsupported element <text> was skipped"],
+                0,
+            ),
+            (
+                r#"<g><path d="M 0 0"/><circle r="1"/></g>"#,
+                &["unsupported element <path> was skipped"],
+                1,
+            ),
+            (
+                r#"<rect width="1"/>"#,
+                &["<rect> without height was skipped"],
+                0,
+            ),
+            (
+                r#"<polygon/>"#,
+                &["<polygon> without points was skipped"],
+                0,
+            ),
+            (
+                r#"<rect width="1em" height="1"/>"#,
+                &[r#"<rect> has unsupported width="1em""#],
+                0,
+            ),
+            (
+                r#"<rect width="-1" height="1"/>"#,
+                &[r#"<rect> has unsupported width="-1""#],
+                0,
+            ),
+            (
+                r#"<circle r="-2"/>"#,
+                &[r#"<circle> has unsupported r="-2""#],
+                0,
+            ),
+            (
+                r#"<ellipse rx="1" ry="-2"/>"#,
+                &[r#"<ellipse> has unsupported ry="-2""#],
+                0,
+            ),
+            (
+                r#"<polyline points="0 0 1"/>"#,
+                &[r#"<polyline> has unsupported points="0 0 1""#],
+                0,
+            ),
+            (
+                r#"<rect width="1" height="1" rx="2" ry="3"/>"#,
+                &[
+                    r#"<rect> has unsupported rx="2""#,
+                    r#"<rect> has unsupported ry="3""#,
+                ],
+                1,
+            ),
+            (
+                r#"<rect width="1" height="1" fill="url(#gradient)"/>"#,
+                &[r##"<rect> has unsupported fill="url(#gradient)""##],
+                1,
+            ),
+            (
+                r#"<rect width="1" height="1" style="stroke: currentColor"/>"#,
+                &[r#"<rect> has unsupported stroke="currentColor""#],
+                1,
+            ),
+            (
+                r#"<rect width="1" height="1" transform="spin(3)"/>"#,
+                &[r#"<rect> has unsupported transform="spin(3)""#],
+                1,
+            ),
+            (
+                r#"<svg viewBox="0 0 10 10"><rect width="1" height="1"/></svg>"#,
+                &[r#"<svg> has unsupported viewBox="0 0 10 10""#],
+                1,
+            ),
+            (
+                r#"<svg width="10" height="10" viewBox="0 0 10 10" preserveAspectRatio="xMinYMin slice"><rect width="1" height="1"/></svg>"#,
+                &[r#"<svg> has unsupported preserveAspectRatio="xMinYMin slice""#],
+                1,
+            ),
+        ];
+        for (content, expected, shape_count) in cases {
+            let import = import(content);
+            assert_eq!(warnings(&import), expected, "{content}");
+            assert_eq!(shapes(&import).len(), shape_count, "{content}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_xml() {
+        assert!(matches!(
+            document_from_svg("<svg><rect></svg>"),
+            Err(DocError::MalformedSvg(_))
+        ));
+    }
+}
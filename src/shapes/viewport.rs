@@ -0,0 +1,88 @@
+// The viewport determines which part of the document the canvas shows and
+// at what magnification. It maps document coordinates to the screen
+// coordinates of the canvas by scaling by the zoom and then shifting by
+// the pan:
+//
+//   screen = document * zoom + pan
+//
+// Anything measured in screen pixels -- handle sizes, click tolerances --
+// has to be divided by the zoom to get the size in the document.
+
+use crate::shapes::core::{Transform, XYPoint};
+
+// The zoom stays within these limits so that the document neither
+// vanishes nor turns into a blur of a single pixel.
+const MIN_ZOOM: f64 = 0.05;
+const MAX_ZOOM: f64 = 64.0;
+
+#[derive(Clone, PartialEq)]
+pub struct Viewport {
+    zoom: f64,
+    pan: XYPoint,
+}
+
+impl Viewport {
+    // The identity viewport shows the document at actual size with its
+    // origin at the origin of the canvas.
+    pub fn new_identity() -> Self {
+        Self {
+            zoom: 1.0,
+            pan: XYPoint::new(0.0, 0.0),
+        }
+    }
+
+    pub fn zoom(&self) -> f64 {
+        self.zoom
+    }
+
+    // The transform from document coordinates to screen coordinates.
+    pub fn transform(&self) -> Transform {
+        Transform::scaling(self.zoom, self.zoom)
+            .then(&Transform::translation(self.pan.x, self.pan.y))
+    }
+
+    pub fn to_screen(&self, point: &XYPoint) -> XYPoint {
+        XYPoint::new(
+            point.x * self.zoom + self.pan.x,
+            point.y * self.zoom + self.pan.y,
+        )
+    }
+
+    pub fn to_document(&self, screen_point: &XYPoint) -> XYPoint {
+        XYPoint::new(
+            (screen_point.x - self.pan.x) / self.zoom,
+            (screen_point.y - self.pan.y) / self.zoom,
+        )
+    }
+
+    // Convert a length in screen pixels to a length in the document.
+    pub fn document_length(&self, screen_length: f64) -> f64 {
+        screen_length / self.zoom
+    }
+
+    // Zoom by a factor while keeping the document point under a screen
+    // point in place -- e.g., zooming around the mouse. The zoom is
+    // clamped to its limits.
+    pub fn zoomed_around(&self, screen_point: &XYPoint, factor: f64) -> Self {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        if !zoom.is_finite() {
+            return self.clone();
+        }
+        let anchor = self.to_document(screen_point);
+        Self {
+            zoom,
+            pan: XYPoint::new(
+                screen_point.x - anchor.x * zoom,
+                screen_point.y - anchor.y * zoom,
+            ),
+        }
+    }
+
+    // Shift the document on screen by a distance in screen pixels.
+    pub fn panned_by(&self, screen_delta: &XYPoint) -> Self {
+        Self {
+            zoom: self.zoom,
+            pan: self.pan.add(screen_delta),
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use crate::shapes::doc::ShapeId;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
+use std::vec::Vec;
+
+// The display order of the shapes in a document from bottom to top. Each
+// shape has a key and the keys increase from bottom to top, so comparing
+// the positions of two shapes takes a pair of hash lookups and adding or
+// removing a shape takes logarithmic time rather than a scan of the whole
+// sequence.
+//
+// Keys are sequences of digits compared digit by digit, like the digits
+// after a decimal point, so there is always room for another key between
+// any two. That means that placing a shape never changes the keys of the
+// other shapes, and a change to the order is described completely by the
+// old and new keys of the shapes that moved. The history relies on this
+// to record z-order changes without copying whole sequences.
+
+#[derive(Clone)]
+pub(crate) struct ZOrder {
+    keys: HashMap<ShapeId, Key>,
+    shape_ids: BTreeMap<Key, ShapeId>,
+}
+
+// A position in a z-order. Keys only mean something relative to other keys
+// from the same order: lower keys are further down. No key ends in a zero
+// digit since nothing would fit between it and the same key without the
+// zero.
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+pub(crate) struct Key(Vec<u16>);
+
+// Digits run from 0 up to (but not including) DIGIT_RANGE. The first key
+// goes in the middle of the range so that there is room on either side.
+const DIGIT_RANGE: u32 = 1 << 16;
+const FIRST_DIGIT: u16 = 1 << 15;
+
+impl ZOrder {
+    pub fn new_empty() -> Self {
+        Self {
+            keys: HashMap::new(),
+            shape_ids: BTreeMap::new(),
+        }
+    }
+
+    // Build the order from a sequence from bottom to top. Later duplicates
+    // are ignored.
+    pub fn new_from_sequence<'a>(sequence: impl IntoIterator<Item = &'a ShapeId>) -> Self {
+        let mut z_order = Self::new_empty();
+        for shape_id in sequence {
+            z_order.push_top(*shape_id);
+        }
+        z_order
+    }
+
+    pub fn contains(&self, shape_id: &ShapeId) -> bool {
+        self.keys.contains_key(shape_id)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &ShapeId> + '_ {
+        self.shape_ids.values()
+    }
+
+    pub fn to_vec(&self) -> Vec<ShapeId> {
+        self.iter().copied().collect()
+    }
+
+    // Add a shape at the top unless it is already present.
+    pub fn push_top(&mut self, shape_id: ShapeId) {
+        if self.contains(&shape_id) {
+            return;
+        }
+        let key = key_between(self.shape_ids.keys().next_back(), None);
+        self.insert(key, shape_id);
+    }
+
+    // Add a shape at the bottom unless it is already present.
+    pub fn push_bottom(&mut self, shape_id: ShapeId) {
+        if self.contains(&shape_id) {
+            return;
+        }
+        let key = key_between(None, self.shape_ids.keys().next());
+        self.insert(key, shape_id);
+    }
+
+    // Put a shape at a key -- e.g., one recorded earlier -- replacing its
+    // current key if it has one. The key must not belong to another shape.
+    pub fn insert_with_key(&mut self, shape_id: ShapeId, key: Key) {
+        self.remove(&shape_id);
+        self.insert(key, shape_id);
+    }
+
+    // Put shapes in the place of one that is present, keeping their order
+    // -- e.g., when grouping or ungrouping. The first takes over its key and
+    // the rest go between that and the next key up. Replacements that are
+    // already present elsewhere move. Does nothing if the shape is not
+    // present.
+    pub fn replace(&mut self, shape_id: &ShapeId, replacements: &[ShapeId]) {
+        let Some(key) = self.keys.get(shape_id).cloned() else {
+            return;
+        };
+        let above = self
+            .shape_ids
+            .range((Bound::Excluded(&key), Bound::Unbounded))
+            .next()
+            .map(|(key, _)| key.clone());
+        let mut keys = keys_between(
+            Some(&key),
+            above.as_ref(),
+            replacements.len().saturating_sub(1),
+        );
+        keys.insert(0, key);
+        self.remove(shape_id);
+        for (key, replacement) in keys.into_iter().zip(replacements) {
+            self.insert_with_key(*replacement, key);
+        }
+    }
+
+    // Remove a shape. Returns false if it was not present.
+    pub fn remove(&mut self, shape_id: &ShapeId) -> bool {
+        match self.keys.remove(shape_id) {
+            Some(key) => {
+                self.shape_ids.remove(&key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The moves below take sets of shapes, ignore any shapes that are not
+    // present, and keep the relative order of the shapes that move. Only
+    // the keys of the shapes that move change.
+
+    pub fn move_to_top(&mut self, shape_ids: &HashSet<ShapeId>) {
+        for shape_id in self.sorted(shape_ids.iter().copied()) {
+            self.remove(&shape_id);
+            self.push_top(shape_id);
+        }
+    }
+
+    pub fn move_to_bottom(&mut self, shape_ids: &HashSet<ShapeId>) {
+        for shape_id in self.sorted(shape_ids.iter().copied()).into_iter().rev() {
+            self.remove(&shape_id);
+            self.push_bottom(shape_id);
+        }
+    }
+
+    // Move each shape up past the shape just above it unless that shape is
+    // moving too. Shapes already at the top stay put, and so do the moving
+    // shapes directly below them.
+    pub fn move_forward(&mut self, shape_ids: &HashSet<ShapeId>) {
+        for shape_id in self.sorted(shape_ids.iter().copied()).into_iter().rev() {
+            let key = &self.keys[&shape_id];
+            let mut above = self
+                .shape_ids
+                .range((Bound::Excluded(key), Bound::Unbounded));
+            if let Some((above_key, above_id)) = above.next()
+                && !shape_ids.contains(above_id)
+            {
+                let new_key = key_between(Some(above_key), above.next().map(|(key, _)| key));
+                self.insert_with_key(shape_id, new_key);
+            }
+        }
+    }
+
+    // The mirror image of move_forward.
+    pub fn move_backward(&mut self, shape_ids: &HashSet<ShapeId>) {
+        for shape_id in self.sorted(shape_ids.iter().copied()) {
+            let key = &self.keys[&shape_id];
+            let mut below = self.shape_ids.range(..key);
+            if let Some((below_key, below_id)) = below.next_back()
+                && !shape_ids.contains(below_id)
+            {
+                let new_key = key_between(below.next_back().map(|(key, _)| key), Some(below_key));
+                self.insert_with_key(shape_id, new_key);
+            }
+        }
+    }
+
+    // Move the shapes so that the lowest of them ends up at the index in
+    // the sequence and the rest follow it directly. Indices past the end
+    // move the shapes to the top. Finding the index takes time proportional
+    // to it, so this is for the user's own reordering rather than for use
+    // inside other edits.
+    pub fn move_to_index(&mut self, shape_ids: &HashSet<ShapeId>, index: usize) {
+        let moving = self.sorted(shape_ids.iter().copied());
+        for shape_id in &moving {
+            self.remove(shape_id);
+        }
+        let Some(above_key) = self.shape_ids.keys().nth(index).cloned() else {
+            for shape_id in moving {
+                self.push_top(shape_id);
+            }
+            return;
+        };
+        let below_key = self.shape_ids.range(..&above_key).next_back();
+        let keys = keys_between(
+            below_key.map(|(key, _)| key),
+            Some(&above_key),
+            moving.len(),
+        );
+        for (key, shape_id) in keys.into_iter().zip(moving) {
+            self.insert(key, shape_id);
+        }
+    }
+
+    pub fn key(&self, shape_id: &ShapeId) -> Option<&Key> {
+        self.keys.get(shape_id)
+    }
+
+    fn insert(&mut self, key: Key, shape_id: ShapeId) {
+        self.keys.insert(shape_id, key.clone());
+        self.shape_ids.insert(key, shape_id);
+    }
+
+    // Sort shape ids from bottom to top, dropping any that are not present.
+    pub fn sorted(&self, shape_ids: impl IntoIterator<Item = ShapeId>) -> Vec<ShapeId> {
+        let mut keyed: Vec<(&Key, ShapeId)> = shape_ids
+            .into_iter()
+            .filter_map(|shape_id| self.keys.get(&shape_id).map(|key| (key, shape_id)))
+            .collect();
+        keyed.sort_unstable_by_key(|(key, _)| *key);
+        keyed.into_iter().map(|(_, shape_id)| shape_id).collect()
+    }
+}
+
+// A key strictly between two keys, where None is past the bottom or the
+// top. We go digit by digit until there is room between the digits of the
+// two keys. Once the new key is below the upper key in some digit, the
+// upper key no longer limits the digits after it. Keys for the ends step
+// one digit past the end key, which keeps the keys short when shapes are
+// added one after another; keys between two others split the difference
+// so that repeated insertions in one place use up digits slowly.
+
+fn key_between(below: Option<&Key>, above: Option<&Key>) -> Key {
+    let (below_digits, mut above_digits) = match (below, above) {
+        (None, None) => return Key(vec![FIRST_DIGIT]),
+        (below, above) => (
+            below.map_or(&[][..], |key| &key.0[..]),
+            above.map(|key| &key.0[..]),
+        ),
+    };
+    let mut digits = Vec::new();
+    for index in 0.. {
+        let low = below_digits.get(index).map_or(0, |digit| u32::from(*digit));
+        // The upper key has a digit here as long as the lower key matches
+        // it so far, since the lower key is below it.
+        let high = above_digits.map_or(DIGIT_RANGE, |digits| u32::from(digits[index]));
+        if 2 <= high - low {
+            let digit = match (below, above_digits) {
+                (_, None) if above.is_none() => low + 1,
+                (None, Some(_)) => high - 1,
+                _ => low + (high - low) / 2,
+            };
+            digits.push(digit as u16);
+            break;
+        }
+        digits.push(low as u16);
+        if high - low == 1 {
+            above_digits = None;
+        }
+    }
+    Key(digits)
+}
+
+// A number of increasing keys strictly between two keys. We bisect rather
+// than stepping from one end so that the keys grow with the logarithm of
+// the number of keys.
+
+fn keys_between(below: Option<&Key>, above: Option<&Key>, count: usize) -> Vec<Key> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let middle = key_between(below, above);
+    let below_count = (count - 1) / 2;
+    let mut keys = keys_between(below, Some(&middle), below_count);
+    let above_keys = keys_between(Some(&middle), above, count - 1 - below_count);
+    keys.push(middle);
+    keys.extend(above_keys);
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::doc::Document;
+
+    // Fresh shape ids, indexed from 1 to match the expectations below.
+    fn new_shape_ids(count: usize) -> Vec<ShapeId> {
+        let mut doc = Document::new_empty();
+        (0..=count).map(|_| doc.generate_shape_id()).collect()
+    }
+
+    fn assert_well_formed(key: &Key) {
+        assert!(key.0.last().is_some_and(|digit| *digit != 0), "{key:?}");
+    }
+
+    #[test]
+    fn keys_between_are_strictly_between() {
+        let mut below = key_between(None, None);
+        let mut above = key_between(Some(&below), None);
+        // Keep inserting just above the lower key so that the keys need
+        // more and more digits.
+        for _ in 0..100 {
+            let middle = key_between(Some(&below), Some(&above));
+            assert_well_formed(&middle);
+            assert!(
+                below < middle && middle < above,
+                "{below:?} {middle:?} {above:?}"
+            );
+            above = middle;
+        }
+        for _ in 0..100 {
+            let middle = key_between(Some(&below), Some(&above));
+            assert_well_formed(&middle);
+            assert!(
+                below < middle && middle < above,
+                "{below:?} {middle:?} {above:?}"
+            );
+            below = middle;
+        }
+        for _ in 0..100 {
+            let bottom = key_between(None, Some(&below));
+            assert_well_formed(&bottom);
+            assert!(bottom < below);
+            below = bottom;
+        }
+    }
+
+    #[test]
+    fn keys_between_are_increasing() {
+        let below = key_between(None, None);
+        let above = key_between(Some(&below), None);
+        let keys = keys_between(Some(&below), Some(&above), 1000);
+        assert_eq!(keys.len(), 1000);
+        assert!(below < keys[0] && keys[999] < above);
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+        keys.iter().for_each(assert_well_formed);
+    }
+
+    #[test]
+    fn replacements_take_the_place_of_the_shape() {
+        let all = new_shape_ids(6);
+        let mut z_order = ZOrder::new_from_sequence(&all[1..4]);
+        let below_key = z_order.key(&all[1]).cloned();
+        z_order.replace(&all[2], &[all[4], all[5], all[6]]);
+        assert_eq!(z_order.to_vec(), [1, 4, 5, 6, 3].map(|index| all[index]));
+        assert_eq!(z_order.key(&all[1]).cloned(), below_key);
+        assert!(!z_order.contains(&all[2]));
+        // Replacing with a shape from elsewhere in the order moves it.
+        z_order.replace(&all[4], &[all[3]]);
+        assert_eq!(z_order.to_vec(), [1, 3, 5, 6].map(|index| all[index]));
+        z_order.replace(&all[2], &[all[4]]);
+        assert_eq!(z_order.to_vec(), [1, 3, 5, 6].map(|index| all[index]));
+    }
+
+    type Move = fn(&mut ZOrder, &HashSet<ShapeId>);
+
+    #[test]
+    fn moves_only_change_the_keys_of_moving_shapes() {
+        let all = new_shape_ids(6);
+        let shape_ids = |indices: &[usize]| -> Vec<ShapeId> {
+            indices.iter().map(|&index| all[index]).collect()
+        };
+        let mut z_order = ZOrder::new_from_sequence(&all[1..]);
+        let moves: [(Move, &[usize], &[usize]); 5] = [
+            (ZOrder::move_to_top, &[2, 4], &[1, 3, 5, 6, 2, 4]),
+            (ZOrder::move_to_bottom, &[6, 4], &[6, 4, 1, 3, 5, 2]),
+            (ZOrder::move_forward, &[6, 3], &[4, 6, 1, 5, 3, 2]),
+            (ZOrder::move_backward, &[1, 2], &[4, 1, 6, 5, 2, 3]),
+            (
+                |z_order, ids| z_order.move_to_index(ids, 1),
+                &[4, 3],
+                &[1, 4, 3, 6, 5, 2],
+            ),
+        ];
+        for (move_ids, moving, expected) in moves {
+            let moving: HashSet<ShapeId> = shape_ids(moving).into_iter().collect();
+            let before = z_order.clone();
+            move_ids(&mut z_order, &moving);
+            assert_eq!(z_order.to_vec(), shape_ids(expected));
+            for shape_id in all[1..]
+                .iter()
+                .filter(|shape_id| !moving.contains(shape_id))
+            {
+                assert_eq!(z_order.key(shape_id), before.key(shape_id));
+            }
+        }
+    }
+}
@@ -1,16 +1,26 @@
+use crate::framework::shortcuts::{KeyChord, Shortcuts};
 use crate::framework::tracking;
-use sauron::{Application, Cmd, MouseEvent, Node, html};
+use futures::channel::mpsc;
+use sauron::wasm_bindgen::JsCast;
+use sauron::wasm_bindgen::closure::Closure;
+use sauron::wasm_bindgen_futures::JsFuture;
+use sauron::web_sys::{EventTarget, PointerEvent, console};
+use sauron::{Application, Cmd, InputEvent, KeyboardEvent, Node, Window, html};
+use web_sys::{HtmlElement, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement};
 
 use crate::shapes::app;
+use crate::shapes::core::XYPoint;
+use crate::shapes::svg_import::document_from_svg;
 
 // The structure of the code is based on The Elm Architecture (TEA) as
 // interpreted by the Sauron Framework. Naming (Model, Msg, update, view) is
 // standard.
 // https://guide.elm-lang.org/architecture/
 
-// The model consists of the app and the tracker information (if any)
+// The model consists of the app and the keyboard shortcuts that drive it.
 pub struct Model {
     app: app::Model,
+    shortcuts: Shortcuts<app::Msg>,
 }
 
 impl Model {
@@ -18,6 +28,7 @@ impl Model {
     pub fn new() -> Self {
         Self {
             app: app::Model::new(),
+            shortcuts: default_shortcuts(),
         }
     }
 }
@@ -25,23 +36,59 @@ impl Model {
 pub enum Msg {
     // Interface back to the tracker.
     ToApp(app::Msg),
-    // Mouse events for the tracker.
+    // Pointer events for the tracker.
     FromTracking(tracking::Event),
+    // Key down and key up events from the window.
+    KeyDown(KeyboardEvent),
+    KeyUp(KeyboardEvent),
+    // The window lost focus or the page was hidden, so keys may be released
+    // without our hearing about it.
+    LostFocus,
+    // Download the drawing as an SVG file.
+    DownloadSvg,
+    // The user chose an SVG file to open.
+    OpenSvgFile(InputEvent),
+    // The contents of the chosen SVG file if we could read it.
+    SvgFileLoaded(Option<String>),
 }
 
 //---- Message helpers
 
 impl Msg {
-    // Convert a mouse move event into a Msg.
-    fn track_mouse_move(evt: MouseEvent) -> Self {
+    // Convert a pointer move event into a Msg.
+    fn track_pointer_move(evt: PointerEvent) -> Self {
         evt.stop_propagation();
-        Self::FromTracking(tracking::Event::mouse_move(evt))
+        Self::FromTracking(tracking::Event::pointer_move(evt, app::CANVAS_ID))
     }
 
-    // Convert a mouse up event into a Msg
-    fn track_mouse_up(evt: MouseEvent) -> Self {
+    // Convert a pointer up event into a Msg
+    fn track_pointer_up(evt: PointerEvent) -> Self {
         evt.stop_propagation();
-        Self::FromTracking(tracking::Event::mouse_up(evt))
+        Self::FromTracking(tracking::Event::pointer_up(evt, app::CANVAS_ID))
+    }
+
+    // Convert a pointer cancel event into a Msg
+    fn track_pointer_cancel(evt: PointerEvent) -> Self {
+        evt.stop_propagation();
+        Self::FromTracking(tracking::Event::pointer_cancel(evt, app::CANVAS_ID))
+    }
+
+    // Convert a key down event into a Msg
+    fn key_down(evt: KeyboardEvent) -> Self {
+        Self::KeyDown(evt)
+    }
+
+    // Convert a key up event into a Msg
+    fn key_up(evt: KeyboardEvent) -> Self {
+        Self::KeyUp(evt)
+    }
+
+    // Convert a window resize into a Msg
+    fn window_resized(width: i32, height: i32) -> Self {
+        Self::ToApp(app::Msg::SetCanvasSize(XYPoint::new(
+            f64::from(width),
+            f64::from(height),
+        )))
     }
 
     // Apply routing to an app message
@@ -55,10 +102,28 @@ impl Msg {
 impl Application for Model {
     type MSG = Msg;
 
+    // Keyboard shortcuts apply no matter what has focus, so we listen
+    // to the window rather than to an element. The canvas is never larger
+    // than the window, so the size of the window bounds what it can show.
+    fn init(&mut self) -> Cmd<Msg> {
+        if let Some(size) = window_size() {
+            self.app.update(&app::Msg::SetCanvasSize(size));
+        }
+        Cmd::batch([
+            Window::on_resize(Msg::window_resized),
+            Window::on_keydown(Msg::key_down),
+            Window::on_keyup(Msg::key_up),
+            on_event(&sauron::window(), "blur", || Msg::LostFocus),
+            on_event(&sauron::document(), "visibilitychange", || Msg::LostFocus),
+        ])
+    }
+
     fn view(&self) -> Node<Msg> {
         use html::attributes::*;
         use html::*;
-        // Ugh. This consumes mouse move events even when we aren't
+        let outline_only = self.app.outline_only();
+        let zoom_percent = (self.app.zoom() * 100.0).round();
+        // Ugh. This consumes pointer move events even when we aren't
         // tracking.
         // Also ugh is that we really want app messages out of here
         // which fights with the containment hierarchy. Mapping and
@@ -73,10 +138,71 @@ impl Application for Model {
             vec![
                 class("canvas-tracker-div"),
                 id("canvas-tracking"),
-                events::on_mousemove(Msg::track_mouse_move),
-                events::on_mouseup(Msg::track_mouse_up),
+                tracking::on_pointermove(Msg::track_pointer_move),
+                tracking::on_pointerup(Msg::track_pointer_up),
+                tracking::on_pointercancel(Msg::track_pointer_cancel),
             ],
-            [self.app.view().map_msg(Msg::to_app)],
+            [
+                div(
+                    [class("toolbar")],
+                    [
+                        div([class("tools")], self.view_tool_buttons()),
+                        label(
+                            [],
+                            [
+                                input(
+                                    [
+                                        r#type("checkbox"),
+                                        checked(outline_only),
+                                        events::on_change(move |_| {
+                                            Msg::ToApp(app::Msg::SetOutlineOnly(!outline_only))
+                                        }),
+                                    ],
+                                    [],
+                                ),
+                                text(" Outline only"),
+                            ],
+                        ),
+                        div([class("grid-controls")], self.view_grid_controls()),
+                        // Show the zoom and reset it when clicked.
+                        button(
+                            [events::on_click(|_| Msg::ToApp(app::Msg::ResetView))],
+                            [text(format!("{zoom_percent}%"))],
+                        ),
+                        button(
+                            [events::on_click(|_| Msg::DownloadSvg)],
+                            [text("Download SVG")],
+                        ),
+                        label(
+                            [],
+                            [
+                                text("Open SVG "),
+                                input(
+                                    [
+                                        r#type("file"),
+                                        accept(".svg,image/svg+xml"),
+                                        events::on_change(Msg::OpenSvgFile),
+                                    ],
+                                    [],
+                                ),
+                            ],
+                        ),
+                    ],
+                ),
+                div(
+                    [class("workspace")],
+                    [
+                        self.app.view().map_msg(Msg::to_app),
+                        self.app.view_layers_panel().map_msg(Msg::to_app),
+                    ],
+                ),
+            ]
+            .into_iter()
+            .chain(
+                self.app
+                    .view_context_menu()
+                    .map(|menu| menu.map_msg(Msg::to_app)),
+            ),
         )
     }
 
@@ -84,13 +210,331 @@ impl Application for Model {
         match &msg {
             Msg::FromTracking(evt) => self.update_app(&app::Msg::from_tracking(evt)),
             Msg::ToApp(app_msg) => self.update_app(app_msg),
+            Msg::KeyDown(evt) => match self.app_msg_for_key_down(evt) {
+                Some(app_msg) => {
+                    evt.prevent_default();
+                    self.update_app(&app_msg)
+                }
+                None => Cmd::none(),
+            },
+            Msg::KeyUp(evt) => match app_msg_for_key_up(evt) {
+                Some(app_msg) => self.update_app(&app_msg),
+                None => Cmd::none(),
+            },
+            Msg::LostFocus => self.update_app(&app::Msg::SetSpaceHeld(false)),
+            Msg::DownloadSvg => {
+                download_text("drawing.svg", "image/svg+xml", &self.app.export_svg());
+                Cmd::none()
+            }
+            Msg::OpenSvgFile(evt) => read_chosen_file(evt, Msg::SvgFileLoaded),
+            Msg::SvgFileLoaded(opt_text) => {
+                match opt_text {
+                    Some(text) => self.open_svg(text),
+                    None => console::error_1(&"Could not read the SVG file".into()),
+                }
+                Cmd::none()
+            }
         }
     }
 }
 
 impl Model {
+    // One button per tool with the current tool marked as active
+    fn view_tool_buttons(&self) -> Vec<Node<Msg>> {
+        use html::attributes::*;
+        use html::*;
+
+        app::Tool::all()
+            .iter()
+            .map(|&tool| {
+                button(
+                    [
+                        classes_flag([("active", tool == self.app.tool())]),
+                        events::on_click(move |_| Msg::ToApp(app::Msg::SetTool(tool))),
+                    ],
+                    [text(tool.label())],
+                )
+            })
+            .collect()
+    }
+
+    // Checkboxes for showing and snapping to the grid and fields for its
+    // spacing and subdivisions. Entries that do not parse leave the grid
+    // as it is.
+    fn view_grid_controls(&self) -> Vec<Node<Msg>> {
+        use html::attributes::*;
+        use html::*;
+
+        let grid = self.app.grid();
+        let (visible, snap, spacing, subdivisions) =
+            (grid.visible, grid.snap, grid.spacing, grid.subdivisions);
+        vec![
+            label(
+                [],
+                [
+                    input(
+                        [
+                            r#type("checkbox"),
+                            checked(visible),
+                            events::on_change(move |_| {
+                                Msg::ToApp(app::Msg::SetGridVisible(!visible))
+                            }),
+                        ],
+                        [],
+                    ),
+                    text(" Grid"),
+                ],
+            ),
+            label(
+                [],
+                [
+                    input(
+                        [
+                            r#type("checkbox"),
+                            checked(snap),
+                            events::on_change(move |_| Msg::ToApp(app::Msg::SetSnapToGrid(!snap))),
+                        ],
+                        [],
+                    ),
+                    text(" Snap"),
+                ],
+            ),
+            label(
+                [],
+                [
+                    text("Spacing "),
+                    input(
+                        [
+                            r#type("number"),
+                            min(1),
+                            value(spacing),
+                            events::on_change(move |evt| {
+                                let spacing = evt.value().parse().unwrap_or(spacing);
+                                Msg::ToApp(app::Msg::SetGridSpacing(spacing))
+                            }),
+                        ],
+                        [],
+                    ),
+                ],
+            ),
+            label(
+                [],
+                [
+                    text("Subdivisions "),
+                    input(
+                        [
+                            r#type("number"),
+                            min(1),
+                            step(1),
+                            value(subdivisions),
+                            events::on_change(move |evt| {
+                                let subdivisions = evt.value().parse().unwrap_or(subdivisions);
+                                Msg::ToApp(app::Msg::SetGridSubdivisions(subdivisions))
+                            }),
+                        ],
+                        [],
+                    ),
+                ],
+            ),
+        ]
+    }
+
+    // Map a key down to an app message through the shortcuts. Keys typed
+    // into a form field keep their usual meaning there. Holding the space
+    // bar lets dragging pan the view.
+    fn app_msg_for_key_down(&self, evt: &KeyboardEvent) -> Option<app::Msg> {
+        if is_typing_into_field(evt) {
+            return None;
+        }
+        if evt.key() == " " {
+            return Some(app::Msg::SetSpaceHeld(true));
+        }
+        self.shortcuts.command_for_event(evt).cloned()
+    }
+
     fn update_app(&mut self, app_msg: &app::Msg) -> Cmd<Msg> {
         self.app.update(app_msg);
         Cmd::none()
     }
+
+    // Replace the document with one imported from SVG. Anything that
+    // could not be imported gets reported on the console.
+    fn open_svg(&mut self, text: &str) {
+        match document_from_svg(text) {
+            Ok(import) => {
+                for warning in &import.warnings {
+                    console::warn_1(&format!("SVG import: {warning}").into());
+                }
+                self.app.open_document(import.doc);
+            }
+            Err(err) => console::error_1(&format!("SVG import failed: {err}").into()),
+        }
+    }
+}
+
+// The standard keyboard shortcuts. With shift held, the bracket keys
+// report braces on most layouts, so we bind both. The arrow keys nudge the
+// selection by a unit in the document or, with shift, by ten.
+
+const NUDGE_DISTANCE: f64 = 1.0;
+const LARGE_NUDGE_DISTANCE: f64 = 10.0;
+
+fn default_shortcuts() -> Shortcuts<app::Msg> {
+    let mut shortcuts = Shortcuts::new_empty();
+    let primary = |key: &str| KeyChord::new(key).with_primary();
+    let primary_shift = |key: &str| KeyChord::new(key).with_primary().with_shift();
+    shortcuts.bind(primary("z"), app::Msg::Undo);
+    shortcuts.bind(primary_shift("z"), app::Msg::Redo);
+    shortcuts.bind(primary("]"), app::Msg::Arrange(app::Arrange::BringForward));
+    shortcuts.bind(primary("["), app::Msg::Arrange(app::Arrange::SendBackward));
+    for key in ["]", "}"] {
+        shortcuts.bind(
+            primary_shift(key),
+            app::Msg::Arrange(app::Arrange::BringToFront),
+        );
+    }
+    for key in ["[", "{"] {
+        shortcuts.bind(
+            primary_shift(key),
+            app::Msg::Arrange(app::Arrange::SendToBack),
+        );
+    }
+    shortcuts.bind(primary("g"), app::Msg::Group);
+    shortcuts.bind(primary_shift("g"), app::Msg::Ungroup);
+    shortcuts.bind(primary("a"), app::Msg::SelectAll);
+    shortcuts.bind(primary("0"), app::Msg::ResetView);
+    shortcuts.bind(KeyChord::new("Escape"), app::Msg::CancelTracking);
+    for key in ["Delete", "Backspace"] {
+        shortcuts.bind(KeyChord::new(key), app::Msg::DeleteSelection);
+    }
+    for (key, dx, dy) in [
+        ("ArrowLeft", -1.0, 0.0),
+        ("ArrowRight", 1.0, 0.0),
+        ("ArrowUp", 0.0, -1.0),
+        ("ArrowDown", 0.0, 1.0),
+    ] {
+        shortcuts.bind(
+            KeyChord::new(key),
+            app::Msg::Nudge(XYPoint::new(dx * NUDGE_DISTANCE, dy * NUDGE_DISTANCE)),
+        );
+        shortcuts.bind(
+            KeyChord::new(key).with_shift(),
+            app::Msg::Nudge(XYPoint::new(
+                dx * LARGE_NUDGE_DISTANCE,
+                dy * LARGE_NUDGE_DISTANCE,
+            )),
+        );
+    }
+    shortcuts
+}
+
+fn app_msg_for_key_up(evt: &KeyboardEvent) -> Option<app::Msg> {
+    match evt.key().as_str() {
+        " " => Some(app::Msg::SetSpaceHeld(false)),
+        _ => None,
+    }
+}
+
+// Is a key event going to a form field or editable text rather than to
+// the drawing?
+
+fn is_typing_into_field(evt: &KeyboardEvent) -> bool {
+    evt.target().is_some_and(|target| {
+        target.dyn_ref::<HtmlInputElement>().is_some()
+            || target.dyn_ref::<HtmlTextAreaElement>().is_some()
+            || target.dyn_ref::<HtmlSelectElement>().is_some()
+            || target
+                .dyn_ref::<HtmlElement>()
+                .is_some_and(|element| element.is_content_editable())
+    })
+}
+
+// The inner size of the window in pixels if the browser tells us.
+
+fn window_size() -> Option<XYPoint> {
+    let window = sauron::window();
+    let width = window.inner_width().ok()?.as_f64()?;
+    let height = window.inner_height().ok()?.as_f64()?;
+    Some(XYPoint::new(width, height))
+}
+
+// Listen for an event on the window or the document, the way that
+// Window::on_keydown does, for events that Sauron has no helper for. The
+// message does not depend on the event.
+
+fn on_event(target: &EventTarget, event_name: &str, msg: fn() -> Msg) -> Cmd<Msg> {
+    let (mut tx, rx) = mpsc::unbounded();
+    let callback: Closure<dyn FnMut(web_sys::Event)> = Closure::new(move |_| {
+        tx.start_send(msg()).expect("send");
+    });
+    target
+        .add_event_listener_with_callback(event_name, callback.as_ref().unchecked_ref())
+        .expect("add event callback");
+    Cmd::recurring(rx, callback)
+}
+
+// Offer text to the user as a file download. We do this by clicking on a
+// temporary link with a data URL. Failures are silently ignored since
+// there is nothing useful that we can do about them.
+
+fn download_text(file_name: &str, mime_type: &str, text: &str) {
+    let document = sauron::document();
+    if let Ok(link) = document.create_element("a")
+        && let Ok(link) = link.dyn_into::<HtmlElement>()
+    {
+        let href = format!(
+            "data:{mime_type};charset=utf-8,{}",
+            encode_uri_component(text)
+        );
+        let _ = link.set_attribute("href", &href);
+        let _ = link.set_attribute("download", file_name);
+        link.click();
+    }
+}
+
+// Percent-encode everything except the characters that encodeURIComponent
+// leaves alone.
+
+fn encode_uri_component(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'!'
+            | b'~'
+            | b'*'
+            | b'\''
+            | b'('
+            | b')' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+// Read the text of the file chosen in a file input. Reading is
+// asynchronous, so the text comes back in a message.
+
+fn read_chosen_file(evt: &InputEvent, to_msg: fn(Option<String>) -> Msg) -> Cmd<Msg> {
+    let opt_file = evt
+        .event
+        .target()
+        .and_then(|target| target.dyn_into::<HtmlInputElement>().ok())
+        .and_then(|input| input.files())
+        .and_then(|files| files.get(0));
+    match opt_file {
+        Some(file) => Cmd::once(async move {
+            let opt_text = JsFuture::from(file.text())
+                .await
+                .ok()
+                .and_then(|value| value.as_string());
+            to_msg(opt_text)
+        }),
+        None => Cmd::none(),
+    }
 }